@@ -1,29 +1,59 @@
 use anyhow::Context;
 use anyhow::Result;
 
+use crate::health::Health;
+use crate::scrape::ScrapeTargets;
+
+use hyper::header;
+use hyper::service::make_service_fn;
+use hyper::service::service_fn;
+use hyper::Body;
+use hyper::Request;
+use hyper::Response;
+use hyper::Server;
+use hyper::StatusCode;
+
 use log::info;
 
-use prometheus_hyper::Server;
+use prometheus::Encoder;
+use prometheus::Registry;
+use prometheus::TextEncoder;
 
+use std::convert::Infallible;
 use std::net::SocketAddr;
 use std::sync::Arc;
 
 use tokio::sync::mpsc;
 use tokio::sync::Notify;
+use tokio::task::JoinHandle;
 
-type ErrorSender = mpsc::Sender<anyhow::Error>;
+pub(crate) type ErrorSender = mpsc::Sender<anyhow::Error>;
 
+/// Serves metrics on /metrics, liveness on /healthz and readiness on /readyz
+///
+/// Metrics are gathered from the injected registry after the scrape targets are refreshed.
 pub struct ADSBExporter {
     bind_address: SocketAddr,
+    registry: Registry,
+    health: Health,
+    scrape_targets: ScrapeTargets,
     shutdown: Arc<Notify>,
 }
 
 impl ADSBExporter {
-    pub fn new(bind_address: SocketAddr) -> Result<Self> {
+    pub fn new(
+        bind_address: SocketAddr,
+        registry: Registry,
+        health: Health,
+        scrape_targets: ScrapeTargets,
+    ) -> Result<Self> {
         let shutdown = Arc::new(Notify::new());
 
         let exporter = ADSBExporter {
             bind_address,
+            registry,
+            health,
+            scrape_targets,
             shutdown,
         };
 
@@ -33,13 +63,10 @@ impl ADSBExporter {
     async fn run(&self, error_tx: ErrorSender) {
         info!("Starting server on {}", self.bind_address);
 
-        let result = Server::run(
-            Arc::new(prometheus::default_registry().clone()),
-            self.bind_address,
-            self.shutdown.notified(),
-        )
-        .await
-        .with_context(|| format!("Failed to start server on {}", self.bind_address));
+        let result = self
+            .serve()
+            .await
+            .with_context(|| format!("Failed to start server on {}", self.bind_address));
 
         if let Err(e) = result {
             error_tx
@@ -49,12 +76,112 @@ impl ADSBExporter {
         }
     }
 
-    pub async fn start(self, error_tx: ErrorSender) {
+    async fn serve(&self) -> Result<()> {
+        let registry = self.registry.clone();
+        let health = self.health.clone();
+        let scrape_targets = self.scrape_targets.clone();
+
+        let service = make_service_fn(move |_| {
+            let registry = registry.clone();
+            let health = health.clone();
+            let scrape_targets = scrape_targets.clone();
+
+            async move {
+                Ok::<_, Infallible>(service_fn(move |request| {
+                    let registry = registry.clone();
+                    let health = health.clone();
+                    let scrape_targets = scrape_targets.clone();
+
+                    async move {
+                        let response = respond(&request, &registry, &health, &scrape_targets).await;
+
+                        Ok::<_, Infallible>(response)
+                    }
+                }))
+            }
+        });
+
+        let shutdown = self.shutdown.clone();
+
+        Server::try_bind(&self.bind_address)?
+            .serve(service)
+            .with_graceful_shutdown(async move { shutdown.notified().await })
+            .await?;
+
+        Ok(())
+    }
+
+    /// Notify to stop accepting scrapes, the server finishes once in-flight scrapes complete
+    pub fn shutdown(&self) -> Arc<Notify> {
+        self.shutdown.clone()
+    }
+
+    /// Start the server, returns the task that finishes when the server stops
+    pub async fn start(self, error_tx: ErrorSender) -> JoinHandle<()> {
         crate::spawn_named(
             async move {
                 self.run(error_tx).await;
             },
             "adsb_exporter",
-        );
+        )
+    }
+}
+
+/// Route a request to the metrics, liveness or readiness response
+///
+/// /healthz reports the process is serving.  /readyz reports whether every receiver's
+/// aircraft.json and stats.json were fetched and advanced, and every BEAST and SBS source sent
+/// messages, within the readiness window and responds 503 with the stale sources when they were
+/// not.
+pub(crate) async fn respond(
+    request: &Request<Body>,
+    registry: &Registry,
+    health: &Health,
+    scrape_targets: &ScrapeTargets,
+) -> Response<Body> {
+    match request.uri().path() {
+        "/metrics" => {
+            scrape_targets.refresh().await;
+
+            let encoder = TextEncoder::new();
+            let mut buffer = vec![];
+
+            encoder
+                .encode(&registry.gather(), &mut buffer)
+                .expect("Encoding metrics failed, bug?");
+
+            response(StatusCode::OK, encoder.format_type(), buffer)
+        }
+        "/healthz" => response(
+            StatusCode::OK,
+            "application/json",
+            br#"{"status":"ok"}"#.to_vec(),
+        ),
+        "/readyz" => {
+            let readiness = health.readiness();
+
+            let status = if readiness.ready {
+                StatusCode::OK
+            } else {
+                StatusCode::SERVICE_UNAVAILABLE
+            };
+
+            let body = serde_json::to_vec(&readiness).expect("Encoding readiness failed, bug?");
+
+            response(status, "application/json", body)
+        }
+        _ => response(
+            StatusCode::NOT_FOUND,
+            "text/plain; charset=utf-8",
+            b"Not found\n".to_vec(),
+        ),
     }
 }
+
+fn response(status: StatusCode, content_type: &str, body: Vec<u8>) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .header(header::CONTENT_TYPE, content_type)
+        .body(Body::from(body))
+        .expect("Building response failed, bug?")
+}
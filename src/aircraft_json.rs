@@ -1,100 +1,173 @@
-use crate::fetch::fetch;
+use crate::fetch::Fetcher;
+use crate::range::Observation;
+use crate::range::RangeBuckets;
+use crate::range::RangeMetrics;
+use crate::range::Ranges;
 
 use anyhow::Context;
 use anyhow::Result;
 
-use geo::algorithm::bearing::Bearing;
-use geo::prelude::*;
 use geo::Coordinate;
-use geo::Line;
 use geo::Point;
 
-use lazy_static::lazy_static;
-
 use log::debug;
 use log::info;
 
-use prometheus::register_gauge_vec;
-use prometheus::register_int_gauge_vec;
-use prometheus::GaugeVec;
-use prometheus::IntGaugeVec;
-
-use reqwest::Client;
+use prometheus::core::Collector;
+use prometheus::core::Desc;
+use prometheus::proto::MetricFamily;
+use prometheus::Gauge;
+use prometheus::Opts;
 
 use serde_json::json;
 use serde_json::Value;
 
-use std::collections::HashMap;
 use std::sync::Arc;
 use std::time::Duration;
 
 use tokio::sync::RwLock;
 use tokio::time::sleep;
 
-lazy_static! {
-    static ref RECENT_OBSERVED: GaugeVec = register_gauge_vec!(
-        "adsb_aircraft_observed_recent",
-        "Number of aircraft observed in the last minute",
-        &["frequency"],
-    )
-    .unwrap();
-    static ref RECENT_POSITIONS: GaugeVec = register_gauge_vec!(
-        "adsb_aircraft_with_position_recent",
-        "Number of aircraft observed with a position in the last minute",
-        &["frequency"],
-    )
-    .unwrap();
-    static ref RECENT_MLAT: GaugeVec = register_gauge_vec!(
-        "adsb_aircraft_mlat_recent",
-        "Number of aircraft observed with a position determined by multilateration in the last minute",
-        &["frequency"],
-    )
-    .unwrap();
-    static ref OBSERVATIONS: IntGaugeVec = register_int_gauge_vec!(
-        "adsb_aircraft_observations_recent",
-        "Number of aircraft positions observed by range and bearing in the last minute",
-        &["frequency", "bearing", "distance"],
-    )
-    .unwrap();
-    static ref RANGES: GaugeVec = register_gauge_vec!(
-        "adsb_aircraft_ranges_recent",
-        "Maximum range to an observed aircraft by bearing in the last minute",
-        &["frequency", "bearing"],
-    )
-    .unwrap();
+/// Metrics exported from aircraft.json
+#[derive(Clone)]
+pub(crate) struct AircraftMetrics {
+    recent_observed: Gauge,
+    recent_positions: Gauge,
+    recent_mlat: Gauge,
+    ranges: RangeMetrics,
 }
 
+impl AircraftMetrics {
+    /// Unregistered metrics of the named `receiver` listening on `frequency`
+    pub(crate) fn new(receiver: &str, frequency: &str) -> Result<Self> {
+        Ok(AircraftMetrics {
+            recent_observed: Gauge::with_opts(
+                Opts::new(
+                    "adsb_aircraft_observed_recent",
+                    "Number of aircraft observed in the recent window",
+                )
+                .const_label("receiver", receiver)
+                .const_label("frequency", frequency),
+            )?,
+            recent_positions: Gauge::with_opts(
+                Opts::new(
+                    "adsb_aircraft_with_position_recent",
+                    "Number of aircraft observed with a position in the recent window",
+                )
+                .const_label("receiver", receiver)
+                .const_label("frequency", frequency),
+            )?,
+            recent_mlat: Gauge::with_opts(
+                Opts::new(
+                    "adsb_aircraft_mlat_recent",
+                    "Number of aircraft observed with a position determined by multilateration in the recent window",
+                )
+                .const_label("receiver", receiver)
+                .const_label("frequency", frequency),
+            )?,
+            ranges: RangeMetrics::new(
+                Opts::new(
+                    "adsb_aircraft_observations_recent",
+                    "Number of aircraft positions observed by range and bearing in the recent window",
+                )
+                .const_label("receiver", receiver)
+                .const_label("frequency", frequency),
+                Opts::new(
+                    "adsb_aircraft_ranges_recent",
+                    "Maximum range in meters to an observed aircraft by bearing in the recent window",
+                )
+                .const_label("receiver", receiver)
+                .const_label("frequency", frequency),
+            )?,
+        })
+    }
+
+    fn collectors(&self) -> Vec<&dyn Collector> {
+        vec![
+            &self.recent_observed,
+            &self.recent_positions,
+            &self.recent_mlat,
+            &self.ranges,
+        ]
+    }
+}
+
+impl Collector for AircraftMetrics {
+    fn desc(&self) -> Vec<&Desc> {
+        self.collectors()
+            .into_iter()
+            .flat_map(|c| c.desc())
+            .collect()
+    }
+
+    fn collect(&self) -> Vec<MetricFamily> {
+        self.collectors()
+            .into_iter()
+            .flat_map(|c| c.collect())
+            .collect()
+    }
+}
+
+#[derive(Clone)]
 pub struct AircraftJson {
-    client: Client,
+    fetcher: Fetcher,
+    metrics: AircraftMetrics,
+    receiver: String,
     frequency: String,
-    url: String,
     interval: Duration,
     position: Arc<RwLock<Option<Coordinate<f64>>>>,
+    recent: Duration,
+    range: RangeBuckets,
 }
 
 impl AircraftJson {
     pub fn new(
-        client: Client,
+        fetcher: Fetcher,
         frequency: u32,
-        url: String,
         interval: Duration,
         position: Arc<RwLock<Option<Coordinate<f64>>>>,
+        recent: Duration,
+        range: RangeBuckets,
     ) -> AircraftJson {
+        let receiver = fetcher.receiver().to_string();
         let frequency = frequency.to_string();
+        let metrics = AircraftMetrics::new(&receiver, &frequency)
+            .expect("Receiver metrics are invalid, bug?");
 
         AircraftJson {
-            client,
+            fetcher,
+            metrics,
+            receiver,
             frequency,
-            url,
             interval,
             position,
+            recent,
+            range,
         }
     }
 
+    pub(crate) fn fetcher(&self) -> &Fetcher {
+        &self.fetcher
+    }
+
+    pub(crate) fn frequency(&self) -> &str {
+        &self.frequency
+    }
+
+    /// Metrics updated by run(), register them to export them
+    pub(crate) fn metrics(&self) -> &AircraftMetrics {
+        &self.metrics
+    }
+
+    /// Update `metrics` instead, like the metrics of the previous watcher of the receiver
+    pub(crate) fn with_metrics(self, metrics: AircraftMetrics) -> Self {
+        AircraftJson { metrics, ..self }
+    }
+
     pub async fn run(&self) {
         loop {
-            if let Some(data) = fetch(&self.client, &self.url).await {
-                match self.update_aircraft(data).await {
+            if let Some(data) = self.fetcher.fetch().await {
+                match self.update_aircraft(&self.metrics, data).await {
                     Ok(_) => (),
                     Err(e) => {
                         debug!("error updating aircraft {:?}", e);
@@ -106,7 +179,11 @@ impl AircraftJson {
         }
     }
 
-    async fn update_aircraft(&self, data: Value) -> Result<()> {
+    pub(crate) async fn update_aircraft(
+        &self,
+        metrics: &AircraftMetrics,
+        data: Value,
+    ) -> Result<()> {
         let aircrafts = data
             .get("aircraft")
             .context("missing aircraft data")?
@@ -115,33 +192,17 @@ impl AircraftJson {
 
         let observed = aircrafts
             .iter()
-            .filter(|a| {
-                if let Some(seen) = a.get("seen") {
-                    seen.as_f64().unwrap_or(f64::INFINITY) < 60.0
-                } else {
-                    false
-                }
-            })
+            .filter(|a| self.seen_recently(a, "seen"))
             .count();
 
-        RECENT_OBSERVED
-            .with_label_values(&[&self.frequency])
-            .set(observed as f64);
+        metrics.recent_observed.set(observed as f64);
 
         let positions = aircrafts
             .iter()
-            .filter(|a| {
-                if let Some(seen) = a.get("seen_pos") {
-                    seen.as_f64().unwrap_or(f64::INFINITY) < 60.0
-                } else {
-                    false
-                }
-            })
+            .filter(|a| self.seen_recently(a, "seen_pos"))
             .count();
 
-        RECENT_POSITIONS
-            .with_label_values(&[&self.frequency])
-            .set(positions as f64);
+        metrics.recent_positions.set(positions as f64);
 
         let lat = json!("lat");
         let empty_vec = vec![];
@@ -149,11 +210,7 @@ impl AircraftJson {
         let mlat = aircrafts
             .iter()
             .filter(|a| {
-                let located = if let Some(seen) = a.get("seen_pos") {
-                    seen.as_f64().unwrap_or(f64::INFINITY) < 60.0
-                } else {
-                    false
-                };
+                let located = self.seen_recently(a, "seen_pos");
 
                 let mlat = if let Some(mlat) = a.get("mlat") {
                     mlat.as_array().unwrap_or(&empty_vec).contains(&lat)
@@ -165,17 +222,15 @@ impl AircraftJson {
             })
             .count();
 
-        RECENT_MLAT
-            .with_label_values(&[&self.frequency])
-            .set(mlat as f64);
+        metrics.recent_mlat.set(mlat as f64);
 
         let receiver_position = self.position.read().await;
         let receiver_position = match *receiver_position {
             Some(position) => position,
             None => {
                 info!(
-                    "Receiver for {}Hz position unknown, maybe receiver.json hasn't been fetched?",
-                    self.frequency
+                    "Receiver {} position unknown, maybe receiver.json hasn't been fetched?",
+                    self.receiver
                 );
                 return Ok(());
             }
@@ -183,83 +238,36 @@ impl AircraftJson {
 
         let receiver_point: Point<f64> = receiver_position.into();
 
-        let mut observations = HashMap::with_capacity(positions);
-        let mut ranges: HashMap<String, Vec<f64>> = HashMap::with_capacity(positions);
+        let mut ranges = Ranges::default();
 
         aircrafts
             .iter()
-            .filter(|a| {
-                if let Some(seen) = a.get("seen_pos") {
-                    seen.as_f64().unwrap_or(f64::INFINITY) < 60.0
-                } else {
-                    false
-                }
-            })
-            .for_each(|a| {
-                let aircraft_lat = a.get("lat").unwrap().as_f64().unwrap();
-                let aircraft_lon = a.get("lon").unwrap().as_f64().unwrap();
-
-                let aircraft_position = Coordinate {
-                    x: aircraft_lat,
-                    y: aircraft_lon,
-                };
-
-                let distance = Line::new(aircraft_position, receiver_position).haversine_length();
-                let distance_bucket = (1 + (distance / 80_000.0) as u32) * 80_000;
-                let distance_bucket = match distance_bucket {
-                    0..=400_000 => distance_bucket.to_string(),
-                    _ => "> 400000".to_string(),
-                };
+            .filter(|a| self.seen_recently(a, "seen_pos"))
+            .filter_map(|a| {
+                let aircraft_lat = a.get("lat")?.as_f64()?;
+                let aircraft_lon = a.get("lon")?.as_f64()?;
 
-                let bearing = receiver_point.bearing(aircraft_position.into());
-                // The documentation says "North is 0° and East is 90°" but this doesn't seem to
-                // match the results, so we need to rotate by 90, then add positive bearing.
-                let bearing = (450.0 + bearing) % 360.0;
-
-                let bearing_bucket = (((bearing + 11.25) / 22.5).floor() * 22.5) % 360.0;
-                let bearing_bucket = bearing_bucket.to_string();
-
-                let key = (distance_bucket, bearing_bucket.clone());
-                let previous_count = observations.get(&key).unwrap_or(&0);
-                let count = *previous_count + 1;
-                observations.insert(key, count);
-
-                let bearing_ranges = if let Some(r) = ranges.get(&bearing_bucket) {
-                    let mut r = r.clone();
-                    r.push(distance);
-                    r
-                } else {
-                    vec![distance]
-                };
-                ranges.insert(bearing_bucket, bearing_ranges);
-            });
-
-        observations
-            .iter()
-            .for_each(|((distance, bearing), count)| {
-                OBSERVATIONS
-                    .with_label_values(&[&self.frequency, bearing, distance])
-                    .set(*count)
+                Some(Point::new(aircraft_lon, aircraft_lat))
+            })
+            .for_each(|aircraft_position| {
+                ranges.add(&Observation::new(
+                    receiver_point,
+                    aircraft_position,
+                    &self.range,
+                ));
             });
 
-        ranges.iter().for_each(|(bearing, bearing_ranges)| {
-            if let Some(maximum) =
-                bearing_ranges.iter().reduce(
-                    |maximum, range| {
-                        if maximum >= range {
-                            range
-                        } else {
-                            maximum
-                        }
-                    },
-                )
-            {
-                RANGES
-                    .with_label_values(&[&self.frequency, bearing])
-                    .set(*maximum)
-            }
-        });
+        metrics.ranges.update(&ranges)?;
 
         Ok(())
     }
+
+    /// True when `field` of `aircraft`, the seconds since it was last seen, is within the recent
+    /// window
+    fn seen_recently(&self, aircraft: &Value, field: &str) -> bool {
+        match aircraft.get(field).and_then(Value::as_f64) {
+            Some(seen) => seen < self.recent.as_secs_f64(),
+            None => false,
+        }
+    }
 }
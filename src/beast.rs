@@ -1,14 +1,88 @@
 mod aircraft;
+mod avr;
+mod capture;
 mod client;
 mod codec;
+mod combiner;
+pub mod cpr;
+pub mod crc;
+pub mod encoder;
+mod filter;
 mod message;
 mod parser;
+mod sbs;
+mod server;
+mod simulator;
+mod source;
+mod statistics;
+mod table;
+mod tracker;
 
 pub use aircraft::Aircraft;
+pub use aircraft::Position;
+pub use avr::AvrCodec;
+pub use capture::CaptureCodec;
+pub use capture::Captured;
 pub use client::Client;
 pub use codec::Codec;
+pub use combiner::Combiner;
+pub use combiner::CombinerMetrics;
+pub use combiner::Feed;
+pub use combiner::Merged;
+pub use combiner::Reception;
+pub use combiner::Receptions;
+pub use filter::parse_icao;
+pub use filter::Filter;
+pub use filter::Kind;
 pub use message::*;
 pub use parser::Parser;
+pub use sbs::SbsWriter;
+pub use server::Connection;
+pub use server::Connections;
+pub use server::Input;
+pub use server::SbsServer;
+pub use server::Server;
+pub use server::ServerMetrics;
+pub use simulator::SimulatedAircraft;
+pub use simulator::Simulator;
+pub use source::open_source;
+pub use source::Compression;
+pub use source::Source;
+pub use statistics::parse_interval;
+pub use statistics::Statistics;
+pub use table::rows;
+pub use table::Row;
+pub use table::Sort;
+pub use table::COLUMNS;
+pub use tracker::Tracker;
 
+#[cfg(test)]
+mod test_avr;
+#[cfg(test)]
+mod test_capture;
+#[cfg(test)]
+mod test_codec;
+#[cfg(test)]
+mod test_combiner;
+#[cfg(test)]
+mod test_cpr;
+#[cfg(test)]
+mod test_crc;
+#[cfg(test)]
+mod test_encoder;
+#[cfg(test)]
+mod test_filter;
 #[cfg(test)]
 mod test_parser;
+#[cfg(test)]
+mod test_sbs;
+#[cfg(test)]
+mod test_server;
+#[cfg(test)]
+mod test_simulator;
+#[cfg(test)]
+mod test_source;
+#[cfg(test)]
+mod test_statistics;
+#[cfg(test)]
+mod test_table;
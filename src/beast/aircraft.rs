@@ -1,2 +1,61 @@
+use crate::beast::cpr::CPRFrame;
+
+use std::time::Instant;
+
+/// A decoded aircraft position in degrees
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Position {
+    pub latitude: f64,
+    pub longitude: f64,
+}
+
+/// State of an aircraft tracked from decoded messages
 #[derive(Debug)]
-pub struct Aircraft {}
+pub struct Aircraft {
+    pub icao: String,
+    /// Number of messages received from this aircraft
+    pub messages: u64,
+    pub last_seen: Instant,
+    /// Last decoded position
+    pub position: Option<Position>,
+    /// Last time a position was decoded
+    pub last_position: Option<Instant>,
+    pub call_sign: Option<String>,
+    /// Mode A code, one octal digit per hex nibble so 7700 is 0x7700
+    pub squawk: Option<u16>,
+    /// Altitude in feet
+    pub altitude: Option<i32>,
+    /// Ground speed in knots
+    pub ground_speed: Option<f64>,
+    /// Track over the ground in degrees clockwise from true north
+    pub track: Option<f64>,
+    /// Vertical rate in feet per minute
+    pub vertical_rate: Option<i32>,
+    pub on_ground: Option<bool>,
+    /// Signal level of the last message with a known signal level in dBFS
+    pub signal_level: Option<f64>,
+    pub(crate) even_cpr: Option<CPRFrame>,
+    pub(crate) odd_cpr: Option<CPRFrame>,
+}
+
+impl Aircraft {
+    pub fn new(icao: String, now: Instant) -> Self {
+        Aircraft {
+            icao,
+            messages: 0,
+            last_seen: now,
+            position: None,
+            last_position: None,
+            call_sign: None,
+            squawk: None,
+            altitude: None,
+            ground_speed: None,
+            track: None,
+            vertical_rate: None,
+            on_ground: None,
+            signal_level: None,
+            even_cpr: None,
+            odd_cpr: None,
+        }
+    }
+}
@@ -0,0 +1,184 @@
+use anyhow::anyhow;
+use anyhow::Error;
+
+use bytes::BytesMut;
+
+use crate::beast::parser::decode_frame;
+use crate::beast::Message;
+
+use log::debug;
+
+use nom::branch::alt;
+use nom::bytes::complete::tag;
+use nom::bytes::complete::take_while_m_n;
+use nom::combinator::all_consuming;
+use nom::combinator::map;
+use nom::combinator::map_res;
+use nom::combinator::opt;
+use nom::multi::many1;
+use nom::sequence::terminated;
+use nom::sequence::tuple;
+use nom::IResult;
+
+use std::fmt::Write;
+
+use tokio_util::codec::Decoder;
+use tokio_util::codec::Encoder;
+
+/// Longest AVR line, a `<` line with a long frame
+const MAXIMUM_LINE_LENGTH: usize = 1 + 12 + 2 + 28 + 1;
+
+/// Decodes and encodes AVR text frames like `*8D4840D6202CC371C32CE0576098;`
+///
+/// Frames with a 12MHz timestamp start with `@` and frames with a timestamp and signal level
+/// start with `<`.  AVR frames without a signal level have a signal level of -∞ dBFS, like a
+/// BEAST frame with a zero signal byte, and frames without a timestamp have a timestamp of 0.
+#[derive(Default)]
+pub struct AvrCodec {}
+
+impl AvrCodec {
+    pub fn new() -> AvrCodec {
+        AvrCodec {}
+    }
+}
+
+impl Decoder for AvrCodec {
+    type Item = Message;
+    type Error = Error;
+
+    fn decode(&mut self, buf: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        loop {
+            let end = match buf.iter().position(|b| *b == b'\n') {
+                Some(end) => end,
+                None => {
+                    if buf.len() > MAXIMUM_LINE_LENGTH * 2 {
+                        return Err(anyhow!("AVR line too long, not an AVR stream?"));
+                    }
+
+                    return Ok(None);
+                }
+            };
+
+            let line = buf.split_to(end + 1);
+
+            let line = match std::str::from_utf8(&line) {
+                Ok(line) => line.trim(),
+                Err(_) => {
+                    debug!("skipping non-UTF-8 AVR line");
+                    continue;
+                }
+            };
+
+            if line.is_empty() {
+                continue;
+            }
+
+            match avr_line(line) {
+                Ok((_, message)) => return Ok(Some(message)),
+                Err(e) => debug!("skipping invalid AVR line {:?}: {}", line, e),
+            }
+        }
+    }
+
+    fn decode_eof(&mut self, buf: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        match self.decode(buf)? {
+            Some(message) => Ok(Some(message)),
+            None => {
+                if !buf.is_empty() {
+                    // final line without a newline
+                    buf.extend_from_slice(b"\n");
+                    return self.decode(buf);
+                }
+
+                Ok(None)
+            }
+        }
+    }
+}
+
+impl Encoder<&Message> for AvrCodec {
+    type Error = Error;
+
+    fn encode(&mut self, message: &Message, buf: &mut BytesMut) -> Result<(), Self::Error> {
+        let mut line = String::with_capacity(MAXIMUM_LINE_LENGTH + 1);
+
+        // A zero 12MHz counter is a frame without a timestamp, like a BEAST frame
+        let timestamp = (message.timestamp * 12.0).round();
+        let timestamp = if timestamp.is_finite() && timestamp >= 1.0 {
+            Some(timestamp as u64 & 0xffff_ffff_ffff)
+        } else {
+            None
+        };
+
+        if message.signal_level.is_finite() {
+            // inverse of the `<` signal level in avr_line
+            let signal = (255.0 * 10f64.powf(message.signal_level / 20.0))
+                .round()
+                .clamp(0.0, 255.0) as u8;
+
+            write!(line, "<{:012X}{:02X}", timestamp.unwrap_or(0), signal)?;
+        } else if let Some(timestamp) = timestamp {
+            write!(line, "@{:012X}", timestamp)?;
+        } else {
+            line.push('*');
+        }
+
+        message
+            .frame
+            .iter()
+            .try_for_each(|b| write!(line, "{:02X}", b))?;
+
+        line.push_str(";\n");
+
+        buf.extend_from_slice(line.as_bytes());
+
+        Ok(())
+    }
+}
+
+fn avr_line(input: &str) -> IResult<&str, Message> {
+    all_consuming(alt((
+        map(
+            tuple((tag("*"), terminated(avr_frame, opt(tag(";"))))),
+            |(_, frame)| decode_frame(0.0, f64::NEG_INFINITY, frame),
+        ),
+        map(
+            tuple((tag("@"), timestamp, terminated(avr_frame, opt(tag(";"))))),
+            |(_, timestamp, frame)| decode_frame(timestamp, f64::NEG_INFINITY, frame),
+        ),
+        map(
+            tuple((
+                tag("<"),
+                timestamp,
+                hex_byte,
+                terminated(avr_frame, opt(tag(";"))),
+            )),
+            |(_, timestamp, signal, frame)| {
+                let signal = signal as f64 / 255.0;
+                decode_frame(timestamp, 10.0 * (signal * signal).log10(), frame)
+            },
+        ),
+    )))(input)
+}
+
+fn avr_frame(input: &str) -> IResult<&str, Vec<u8>> {
+    map_res(many1(hex_byte), |frame: Vec<u8>| match frame.len() {
+        2 | 7 | 14 => Ok(frame),
+        length => Err(anyhow!("invalid AVR frame length {}", length)),
+    })(input)
+}
+
+fn hex_byte(input: &str) -> IResult<&str, u8> {
+    map_res(
+        take_while_m_n(2, 2, |c: char| c.is_ascii_hexdigit()),
+        |hex| u8::from_str_radix(hex, 16),
+    )(input)
+}
+
+// 12MHz clock, converted to µs like parser::header_timestamp
+fn timestamp(input: &str) -> IResult<&str, f64> {
+    map_res(
+        take_while_m_n(12, 12, |c: char| c.is_ascii_hexdigit()),
+        |hex| u64::from_str_radix(hex, 16).map(|ts| ts as f64 / 12.0),
+    )(input)
+}
@@ -0,0 +1,126 @@
+use anyhow::Error;
+
+use bytes::Buf;
+use bytes::BufMut;
+use bytes::BytesMut;
+
+use crate::beast::codec::put_escaped;
+use crate::beast::codec::resynchronize;
+use crate::beast::parser::sep_or_not;
+use crate::beast::Codec;
+use crate::beast::Message;
+use crate::beast::Parser;
+
+use log::debug;
+
+use nom::bytes::streaming::tag;
+use nom::combinator::map;
+use nom::combinator::opt;
+use nom::multi::fold_many_m_n;
+use nom::sequence::preceded;
+use nom::Err;
+use nom::IResult;
+
+use std::time::Duration;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+use tokio_util::codec::Decoder;
+use tokio_util::codec::Encoder;
+
+/// BEAST message type of a wall-clock annotation, not used by receivers
+const ANNOTATION: u8 = b'W';
+
+/// A message read from a capture with the wall-clock time it was recorded
+#[derive(Debug, PartialEq)]
+pub struct Captured {
+    /// Time the message was received when recorded, None for unannotated BEAST data
+    pub received: Option<SystemTime>,
+    pub message: Message,
+}
+
+/// Reads and writes BEAST captures annotated with wall-clock receive times
+///
+/// Each frame is preceded by an annotation frame of type `W` holding the receive time as eight
+/// big-endian bytes of microseconds since the UNIX epoch, escaped like other BEAST data.  Plain
+/// BEAST data decodes with no receive time, and BEAST readers resynchronize past annotations.
+#[derive(Default)]
+pub struct CaptureCodec {
+    parser: Parser,
+    codec: Codec,
+}
+
+impl CaptureCodec {
+    pub fn new() -> Self {
+        CaptureCodec::default()
+    }
+
+    fn parse<'a>(&'a self, input: &'a [u8]) -> IResult<&'a [u8], Captured> {
+        let (input, received) = opt(annotation)(input)?;
+        let (input, message) = self.parser.parse(input)?;
+
+        Ok((input, Captured { received, message }))
+    }
+}
+
+fn annotation(input: &[u8]) -> IResult<&[u8], SystemTime> {
+    map(
+        preceded(
+            tag(&[0x1a, ANNOTATION][..]),
+            fold_many_m_n(8, 8, sep_or_not, || 0, |t, c| (t << 8) | c as u64),
+        ),
+        |micros| UNIX_EPOCH + Duration::from_micros(micros),
+    )(input)
+}
+
+impl Decoder for CaptureCodec {
+    type Item = Captured;
+    type Error = Error;
+
+    fn decode(&mut self, buf: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        loop {
+            let length = buf.len();
+
+            match self.parse(buf) {
+                Ok((remaining, captured)) => {
+                    let consumed = length - remaining.len();
+                    buf.advance(consumed);
+
+                    return Ok(Some(captured));
+                }
+                Err(Err::Incomplete(_)) => return Ok(None),
+                Err(Err::Error(_)) | Err(Err::Failure(_)) => {
+                    let skip = resynchronize(buf);
+
+                    debug!("skipping {} bytes to resynchronize", skip);
+
+                    buf.advance(skip);
+                }
+            }
+        }
+    }
+}
+
+impl Encoder<(SystemTime, &Message)> for CaptureCodec {
+    type Error = Error;
+
+    fn encode(
+        &mut self,
+        (received, message): (SystemTime, &Message),
+        buf: &mut BytesMut,
+    ) -> Result<(), Self::Error> {
+        let micros = received
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_micros() as u64;
+
+        buf.put_u8(0x1a);
+        buf.put_u8(ANNOTATION);
+        micros
+            .to_be_bytes()
+            .iter()
+            .for_each(|b| put_escaped(buf, *b));
+
+        self.codec.encode(message, buf)
+    }
+}
@@ -2,11 +2,10 @@ use anyhow::Context;
 use anyhow::Result;
 
 use crate::beast::codec::Codec;
+use crate::beast::Message;
 
 use futures_util::StreamExt;
 
-use log::debug;
-
 use tokio::net::TcpStream;
 
 use tokio_util::codec::Framed;
@@ -29,11 +28,13 @@ impl Client {
         Ok(client)
     }
 
-    pub async fn run(&mut self) {
-        while let Some(result) = self.reader.next().await {
-            debug!("result: {:?}", result);
-            panic!();
-        }
+    pub fn address(&self) -> &str {
+        &self.address
+    }
+
+    /// Read the next message from the server, returns None when the connection is closed
+    pub async fn read(&mut self) -> Option<Result<Message>> {
+        self.reader.next().await
     }
 }
 
@@ -1,17 +1,20 @@
 use anyhow::Error;
 
-use bytes::Bytes;
+use bytes::Buf;
+use bytes::BufMut;
 use bytes::BytesMut;
 
 use crate::beast::Message;
 use crate::beast::Parser;
 
-use nom::Err;
+use log::debug;
 
-use std::borrow::Borrow;
+use nom::Err;
 
 use tokio_util::codec::Decoder;
+use tokio_util::codec::Encoder;
 
+#[derive(Default)]
 pub struct Codec {
     parser: Parser,
 }
@@ -28,22 +31,92 @@ impl Decoder for Codec {
     type Error = Error;
 
     fn decode(&mut self, buf: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
-        let bytes = buf.split_to(buf.len());
-        let input = bytes.borrow();
+        loop {
+            let length = buf.len();
+
+            match self.parser.parse(buf) {
+                Ok((remaining, message)) => {
+                    let consumed = length - remaining.len();
+                    buf.advance(consumed);
+
+                    return Ok(Some(message));
+                }
+                Err(Err::Incomplete(_)) => return Ok(None),
+                Err(Err::Error(_)) | Err(Err::Failure(_)) => {
+                    // Not the start of a frame we understand, skip to the next frame
+                    let skip = resynchronize(buf);
 
-        match self.parser.parse(input) {
-            Ok((input, message)) => {
-                buf.extend_from_slice(&Bytes::copy_from_slice(input));
+                    debug!("skipping {} bytes to resynchronize", skip);
 
-                Ok(Some(message))
+                    buf.advance(skip);
+                }
             }
-            Err(Err::Incomplete(_)) => {
-                buf.extend_from_slice(&Bytes::copy_from_slice(input));
+        }
+    }
+}
+
+impl Encoder<&Message> for Codec {
+    type Error = Error;
+
+    fn encode(&mut self, message: &Message, buf: &mut BytesMut) -> Result<(), Self::Error> {
+        let message_format = match message.frame.len() {
+            2 => b'1',
+            7 => b'2',
+            14 => b'3',
+            length => return Err(anyhow::anyhow!("invalid frame length {}", length)),
+        };
+
+        buf.reserve(2 * (8 + message.frame.len()) + 2);
+
+        buf.put_u8(0x1a);
+        buf.put_u8(message_format);
+
+        let timestamp = (message.timestamp * 12.0).round() as u64;
+        timestamp.to_be_bytes()[2..]
+            .iter()
+            .for_each(|b| put_escaped(buf, *b));
+
+        put_escaped(buf, signal_byte(message.signal_level));
 
-                Ok(None)
+        message.frame.iter().for_each(|b| put_escaped(buf, *b));
+
+        Ok(())
+    }
+}
+
+/// Number of bytes before the next frame start after the first byte of `buf`
+///
+/// A doubled 0x1a is an escaped data byte, like in an unknown frame's payload, so it is not
+/// mistaken for the start of a frame.
+pub(crate) fn resynchronize(buf: &[u8]) -> usize {
+    let mut i = 1;
+
+    while i < buf.len() {
+        if buf[i] == 0x1a {
+            if buf.get(i + 1) != Some(&0x1a) {
+                return i;
             }
-            Err(Err::Error(e)) => panic!("impossible error! {:?}", e),
-            Err(Err::Failure(_)) => panic!("impossible failure!"),
+
+            i += 1;
         }
+
+        i += 1;
     }
+
+    buf.len()
+}
+
+pub(crate) fn put_escaped(buf: &mut BytesMut, b: u8) {
+    if b == 0x1a {
+        buf.put_u8(0x1a);
+    }
+
+    buf.put_u8(b);
+}
+
+// Inverse of parser::header_signal
+fn signal_byte(signal_level: f64) -> u8 {
+    (255.0 * 10.0_f64.powf(signal_level / 20.0))
+        .round()
+        .clamp(0.0, 255.0) as u8
 }
@@ -0,0 +1,295 @@
+use anyhow::Result;
+
+use crate::beast::Message;
+
+use log::debug;
+
+use prometheus::core::Collector;
+use prometheus::core::Desc;
+use prometheus::proto::MetricFamily;
+use prometheus::IntCounterVec;
+use prometheus::Opts;
+
+use std::collections::HashMap;
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::sync::broadcast;
+use tokio::sync::mpsc;
+use tokio::time::sleep_until;
+use tokio::time::Instant;
+
+/// Metrics of messages merged from each receiver
+#[derive(Clone)]
+pub struct CombinerMetrics {
+    received: IntCounterVec,
+    unique: IntCounterVec,
+    overlap: IntCounterVec,
+    best_signal: IntCounterVec,
+}
+
+impl CombinerMetrics {
+    /// Unregistered metrics of merged messages
+    pub fn new() -> Result<Self> {
+        Ok(CombinerMetrics {
+            received: IntCounterVec::new(
+                Opts::new(
+                    "adsb_beast_receiver_messages_total",
+                    "Number of Mode S messages heard by a receiver",
+                ),
+                &["receiver"],
+            )?,
+            unique: IntCounterVec::new(
+                Opts::new(
+                    "adsb_beast_receiver_unique_messages_total",
+                    "Number of Mode S messages heard only by a receiver",
+                ),
+                &["receiver"],
+            )?,
+            overlap: IntCounterVec::new(
+                Opts::new(
+                    "adsb_beast_receiver_overlap_messages_total",
+                    "Number of Mode S messages heard by a receiver and at least one other receiver",
+                ),
+                &["receiver"],
+            )?,
+            best_signal: IntCounterVec::new(
+                Opts::new(
+                    "adsb_beast_receiver_best_signal_messages_total",
+                    "Number of Mode S messages where a receiver had the best signal level",
+                ),
+                &["receiver"],
+            )?,
+        })
+    }
+
+    /// Remove the series of a receiver that is gone
+    pub fn forget(&self, receiver: &str) {
+        for metric in [
+            &self.received,
+            &self.unique,
+            &self.overlap,
+            &self.best_signal,
+        ] {
+            crate::series::retain(metric, |labels| labels.get("receiver") != Some(&receiver));
+        }
+    }
+
+    fn collectors(&self) -> Vec<&dyn Collector> {
+        vec![
+            &self.received,
+            &self.unique,
+            &self.overlap,
+            &self.best_signal,
+        ]
+    }
+}
+
+impl Collector for CombinerMetrics {
+    fn desc(&self) -> Vec<&Desc> {
+        self.collectors()
+            .into_iter()
+            .flat_map(|c| c.desc())
+            .collect()
+    }
+
+    fn collect(&self) -> Vec<MetricFamily> {
+        self.collectors()
+            .into_iter()
+            .flat_map(|c| c.collect())
+            .collect()
+    }
+}
+
+/// A message heard by a single receiver
+#[derive(Debug)]
+pub struct Reception {
+    pub receiver: String,
+    pub message: Message,
+}
+
+/// A message and every receiver that heard it within the merge window
+#[derive(Debug)]
+pub struct Merged {
+    /// The copy of the message with the best signal level as that receiver heard it, including
+    /// its timestamp, so timestamps of merged messages come from different receivers' clocks
+    pub message: Message,
+    /// Receivers that heard the message, the receiver with the best signal level first
+    pub receivers: Vec<String>,
+}
+
+/// Merged messages for metrics and BEAST clients
+pub type Feed = broadcast::Sender<Arc<Merged>>;
+
+/// Messages from each receiver for a Combiner
+pub type Receptions = mpsc::Sender<Reception>;
+
+struct Pending {
+    first_seen: Instant,
+    message: Message,
+    receivers: Vec<String>,
+}
+
+/// Merges messages from multiple receivers, removing identical Mode S frames heard within a window
+///
+/// A zero window publishes every message as it arrives, for when there is only one receiver.
+pub struct Combiner {
+    window: Duration,
+    metrics: CombinerMetrics,
+    pending: HashMap<Vec<u8>, Pending>,
+    expiry: VecDeque<(Instant, Vec<u8>)>,
+}
+
+impl Combiner {
+    pub fn new(window: Duration, metrics: CombinerMetrics) -> Self {
+        Combiner {
+            window,
+            metrics,
+            pending: HashMap::new(),
+            expiry: VecDeque::new(),
+        }
+    }
+
+    pub async fn run(mut self, mut receptions: mpsc::Receiver<Reception>, feed: Feed) {
+        loop {
+            let deadline = match self.expiry.front() {
+                Some((first_seen, _)) => *first_seen + self.window,
+                None => Instant::now() + self.window,
+            };
+
+            tokio::select! {
+                reception = receptions.recv() => match reception {
+                    Some(reception) => {
+                        for merged in self.add(reception, Instant::now()) {
+                            publish(&feed, merged);
+                        }
+                    }
+                    None => break,
+                },
+                _ = sleep_until(deadline), if !self.expiry.is_empty() => {
+                    for merged in self.expire(Instant::now()) {
+                        publish(&feed, merged);
+                    }
+                },
+            }
+        }
+
+        debug!("all receivers closed, flushing pending messages");
+
+        for merged in self.expire(Instant::now() + self.window) {
+            publish(&feed, merged);
+        }
+    }
+
+    /// Add a message heard by a receiver, returning any messages that are ready
+    pub fn add(&mut self, reception: Reception, now: Instant) -> Vec<Merged> {
+        let Reception { receiver, message } = reception;
+
+        // Mode A/C replies are short and repeat constantly, identical frames are not duplicates
+        if message.downlink_format().is_none() {
+            return vec![Merged {
+                message,
+                receivers: vec![receiver],
+            }];
+        }
+
+        self.metrics.received.with_label_values(&[&receiver]).inc();
+
+        if self.window.is_zero() {
+            return vec![self.merge(Pending {
+                first_seen: now,
+                message,
+                receivers: vec![receiver],
+            })];
+        }
+
+        let mut ready = vec![];
+
+        if let Some(pending) = self.pending.get(&message.frame) {
+            if pending.receivers.contains(&receiver) {
+                // a receiver hearing the same frame twice is a new transmission
+                let pending = self.pending.remove(&message.frame).unwrap();
+                ready.push(self.merge(pending));
+            }
+        }
+
+        match self.pending.get_mut(&message.frame) {
+            Some(pending) => {
+                if message.signal_level > pending.message.signal_level {
+                    // Take the whole copy, the first receiver's timestamp is from a different clock
+                    pending.message = message;
+                    pending.receivers.insert(0, receiver);
+                } else {
+                    pending.receivers.push(receiver);
+                }
+            }
+            None => {
+                self.expiry.push_back((now, message.frame.clone()));
+                self.pending.insert(
+                    message.frame.clone(),
+                    Pending {
+                        first_seen: now,
+                        message,
+                        receivers: vec![receiver],
+                    },
+                );
+            }
+        }
+
+        ready
+    }
+
+    /// Remove messages whose merge window has closed
+    pub fn expire(&mut self, now: Instant) -> Vec<Merged> {
+        let mut ready = vec![];
+
+        while let Some((first_seen, _)) = self.expiry.front() {
+            if now.duration_since(*first_seen) < self.window {
+                break;
+            }
+
+            let (first_seen, frame) = self.expiry.pop_front().unwrap();
+
+            // a newer transmission of the same frame has its own expiry entry
+            match self.pending.get(&frame) {
+                Some(pending) if pending.first_seen == first_seen => {
+                    let pending = self.pending.remove(&frame).unwrap();
+                    ready.push(self.merge(pending));
+                }
+                _ => (),
+            }
+        }
+
+        ready
+    }
+
+    fn merge(&self, pending: Pending) -> Merged {
+        let Pending {
+            message, receivers, ..
+        } = pending;
+
+        self.metrics
+            .best_signal
+            .with_label_values(&[&receivers[0]])
+            .inc();
+
+        if receivers.len() == 1 {
+            self.metrics
+                .unique
+                .with_label_values(&[&receivers[0]])
+                .inc();
+        } else {
+            receivers
+                .iter()
+                .for_each(|receiver| self.metrics.overlap.with_label_values(&[receiver]).inc());
+        }
+
+        Merged { message, receivers }
+    }
+}
+
+fn publish(feed: &Feed, merged: Merged) {
+    // no subscribers is not an error
+    let _ = feed.send(Arc::new(merged));
+}
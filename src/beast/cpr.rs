@@ -0,0 +1,186 @@
+use crate::beast::CPRFormat;
+use crate::beast::Position;
+
+use geo::algorithm::haversine_distance::HaversineDistance;
+use geo::Point;
+
+use std::f64::consts::PI;
+use std::time::Duration;
+use std::time::Instant;
+
+/// Maximum time between an even and odd frame for global decoding
+const GLOBAL_PAIR_AGE: Duration = Duration::from_secs(10);
+
+/// Number of latitude zones between the equator and a pole
+const NZ: f64 = 15.0;
+
+/// 2^17, the CPR latitude and longitude resolution
+const CPR_MAX: f64 = 131_072.0;
+
+/// Local decoding is only unambiguous within 180 NM of the reference, in meters
+const LOCAL_RANGE: f64 = 180.0 * 1852.0;
+
+/// Fastest plausible ground speed between two positions, about 1,000 knots in meters per second
+const MAXIMUM_SPEED: f64 = 515.0;
+
+/// Distance allowed between positions regardless of elapsed time, in meters.  This absorbs error
+/// in the reference position.
+const POSITION_SLACK: f64 = 1852.0;
+
+/// An airborne position CPR frame
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CPRFrame {
+    pub format: CPRFormat,
+    pub latitude: u32,
+    pub longitude: u32,
+    pub received: Instant,
+}
+
+/// Decode a position from an even and odd frame pair.  The position is for whichever frame was
+/// received last.
+pub fn decode_global(even: &CPRFrame, odd: &CPRFrame) -> Option<Position> {
+    let (newest, oldest) = if even.received >= odd.received {
+        (even, odd)
+    } else {
+        (odd, even)
+    };
+
+    if newest.received.duration_since(oldest.received) > GLOBAL_PAIR_AGE {
+        return None;
+    }
+
+    let lat_even = even.latitude as f64 / CPR_MAX;
+    let lat_odd = odd.latitude as f64 / CPR_MAX;
+    let lon_even = even.longitude as f64 / CPR_MAX;
+    let lon_odd = odd.longitude as f64 / CPR_MAX;
+
+    let j = (59.0 * lat_even - 60.0 * lat_odd + 0.5).floor();
+
+    let latitude_even = southern(360.0 / 60.0 * (j.rem_euclid(60.0) + lat_even));
+    let latitude_odd = southern(360.0 / 59.0 * (j.rem_euclid(59.0) + lat_odd));
+
+    let nl = number_of_longitude_zones(latitude_even);
+
+    if nl != number_of_longitude_zones(latitude_odd) {
+        // the frames straddle a longitude zone boundary
+        return None;
+    }
+
+    let m = (lon_even * (nl - 1.0) - lon_odd * nl + 0.5).floor();
+
+    let (latitude, longitude) = match newest.format {
+        CPRFormat::Even => {
+            let ni = nl.max(1.0);
+
+            (latitude_even, 360.0 / ni * (m.rem_euclid(ni) + lon_even))
+        }
+        CPRFormat::Odd => {
+            let ni = (nl - 1.0).max(1.0);
+
+            (latitude_odd, 360.0 / ni * (m.rem_euclid(ni) + lon_odd))
+        }
+    };
+
+    Some(Position {
+        latitude,
+        longitude: western(longitude),
+    })
+}
+
+/// Decode a position from a single frame relative to a reference position within 180 NM
+pub fn decode_local(frame: &CPRFrame, reference: &Position) -> Position {
+    let i = match frame.format {
+        CPRFormat::Even => 0.0,
+        CPRFormat::Odd => 1.0,
+    };
+
+    let lat_cpr = frame.latitude as f64 / CPR_MAX;
+    let lon_cpr = frame.longitude as f64 / CPR_MAX;
+
+    let d_lat = 360.0 / (4.0 * NZ - i);
+
+    let j = (reference.latitude / d_lat).floor()
+        + (reference.latitude.rem_euclid(d_lat) / d_lat - lat_cpr + 0.5).floor();
+
+    let latitude = d_lat * (j + lat_cpr);
+
+    let d_lon = 360.0 / (number_of_longitude_zones(latitude) - i).max(1.0);
+
+    let m = (reference.longitude / d_lon).floor()
+        + (reference.longitude.rem_euclid(d_lon) / d_lon - lon_cpr + 0.5).floor();
+
+    let longitude = d_lon * (m + lon_cpr);
+
+    Position {
+        latitude,
+        longitude: western(longitude),
+    }
+}
+
+/// Check that an aircraft could have moved from `reference` to `position` in `elapsed`
+///
+/// A corrupt frame that passes the CRC still decodes to a position near the reference, so a
+/// local decode is only accepted when it is within local decoding range and reachable at
+/// MAXIMUM_SPEED.
+pub fn reachable(reference: &Position, position: &Position, elapsed: Duration) -> bool {
+    let distance = point(reference).haversine_distance(&point(position));
+
+    distance <= LOCAL_RANGE && distance <= POSITION_SLACK + MAXIMUM_SPEED * elapsed.as_secs_f64()
+}
+
+/// Encode a position as the 17 bit CPR latitude and longitude of an even or odd frame
+pub fn encode(position: &Position, format: CPRFormat) -> (u32, u32) {
+    let i = match format {
+        CPRFormat::Even => 0.0,
+        CPRFormat::Odd => 1.0,
+    };
+
+    let d_lat = 360.0 / (4.0 * NZ - i);
+
+    let yz = (CPR_MAX * position.latitude.rem_euclid(d_lat) / d_lat + 0.5).floor();
+    let r_lat = d_lat * (yz / CPR_MAX + (position.latitude / d_lat).floor());
+
+    let d_lon = 360.0 / (number_of_longitude_zones(r_lat) - i).max(1.0);
+
+    let xz = (CPR_MAX * position.longitude.rem_euclid(d_lon) / d_lon + 0.5).floor();
+
+    (yz as u32 & 0x1ffff, xz as u32 & 0x1ffff)
+}
+
+fn point(position: &Position) -> Point<f64> {
+    Point::new(position.longitude, position.latitude)
+}
+
+// NL
+fn number_of_longitude_zones(latitude: f64) -> f64 {
+    let latitude = latitude.abs();
+
+    if latitude < 1e-9 {
+        return 59.0;
+    } else if (latitude - 87.0).abs() < 1e-9 {
+        return 2.0;
+    } else if latitude > 87.0 {
+        return 1.0;
+    }
+
+    let a = 1.0 - (PI / (2.0 * NZ)).cos();
+    let b = (PI / 180.0 * latitude).cos().powi(2);
+
+    (2.0 * PI / (1.0 - a / b).acos()).floor()
+}
+
+fn southern(latitude: f64) -> f64 {
+    if latitude >= 270.0 {
+        latitude - 360.0
+    } else {
+        latitude
+    }
+}
+
+fn western(longitude: f64) -> f64 {
+    if longitude >= 180.0 {
+        longitude - 360.0
+    } else {
+        longitude
+    }
+}
@@ -0,0 +1,28 @@
+/// Mode S CRC-24 generator polynomial, including the implicit 25th bit
+const GENERATOR: u32 = 0x1ff_f409;
+
+/// Mode S CRC-24 of `data`
+///
+/// For DF11, DF17 and DF18 frames the CRC of everything but the last three bytes equals the
+/// parity field.  For other downlink formats the parity field is the CRC XORed with the
+/// aircraft address.
+pub fn checksum(data: &[u8]) -> u32 {
+    data.iter().fold(0, |crc, byte| {
+        (0..8).fold(crc ^ ((*byte as u32) << 16), |crc, _| {
+            let crc = crc << 1;
+
+            if crc & 0x100_0000 != 0 {
+                crc ^ GENERATOR
+            } else {
+                crc
+            }
+        })
+    })
+}
+
+/// Parity field of a Mode S frame
+pub fn parity(frame: &[u8]) -> u32 {
+    let n = frame.len();
+
+    u32::from_be_bytes([0, frame[n - 3], frame[n - 2], frame[n - 1]])
+}
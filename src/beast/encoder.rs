@@ -0,0 +1,193 @@
+use crate::beast::cpr;
+use crate::beast::crc::checksum;
+use crate::beast::parser::ID_PATTERN;
+use crate::beast::CPRFormat;
+use crate::beast::Position;
+
+/// Transponder capability of level 2+ transponders that are airborne
+const CAPABILITY_AIRBORNE: u64 = 5;
+
+/// ADS-B emitter category of a medium jet in type code 4
+const CATEGORY_MEDIUM: u64 = 3;
+
+/// Builds a frame from fields of a given width in bits, most significant field first
+#[derive(Default)]
+struct Bits {
+    value: u128,
+    length: u32,
+}
+
+impl Bits {
+    fn push(mut self, value: u64, width: u32) -> Self {
+        let mask = (1u128 << width) - 1;
+
+        self.value = (self.value << width) | (value as u128 & mask);
+        self.length += width;
+
+        self
+    }
+
+    /// Append the parity field, the CRC of the frame XORed with `address`
+    fn parity(self, address: u32) -> Vec<u8> {
+        let length = (self.length / 8) as usize;
+        let mut frame = self.value.to_be_bytes()[16 - length..].to_vec();
+
+        let parity = checksum(&frame) ^ address;
+
+        frame.extend_from_slice(&parity.to_be_bytes()[1..]);
+
+        frame
+    }
+}
+
+/// DF17 extended squitter with the 56 bit ME field `message`
+fn extended_squitter(icao: u32, message: Bits) -> Vec<u8> {
+    Bits::default()
+        .push(17, 5)
+        .push(CAPABILITY_AIRBORNE, 3)
+        .push(icao as u64, 24)
+        .push(message.value as u64, 56)
+        .parity(0)
+}
+
+/// DF17 aircraft identification
+///
+/// Characters outside the ADS-B character set of A-Z, 0-9 and space are sent as spaces.
+pub fn identification(icao: u32, call_sign: &str) -> Vec<u8> {
+    let characters = call_sign
+        .chars()
+        .chain(std::iter::repeat(' '))
+        .take(8)
+        .fold(Bits::default(), |bits, c| {
+            bits.push(call_sign_character(c), 6)
+        });
+
+    let message = Bits::default()
+        .push(4, 5)
+        .push(CATEGORY_MEDIUM, 3)
+        .push(characters.value as u64, 48);
+
+    extended_squitter(icao, message)
+}
+
+/// DF17 airborne position with barometric altitude in feet
+pub fn airborne_position(
+    icao: u32,
+    altitude: i32,
+    position: &Position,
+    format: CPRFormat,
+) -> Vec<u8> {
+    let (latitude, longitude) = cpr::encode(position, format);
+    let n = altitude_25(altitude);
+
+    let message = Bits::default()
+        .push(11, 5)
+        .push(0, 2) // surveillance status
+        .push(0, 1) // single antenna flag
+        .push(((n & 0x7f0) << 1) | 0x10 | (n & 0xf), 12)
+        .push(0, 1) // UTC synchronized
+        .push(format as u64, 1)
+        .push(latitude as u64, 17)
+        .push(longitude as u64, 17);
+
+    extended_squitter(icao, message)
+}
+
+/// DF17 ground velocity in knots east and north and vertical rate in feet per minute
+pub fn velocity(icao: u32, east: f64, north: f64, vertical_rate: i32) -> Vec<u8> {
+    let message = Bits::default()
+        .push(19, 5)
+        .push(1, 3) // subsonic ground velocity
+        .push(0, 1) // intent change
+        .push(0, 1) // IFR capability
+        .push(0, 3) // navigation uncertainty
+        .push((east < 0.0) as u64, 1)
+        .push(speed_component(east), 10)
+        .push((north < 0.0) as u64, 1)
+        .push(speed_component(north), 10)
+        .push(1, 1) // barometric vertical rate
+        .push((vertical_rate < 0) as u64, 1)
+        .push(
+            ((vertical_rate.abs() as f64 / 64.0).round() as u64 + 1).min(511),
+            9,
+        )
+        .push(0, 2)
+        .push(0, 8); // no altitude difference
+
+    extended_squitter(icao, message)
+}
+
+/// DF17 aircraft status with an emergency state from 0 (none) to 6 and a squawk
+pub fn status(icao: u32, emergency: u8, squawk: u16) -> Vec<u8> {
+    let message = Bits::default()
+        .push(28, 5)
+        .push(1, 3) // emergency and priority status
+        .push(emergency as u64, 3)
+        .push(ident(squawk) as u64, 13)
+        .push(0, 32);
+
+    extended_squitter(icao, message)
+}
+
+/// DF4 altitude reply
+pub fn altitude_reply(icao: u32, altitude: i32, on_ground: bool) -> Vec<u8> {
+    let n = altitude_25(altitude);
+
+    Bits::default()
+        .push(4, 5)
+        .push(on_ground as u64, 3)
+        .push(0, 5) // downlink request
+        .push(0, 6) // utility message
+        .push(
+            ((n & 0x7e0) << 2) | ((n & 0x10) << 1) | 0x10 | (n & 0xf),
+            13,
+        )
+        .parity(icao)
+}
+
+/// DF5 identity reply
+pub fn identity_reply(icao: u32, squawk: u16, on_ground: bool) -> Vec<u8> {
+    Bits::default()
+        .push(5, 5)
+        .push(on_ground as u64, 3)
+        .push(0, 5) // downlink request
+        .push(0, 6) // utility message
+        .push(ident(squawk) as u64, 13)
+        .parity(icao)
+}
+
+/// DF11 all-call reply to interrogator 0, like an acquisition squitter
+pub fn all_call_reply(icao: u32) -> Vec<u8> {
+    Bits::default()
+        .push(11, 5)
+        .push(CAPABILITY_AIRBORNE, 3)
+        .push(icao as u64, 24)
+        .parity(0)
+}
+
+/// Altitude in 25 foot increments above -1000 feet for Q bit altitude codes
+fn altitude_25(altitude: i32) -> u64 {
+    ((altitude + 1000) as f64 / 25.0).round().clamp(0.0, 2047.0) as u64
+}
+
+// Inverse of parser::call_sign_character
+fn call_sign_character(c: char) -> u64 {
+    match c {
+        'A'..='Z' => c as u64 - 64,
+        '0'..='9' => c as u64,
+        _ => 32,
+    }
+}
+
+// Inverse of parser::ident
+fn ident(squawk: u16) -> u16 {
+    ID_PATTERN
+        .iter()
+        .filter(|(_, decoded)| squawk & decoded == *decoded)
+        .fold(0, |id, (encoded, _)| id | encoded)
+}
+
+/// Magnitude of a velocity component in knots, 0 means no information so 1 is 0 knots
+fn speed_component(knots: f64) -> u64 {
+    (knots.abs().round() as u64 + 1).min(1023)
+}
@@ -0,0 +1,265 @@
+use anyhow::anyhow;
+use anyhow::Context;
+use anyhow::Result;
+
+use crate::beast::ADSBMessage;
+use crate::beast::Data;
+use crate::beast::Message;
+
+use std::str::FromStr;
+
+/// Selects messages by downlink format, ICAO address, type code, signal level, call sign and kind
+///
+/// Each criterion matches any of its values and an empty criterion matches every message.  A
+/// message must match every criterion.  Filters are written like a URL query string:
+/// `df=17,11&icao=A1B2C3&tc=9,10&signal=-20&callsign=KLM*&kind=position,error`
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Filter {
+    pub downlink_formats: Vec<u8>,
+    /// ICAO addresses, matched against announced addresses and those recovered from parity
+    pub icaos: Vec<u32>,
+    pub type_codes: Vec<u8>,
+    /// Minimum signal level in dBFS
+    pub minimum_signal: Option<f64>,
+    /// Call sign patterns where `*` matches any characters and `?` matches one character
+    ///
+    /// Only aircraft identification messages contain a call sign.
+    pub call_signs: Vec<String>,
+    pub kinds: Vec<Kind>,
+}
+
+impl Filter {
+    pub fn matches(&self, message: &Message) -> bool {
+        if !self.downlink_formats.is_empty() {
+            match message.downlink_format() {
+                Some(df) if self.downlink_formats.contains(&df) => (),
+                _ => return false,
+            }
+        }
+
+        if !self.icaos.is_empty() {
+            match message.address() {
+                Some(address) if self.icaos.contains(&address) => (),
+                _ => return false,
+            }
+        }
+
+        if !self.type_codes.is_empty() {
+            match message.type_code() {
+                Some(tc) if self.type_codes.contains(&tc) => (),
+                _ => return false,
+            }
+        }
+
+        if let Some(minimum) = self.minimum_signal {
+            if message.signal_level < minimum {
+                return false;
+            }
+        }
+
+        if !self.call_signs.is_empty() {
+            match call_sign(message) {
+                Some(call_sign) if self.call_signs.iter().any(|p| glob(p, call_sign)) => (),
+                _ => return false,
+            }
+        }
+
+        if !self.kinds.is_empty() && !self.kinds.iter().any(|kind| kind.matches(message)) {
+            return false;
+        }
+
+        true
+    }
+}
+
+impl FromStr for Filter {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut filter = Filter::default();
+
+        for pair in s.split('&').filter(|p| !p.is_empty()) {
+            let (key, value) = pair
+                .split_once('=')
+                .with_context(|| format!("filter \"{}\" is missing '='", pair))?;
+
+            let values = value.split(',').filter(|v| !v.is_empty());
+
+            match key {
+                "df" => {
+                    for value in values {
+                        let df = value
+                            .parse()
+                            .with_context(|| format!("invalid downlink format \"{}\"", value))?;
+
+                        filter.downlink_formats.push(df);
+                    }
+                }
+                "icao" => {
+                    for value in values {
+                        filter.icaos.push(parse_icao(value)?);
+                    }
+                }
+                "tc" => {
+                    for value in values {
+                        let tc = value
+                            .parse()
+                            .with_context(|| format!("invalid type code \"{}\"", value))?;
+
+                        filter.type_codes.push(tc);
+                    }
+                }
+                "signal" => {
+                    let signal = value
+                        .parse()
+                        .with_context(|| format!("invalid signal level \"{}\"", value))?;
+
+                    filter.minimum_signal = Some(signal);
+                }
+                "callsign" => filter
+                    .call_signs
+                    .extend(values.map(|v| v.to_ascii_uppercase())),
+                "kind" => {
+                    for value in values {
+                        filter.kinds.push(value.parse()?);
+                    }
+                }
+                _ => return Err(anyhow!("unknown filter \"{}\"", key)),
+            }
+        }
+
+        Ok(filter)
+    }
+}
+
+/// Parse a hexadecimal ICAO address
+pub fn parse_icao(icao: &str) -> Result<u32> {
+    match u32::from_str_radix(icao, 16) {
+        Ok(address) if address <= 0xff_ffff => Ok(address),
+        _ => Err(anyhow!("invalid ICAO address \"{}\"", icao)),
+    }
+}
+
+/// Kind of decoded message
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Kind {
+    /// ADS-B airborne position
+    Position,
+    /// ADS-B aircraft identification
+    Identification,
+    /// ADS-B velocity
+    Velocity,
+    /// ADS-B aircraft status
+    Status,
+    /// ADS-B target state and status
+    TargetState,
+    /// Altitude and ACAS replies
+    Altitude,
+    /// Identity (squawk) replies
+    Identity,
+    /// All-call replies
+    AllCall,
+    /// Frames that could not be decoded
+    Error,
+    /// Mode A/C frames and formats or type codes that are not decoded
+    Unsupported,
+}
+
+impl Kind {
+    pub fn matches(&self, message: &Message) -> bool {
+        let adsb = match &message.data {
+            Data::ExtendedSquitter(squitter) => Some(&squitter.message),
+            _ => None,
+        };
+
+        match self {
+            Kind::Position => matches!(adsb, Some(ADSBMessage::AirbornePosition(_))),
+            Kind::Identification => {
+                matches!(adsb, Some(ADSBMessage::AircraftIdentification(_)))
+            }
+            Kind::Velocity => matches!(adsb, Some(ADSBMessage::Velocity(_))),
+            Kind::Status => matches!(adsb, Some(ADSBMessage::AircraftStatus(_))),
+            Kind::TargetState => {
+                matches!(adsb, Some(ADSBMessage::TargetState(_)))
+                    || matches!(message.data, Data::TargetState(_))
+            }
+            Kind::Altitude => matches!(
+                message.data,
+                Data::AltitudeReply(_)
+                    | Data::ACASSurveillanceReply(_)
+                    | Data::ACASCoordinationReply(_)
+            ),
+            Kind::Identity => matches!(message.data, Data::SurveillanceReply(_)),
+            Kind::AllCall => matches!(message.data, Data::AllCallReply(_)),
+            Kind::Error => matches!(message.data, Data::Error(_)),
+            Kind::Unsupported => {
+                matches!(adsb, Some(ADSBMessage::Unsupported(_)))
+                    || matches!(message.data, Data::Unsupported(_))
+            }
+        }
+    }
+}
+
+impl FromStr for Kind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "position" => Ok(Kind::Position),
+            "identification" => Ok(Kind::Identification),
+            "velocity" => Ok(Kind::Velocity),
+            "status" => Ok(Kind::Status),
+            "target-state" => Ok(Kind::TargetState),
+            "altitude" => Ok(Kind::Altitude),
+            "identity" => Ok(Kind::Identity),
+            "all-call" => Ok(Kind::AllCall),
+            "error" => Ok(Kind::Error),
+            "unsupported" => Ok(Kind::Unsupported),
+            _ => Err(anyhow!("unknown message kind \"{}\"", s)),
+        }
+    }
+}
+
+fn call_sign(message: &Message) -> Option<&str> {
+    match &message.data {
+        Data::ExtendedSquitter(squitter) => match &squitter.message {
+            ADSBMessage::AircraftIdentification(identification) => {
+                Some(identification.call_sign.trim_end())
+            }
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Match `text` against `pattern` where `*` matches any characters and `?` matches one
+fn glob(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+
+    let (mut p, mut t) = (0, 0);
+    let mut backtrack = None;
+
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, t));
+                p += 1;
+            }
+            Some(c) if *c == '?' || *c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match backtrack {
+                Some((star, matched)) => {
+                    p = star + 1;
+                    t = matched + 1;
+                    backtrack = Some((star, matched + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|c| *c == '*')
+}
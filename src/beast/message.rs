@@ -1,8 +1,14 @@
+use crate::beast::crc::checksum;
+use crate::beast::crc::parity;
+
 use nom::combinator::*;
 use nom::error::*;
 use nom::sequence::*;
 
-#[derive(Debug, PartialEq)]
+use serde::Serialize;
+use serde::Serializer;
+
+#[derive(Debug, PartialEq, Serialize)]
 pub struct ACASCoordinationReply {
     pub vertical_status: VerticalStatus,
     pub sensitivity_level: SensitivityLevel,
@@ -11,7 +17,7 @@ pub struct ACASCoordinationReply {
     pub vds: u32, // TODO, only 24 bits
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Serialize)]
 pub struct ACASSurveillanceReply {
     pub vertical_status: VerticalStatus,
     pub cross_link: CrossLink,
@@ -20,7 +26,7 @@ pub struct ACASSurveillanceReply {
     pub altitude: Altitude,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Serialize)]
 pub enum ADSBMessage {
     AircraftIdentification(AircraftIdentification),
     AircraftStatus(AircraftStatus),
@@ -30,12 +36,12 @@ pub enum ADSBMessage {
     Unsupported(Vec<u8>),
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Serialize)]
 pub enum ResolutionAdvisory {
     None,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Serialize)]
 pub struct AirbornePosition {
     pub surveillance_status: SurveillanceStatus,
     pub single_antenna: bool,
@@ -46,7 +52,7 @@ pub struct AirbornePosition {
     pub cpr_longitude: u32,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Serialize)]
 pub enum AircraftCategory {
     None,
     SurfaceEmergencyVehicle,
@@ -65,11 +71,13 @@ pub enum AircraftCategory {
     Heavy,
     HighPerformance,
     Rotorcraft,
+    Reserved,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Serialize)]
 pub struct AircraftStatus {
     pub emergency: Emergency,
+    #[serde(serialize_with = "squawk")]
     pub squawk: u16,
 }
 
@@ -94,13 +102,13 @@ impl AircraftStatus {
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Serialize)]
 pub struct AircraftIdentification {
     pub category: AircraftCategory,
     pub call_sign: String,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Serialize)]
 pub struct Airspeed {
     pub supersonic_aircraft: bool,
     pub magnetic_heading_available: bool,
@@ -109,33 +117,33 @@ pub struct Airspeed {
     pub airspeed: u16,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Serialize)]
 pub enum AirspeedType {
     Indicated,
     True,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Serialize)]
 pub struct AllCallReply {
     pub capability: u8,
     pub icao: String,
     pub parity: u32,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Serialize)]
 pub enum Altitude {
     Invalid,
     Feet(i32),
     Meters(i32),
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Serialize)]
 pub enum AltitudeDifference {
     NoInformation,
     Feet(i16),
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Serialize)]
 pub struct AltitudeReply {
     pub flight_status: FlightStatus,
     pub downlink_request: u8,
@@ -143,44 +151,44 @@ pub struct AltitudeReply {
     pub altitude: Altitude,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Serialize)]
 pub enum AltitudeSetting {
     None,
     Feet(u32),
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Serialize)]
 pub enum AltitudeSource {
     Unknown,
     MCPFCU,
     FMS,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Serialize)]
 pub enum BarometerSetting {
     None,
     MilliBar(f64),
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Serialize)]
 pub struct BeastParseError {
     pub data: Vec<u8>,
     pub error: String,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
 pub enum CPRFormat {
     Even,
     Odd,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Serialize)]
 pub enum CrossLink {
     Unsupported,
     Supported,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Serialize)]
 pub enum Data {
     ACASSurveillanceReply(ACASSurveillanceReply),
     ACASCoordinationReply(ACASCoordinationReply),
@@ -193,13 +201,13 @@ pub enum Data {
     Error(BeastParseError),
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Serialize)]
 pub enum EastWestDirection {
     WestToEast,
     EastToWest,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Serialize)]
 pub enum Emergency {
     NoInformation,
     None,
@@ -211,14 +219,14 @@ pub enum Emergency {
     Downed,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Serialize)]
 pub struct ExtendedSquitter {
     pub capability: u8,
     pub icao: String,
     pub message: ADSBMessage,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Serialize)]
 pub enum FlightStatus {
     Airborne,
     OnGround,
@@ -241,12 +249,12 @@ impl FlightStatus {
             5 => FlightStatus::SPI,
             6 => FlightStatus::Reserved,
             7 => FlightStatus::Unassigned,
-            _ => unreachable!("Impossible flight status {}, only 3 bits allowed", fs)
+            _ => unreachable!("Impossible flight status {}, only 3 bits allowed", fs),
         }
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Serialize)]
 pub struct GroundVelocity {
     pub supersonic_aircraft: bool,
     pub east_west_direction: EastWestDirection,
@@ -255,29 +263,73 @@ pub struct GroundVelocity {
     pub north_south_velocity: u16,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Serialize)]
 pub enum HeadingSetting {
     None,
     MagneticOrTrue(f64),
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Serialize)]
 pub struct Message {
     /// Timestamp the message arrived at the ADS-B receiver in µs since the dump process started
     pub timestamp: f64,
     /// RSSI in dBFS
     pub signal_level: f64,
     pub data: Data,
+    /// Unescaped Mode A/C or Mode S frame the message was decoded from
+    pub frame: Vec<u8>,
+}
+
+impl Message {
+    /// Downlink format of a Mode S frame, None for Mode A/C frames
+    pub fn downlink_format(&self) -> Option<u8> {
+        match self.frame.len() {
+            7 | 14 => Some(self.frame[0] >> 3),
+            _ => None,
+        }
+    }
+
+    /// ICAO address of the aircraft that sent a Mode S frame
+    ///
+    /// DF11, DF17 and DF18 announce the address.  DF0, DF4, DF5, DF16, DF20 and DF21 overlay it
+    /// on the parity field so it is recovered from the CRC, which means a corrupt frame yields a
+    /// bogus address.
+    pub fn address(&self) -> Option<u32> {
+        let frame = &self.frame;
+
+        match self.downlink_format()? {
+            11 | 17 | 18 => Some(u32::from_be_bytes([0, frame[1], frame[2], frame[3]])),
+            0 | 4 | 5 | 16 | 20 | 21 => Some(checksum(&frame[..frame.len() - 3]) ^ parity(frame)),
+            _ => None,
+        }
+    }
+
+    /// ICAO address announced by the aircraft, if the message contains one
+    pub fn icao(&self) -> Option<&str> {
+        match &self.data {
+            Data::AllCallReply(reply) => Some(&reply.icao),
+            Data::ExtendedSquitter(squitter) => Some(&squitter.icao),
+            _ => None,
+        }
+    }
+
+    /// ADS-B type code of an extended squitter frame
+    pub fn type_code(&self) -> Option<u8> {
+        match self.downlink_format() {
+            Some(17) | Some(18) if self.frame.len() == 14 => Some(self.frame[4] >> 3),
+            _ => None,
+        }
+    }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Serialize)]
 pub enum NorthSouthDirection {
     SouthToNorth,
     NorthToSouth,
 }
 
 /// Maximum airspeeds are in knots
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Serialize)]
 pub enum ReplyInformation {
     Inoperative,
     ACASInhibited,
@@ -290,13 +342,13 @@ pub enum ReplyInformation {
     Unsupported(u8),
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Serialize)]
 pub enum SensitivityLevel {
     Inoperative,
     Operative(u8),
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Serialize)]
 pub enum SourceIntegrityLevel {
     Unknown,
     PerThousand,
@@ -304,21 +356,27 @@ pub enum SourceIntegrityLevel {
     PerTenMillion,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Serialize)]
 pub enum SourceIntegrityLevelSupplement {
     PerHour,
     PerSample,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Serialize)]
 pub struct SurveillanceReply {
     pub flight_status: FlightStatus,
     pub downlink_request: u8,
     pub utility_message: u8,
+    #[serde(serialize_with = "squawk")]
     pub id: u16,
 }
 
-#[derive(Debug, PartialEq)]
+/// Serializes a Mode A code, stored as one octal digit per hex nibble, as its four digits
+fn squawk<S: Serializer>(squawk: &u16, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&format!("{:04X}", squawk))
+}
+
+#[derive(Debug, PartialEq, Serialize)]
 pub enum SurveillanceStatus {
     NoCondition,
     PermanentAlert,
@@ -326,16 +384,16 @@ pub enum SurveillanceStatus {
     SPICondition,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Serialize)]
 pub enum TargetStateType {
     SubType0(TargetState0),
     SubType1(TargetState1),
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Serialize)]
 pub struct TargetState0 {}
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Serialize)]
 pub struct TargetState1 {
     pub sil_supplement: SourceIntegrityLevelSupplement,
     pub altitude_source: AltitudeSource,
@@ -353,7 +411,7 @@ pub struct TargetState1 {
     pub lnav: Option<bool>,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Serialize)]
 pub struct Velocity {
     pub intent_change: bool,
     pub ifr_capability: bool,
@@ -475,26 +533,26 @@ fn velocity_ground(supersonic_aircraft: bool, velocity: u32) -> VelocityType {
     VelocityType::Ground(velocity)
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Serialize)]
 pub enum VelocityType {
     Airborne(Airspeed),
     Ground(GroundVelocity),
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Serialize)]
 pub enum VerticalRate {
     NoInformation,
     FeetPerMinute(VerticalRateSource),
 }
 
 /// Vertical rate in feet per minute
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Serialize)]
 pub enum VerticalRateSource {
     GNSS(i32),
     Barometer(i32),
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Serialize)]
 pub enum VerticalStatus {
     Ground,
     Airborne,
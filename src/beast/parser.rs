@@ -39,18 +39,18 @@ pub fn unescape<'a>(length: usize) -> impl FnMut(&'a [u8]) -> IResult<&'a [u8],
     )
 }
 
-pub fn sep_or_not<'a>(input: &'a [u8]) -> IResult<&'a [u8], u8> {
+pub fn sep_or_not(input: &[u8]) -> IResult<&[u8], u8> {
     alt((
         value(0x1a, preceded(tag(b"\x1a"), tag(b"\x1a"))),
-        map(take(1usize), |c: &[u8]| c[0] as u8),
+        map(take(1usize), |c: &[u8]| c[0]),
     ))(input)
 }
 
-fn beast_header<'a>(input: &'a [u8]) -> IResult<&'a [u8], (usize, f64, f64)> {
+fn beast_header(input: &[u8]) -> IResult<&[u8], (usize, f64, f64)> {
     tuple((header_message_size, header_timestamp, header_signal))(input)
 }
 
-pub fn header_message_size<'a>(input: &'a [u8]) -> IResult<&'a [u8], usize> {
+pub fn header_message_size(input: &[u8]) -> IResult<&[u8], usize> {
     map(
         preceded(tag(b"\x1a"), alt((tag(b"1"), tag(b"2"), tag(b"3")))),
         |message_format: &[u8]| match message_format {
@@ -62,74 +62,93 @@ pub fn header_message_size<'a>(input: &'a [u8]) -> IResult<&'a [u8], usize> {
     )(input)
 }
 
-pub fn header_timestamp<'a>(input: &'a [u8]) -> IResult<&'a [u8], f64> {
+pub fn header_timestamp(input: &[u8]) -> IResult<&[u8], f64> {
     map(
         fold_many_m_n(6, 6, sep_or_not, || 0, |ts, c| (ts << 8) | c as u64),
         |ts| ts as f64 / 12.0,
     )(input)
 }
 
-pub fn header_signal<'a>(input: &'a [u8]) -> IResult<&'a [u8], f64> {
+pub fn header_signal(input: &[u8]) -> IResult<&[u8], f64> {
     map(sep_or_not, |signal| {
         let signal = signal as f64 / 255.0;
         10.0 * (signal * signal).log10()
     })(input)
 }
 
-pub fn parse_message<'a>(
+pub fn parse_message(
     message_length: usize,
     timestamp: f64,
     signal_level: f64,
-    input: &'a [u8],
-) -> IResult<&'a [u8], Message> {
-    map(unescape(message_length), |message| {
-        if message_length == MODE_AC_LENGTH {
-            Message {
-                timestamp,
-                signal_level,
-                data: Data::Unsupported(message.to_vec()),
-            }
-        } else {
-            match parse_downlink_format(timestamp, signal_level, &message) {
-                Ok((_, m)) => m,
-                Err(e) => Message {
-                    timestamp,
-                    signal_level,
-                    data: Data::Error(BeastParseError {
-                        data: message.to_vec(),
-                        error: format!("{}", e),
-                    }),
-                },
-            }
-        }
+    input: &[u8],
+) -> IResult<&[u8], Message> {
+    map(unescape(message_length), |frame| {
+        decode_frame(timestamp, signal_level, frame)
     })(input)
 }
 
-fn parse_downlink_format<'a>(
-    timestamp: f64,
-    signal_level: f64,
-    input: &'a [u8],
-) -> IResult<&'a [u8], Message> {
+/// Decode an unescaped Mode A/C or Mode S frame into a Message
+pub fn decode_frame(timestamp: f64, signal_level: f64, frame: Vec<u8>) -> Message {
+    if frame.len() == MODE_AC_LENGTH {
+        return Message {
+            timestamp,
+            signal_level,
+            data: Data::Unsupported(frame.clone()),
+            frame,
+        };
+    }
+
+    let data = match parse_downlink_format(&frame) {
+        Ok((_, data)) => data,
+        Err(e) => Data::Error(BeastParseError {
+            data: frame.clone(),
+            error: format!("{}", e),
+        }),
+    };
+
+    Message {
+        timestamp,
+        signal_level,
+        data,
+        frame,
+    }
+}
+
+fn parse_downlink_format(input: &[u8]) -> IResult<&[u8], Data> {
     use nom::bits::bits;
     use nom::bits::complete::take;
 
     map(
         bits::<_, _, Error<(&[u8], usize)>, Error<&[u8]>, _>(take(5usize)),
         |downlink_format: u8| {
-            let data = match downlink_format {
+            let expected_length = match downlink_format {
+                0..=15 => MODE_S_SHORT_LENGTH,
+                _ => MODE_S_LONG_LENGTH,
+            };
+
+            if input.len() != expected_length {
+                return Data::Error(BeastParseError {
+                    data: input.to_vec(),
+                    error: format!(
+                        "downlink format {} requires {} bytes, got {}",
+                        downlink_format,
+                        expected_length,
+                        input.len()
+                    ),
+                });
+            }
+
+            match downlink_format {
                 0 => parse_df_0(input),
                 4 => parse_df_4(input),
                 5 => parse_df_5(input),
                 11 => parse_df_11(input),
                 16 => parse_df_16(input),
                 17 => parse_df_17(input),
+                // Comm-B replies share the DF4 and DF5 layout, the MB field is left in the frame
+                20 => parse_df_4(input),
+                21 => parse_df_5(input),
                 _ => Data::Unsupported(input.to_vec()),
-            };
-
-            Message {
-                timestamp,
-                signal_level,
-                data,
             }
         },
     )(input)
@@ -375,6 +394,11 @@ pub fn altitude_code(ac: u16) -> Altitude {
     }
 }
 
+// ADS-B altitudes omit the M bit of the 13 bit AC field, it is always 0 (feet)
+fn insert_m_bit(ac: u16) -> u16 {
+    ((ac & 0xfc0) << 1) | (ac & 0x3f)
+}
+
 // CA
 fn capability(ca: u8) -> u8 {
     ca
@@ -407,17 +431,21 @@ fn message(me: u64) -> ADSBMessage {
     let input = me.to_be_bytes();
     let input = &input[1..];
 
-    let (_, type_code) =
-        bits::<_, _, Error<(&[u8], usize)>, Error<&[u8]>, _>(take(5usize))(input).unwrap();
+    let (_, (type_code, sub_type)) = bits::<_, _, Error<(&[u8], usize)>, Error<&[u8]>, _>(tuple((
+        take(5usize),
+        take(3usize),
+    )))(input)
+    .unwrap();
 
-    match type_code {
-        1..=4 => aircraft_identification(input, type_code),
+    match (type_code, sub_type) {
+        (2..=4, _) => aircraft_identification(input, type_code),
         //5..=8 => unimplemented!("surface_position"),
-        9..=18 => airborne_position(input),
-        19 => velocity(input),
+        (9..=18, _) => airborne_position(input),
+        (19, 1..=4) => velocity(input),
         //20..=22 => unimplemented!("airborne_position"),
-        28 => aircraft_status(input),
-        29 => target_state(input),
+        (28, 0) => aircraft_status(input),
+        (28, 1) if input[1] & 0xe0 != 0xe0 => aircraft_status(input),
+        (29, _) if input[0] & 0x06 <= 0x02 => target_state(input),
         //31 => unimplemented!("operational_status"),
         _ => ADSBMessage::Unsupported(input.to_vec()),
     }
@@ -459,7 +487,7 @@ fn vertical_status(vs: u8) -> VerticalStatus {
     }
 }
 
-const ID_PATTERN: [(u16, u16); 12] = [
+pub(crate) const ID_PATTERN: [(u16, u16); 12] = [
     (0x1000, 0x0010),
     (0x0800, 0x1000),
     (0x0400, 0x0020),
@@ -495,7 +523,7 @@ fn airborne_position(input: &[u8]) -> ADSBMessage {
         tuple((
             preceded::<_, u8, _, _, _, _>(take(5usize), map(take(2usize), surveillance_status)),
             map(take(1usize), |saf: u8| saf == 1),
-            map(take(12usize), altitude_code),
+            map(take(12usize), |ac: u16| altitude_code(insert_m_bit(ac))),
             map(take(1usize), |t: u8| t == 1),
             map(take(1usize), cpr_format),
             take(17usize),
@@ -592,10 +620,6 @@ fn aircraft_status(input: &[u8]) -> ADSBMessage {
 }
 
 fn aircraft_category(type_code: u8, category: u8) -> AircraftCategory {
-    if 1 == type_code {
-        unreachable!("reserved aircraft category for type code {}", type_code);
-    }
-
     if 0 == category {
         return AircraftCategory::None;
     }
@@ -605,20 +629,14 @@ fn aircraft_category(type_code: u8, category: u8) -> AircraftCategory {
             1 => AircraftCategory::SurfaceEmergencyVehicle,
             3 => AircraftCategory::SurfaceServiceVehicle,
             4..=7 => AircraftCategory::GroundObstruction,
-            _ => unreachable!(
-                "unknown aircraft category {} for type code {}",
-                category, type_code
-            ),
+            _ => AircraftCategory::Reserved,
         },
         3 => match category {
             1 => AircraftCategory::Glider,
             2 => AircraftCategory::LighterThanAir,
             3 => AircraftCategory::Parachutist,
             4 => AircraftCategory::Ultralight,
-            5 => unreachable!(
-                "reserved aircraft category {} for type code {}",
-                category, type_code
-            ),
+            5 => AircraftCategory::Reserved,
             6 => AircraftCategory::UnmannedAerialVehicle,
             7 => AircraftCategory::SpaceVehicle,
             _ => unreachable!(
@@ -643,12 +661,13 @@ fn aircraft_category(type_code: u8, category: u8) -> AircraftCategory {
     }
 }
 
+// Characters outside the ADS-B character set are replaced with '?', like dump1090
 fn call_sign_character(c: u32) -> char {
     match c {
         1..=26 => char::from_u32(c + 64).unwrap(),
         32 => ' ',
         48..=57 => char::from_u32(c).unwrap(),
-        _ => unreachable!("invalid call sign character {}", c),
+        _ => '?',
     }
 }
 
@@ -670,6 +689,8 @@ fn surveillance_status(ss: u8) -> SurveillanceStatus {
     }
 }
 
+type TargetState0Fields = (u8, u8, u8, u8, u8, u16, u8, u8, u8, u8, u8, u8, u8, u8, u8);
+
 fn target_state(input: &[u8]) -> ADSBMessage {
     use nom::bits::bits;
     use nom::bits::complete::tag;
@@ -699,9 +720,7 @@ fn target_state(input: &[u8]) -> ADSBMessage {
                             preceded::<_, u8, _, _, _, _>(take(5usize), take(2usize)),
                             take(3usize),
                         )),
-                        |_: (u8, u8, u8, u8, u8, u16, u8, u8, u8, u8, u8, u8, u8, u8, u8)| {
-                            TargetStateType::SubType0(TargetState0 {})
-                        },
+                        |_: TargetState0Fields| TargetStateType::SubType0(TargetState0 {}),
                     ),
                 ),
                 preceded::<_, u8, _, _, _, _>(
@@ -0,0 +1,262 @@
+use crate::beast::tracker::ground_velocity;
+use crate::beast::tracker::is_emergency;
+use crate::beast::ADSBMessage;
+use crate::beast::Aircraft;
+use crate::beast::Altitude;
+use crate::beast::Data;
+use crate::beast::FlightStatus;
+use crate::beast::Message;
+use crate::beast::Position;
+use crate::beast::SurveillanceStatus;
+use crate::beast::Tracker;
+use crate::beast::VelocityType;
+use crate::beast::VerticalRate;
+use crate::beast::VerticalRateSource;
+use crate::beast::VerticalStatus;
+
+use chrono::DateTime;
+use chrono::Local;
+
+use std::time::Duration;
+use std::time::Instant;
+
+/// Aircraft not heard from within this time are forgotten
+const MAX_AGE: Duration = Duration::from_secs(60);
+
+/// How often forgotten aircraft are removed
+const EXPIRE_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Writes decoded messages as SBS-1 BaseStation lines, like dump1090 port 30003
+///
+/// Aircraft are tracked so addresses recovered from the parity field can be attributed and
+/// positions decoded.  Messages with no BaseStation equivalent produce no line.
+#[derive(Debug)]
+pub struct SbsWriter {
+    tracker: Tracker,
+    last_expire: Instant,
+}
+
+impl SbsWriter {
+    pub fn new() -> Self {
+        SbsWriter {
+            tracker: Tracker::new(),
+            last_expire: Instant::now(),
+        }
+    }
+
+    /// Track `message` and format it as a `MSG` line without a line terminator
+    ///
+    /// `time` is used for both the generated and logged date and time fields.
+    pub fn write(
+        &mut self,
+        message: &Message,
+        now: Instant,
+        time: &DateTime<Local>,
+    ) -> Option<String> {
+        if now.saturating_duration_since(self.last_expire) >= EXPIRE_INTERVAL {
+            self.tracker.expire(MAX_AGE, now);
+            self.last_expire = now;
+        }
+
+        let address = message.address()?;
+        let aircraft = self.tracker.update(message, now)?;
+
+        let (transmission_type, fields) = fields(message, aircraft)?;
+
+        let date = time.format("%Y/%m/%d");
+        let time = time.format("%H:%M:%S%.3f");
+
+        Some(format!(
+            "MSG,{},1,1,{:06X},1,{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{}",
+            transmission_type,
+            address,
+            date,
+            time,
+            date,
+            time,
+            fields.call_sign.unwrap_or_default(),
+            optional(fields.altitude),
+            optional(fields.ground_speed.map(|s| format!("{:.0}", s))),
+            optional(fields.track.map(|t| format!("{:.0}", t))),
+            optional(fields.position.map(|p| format!("{:.5}", p.latitude))),
+            optional(fields.position.map(|p| format!("{:.5}", p.longitude))),
+            optional(fields.vertical_rate),
+            optional(fields.squawk.map(|s| format!("{:04X}", s))),
+            flag(fields.alert),
+            flag(fields.emergency),
+            flag(fields.spi),
+            flag(fields.on_ground),
+        ))
+    }
+}
+
+impl Default for SbsWriter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Fields of a `MSG` line that vary by transmission type
+#[derive(Default)]
+struct Fields {
+    call_sign: Option<String>,
+    altitude: Option<i32>,
+    ground_speed: Option<f64>,
+    track: Option<f64>,
+    position: Option<Position>,
+    vertical_rate: Option<i32>,
+    squawk: Option<u16>,
+    alert: Option<bool>,
+    emergency: Option<bool>,
+    spi: Option<bool>,
+    on_ground: Option<bool>,
+}
+
+fn fields(message: &Message, aircraft: &Aircraft) -> Option<(u8, Fields)> {
+    let emergency = aircraft.squawk.map(is_emergency);
+
+    match &message.data {
+        Data::ExtendedSquitter(squitter) => match &squitter.message {
+            ADSBMessage::AircraftIdentification(identification) => Some((
+                1,
+                Fields {
+                    call_sign: Some(identification.call_sign.trim_end().to_string()),
+                    ..Fields::default()
+                },
+            )),
+            ADSBMessage::AirbornePosition(position) => {
+                // Only report the position if it was decoded from this message
+                let decoded = match aircraft.last_position {
+                    Some(seen) if seen == aircraft.last_seen => aircraft.position,
+                    _ => None,
+                };
+
+                Some((
+                    3,
+                    Fields {
+                        altitude: feet(&position.altitude),
+                        position: decoded,
+                        alert: Some(matches!(
+                            position.surveillance_status,
+                            SurveillanceStatus::PermanentAlert | SurveillanceStatus::TemporaryAlert
+                        )),
+                        emergency,
+                        spi: Some(position.surveillance_status == SurveillanceStatus::SPICondition),
+                        on_ground: Some(false),
+                        ..Fields::default()
+                    },
+                ))
+            }
+            ADSBMessage::Velocity(velocity) => {
+                let ground = match &velocity.velocity {
+                    VelocityType::Ground(ground) => ground_velocity(ground),
+                    VelocityType::Airborne(_) => None,
+                };
+
+                let vertical_rate = match &velocity.vertical_rate {
+                    VerticalRate::NoInformation => None,
+                    VerticalRate::FeetPerMinute(VerticalRateSource::GNSS(rate)) => Some(*rate),
+                    VerticalRate::FeetPerMinute(VerticalRateSource::Barometer(rate)) => Some(*rate),
+                };
+
+                Some((
+                    4,
+                    Fields {
+                        ground_speed: ground.map(|(speed, _)| speed),
+                        track: ground.map(|(_, track)| track),
+                        vertical_rate,
+                        ..Fields::default()
+                    },
+                ))
+            }
+            _ => None,
+        },
+        Data::AltitudeReply(reply) => Some((
+            5,
+            Fields {
+                altitude: feet(&reply.altitude),
+                alert: Some(alert(&reply.flight_status)),
+                emergency,
+                spi: Some(spi(&reply.flight_status)),
+                on_ground: on_ground(&reply.flight_status),
+                ..Fields::default()
+            },
+        )),
+        Data::SurveillanceReply(reply) => Some((
+            6,
+            Fields {
+                squawk: Some(reply.id),
+                alert: Some(alert(&reply.flight_status)),
+                emergency: Some(is_emergency(reply.id)),
+                spi: Some(spi(&reply.flight_status)),
+                on_ground: on_ground(&reply.flight_status),
+                ..Fields::default()
+            },
+        )),
+        Data::ACASSurveillanceReply(reply) => Some((
+            7,
+            Fields {
+                altitude: feet(&reply.altitude),
+                on_ground: vertical_on_ground(&reply.vertical_status),
+                ..Fields::default()
+            },
+        )),
+        Data::ACASCoordinationReply(reply) => Some((
+            7,
+            Fields {
+                altitude: feet(&reply.altitude),
+                on_ground: vertical_on_ground(&reply.vertical_status),
+                ..Fields::default()
+            },
+        )),
+        Data::AllCallReply(_) => Some((8, Fields::default())),
+        _ => None,
+    }
+}
+
+fn alert(flight_status: &FlightStatus) -> bool {
+    matches!(
+        flight_status,
+        FlightStatus::AirborneAlert | FlightStatus::OnGroundAlert | FlightStatus::SPIAlert
+    )
+}
+
+fn feet(altitude: &Altitude) -> Option<i32> {
+    match altitude {
+        Altitude::Feet(feet) => Some(*feet),
+        _ => None,
+    }
+}
+
+fn on_ground(flight_status: &FlightStatus) -> Option<bool> {
+    match flight_status {
+        FlightStatus::Airborne | FlightStatus::AirborneAlert => Some(false),
+        FlightStatus::OnGround | FlightStatus::OnGroundAlert => Some(true),
+        _ => None,
+    }
+}
+
+fn spi(flight_status: &FlightStatus) -> bool {
+    matches!(flight_status, FlightStatus::SPIAlert | FlightStatus::SPI)
+}
+
+fn vertical_on_ground(vertical_status: &VerticalStatus) -> Option<bool> {
+    match vertical_status {
+        VerticalStatus::Airborne => Some(false),
+        VerticalStatus::Ground => Some(true),
+        VerticalStatus::Either => None,
+    }
+}
+
+// BaseStation flags are -1 for true and 0 for false
+fn flag(value: Option<bool>) -> &'static str {
+    match value {
+        Some(true) => "-1",
+        Some(false) => "0",
+        None => "",
+    }
+}
+
+fn optional<T: ToString>(value: Option<T>) -> String {
+    value.map(|v| v.to_string()).unwrap_or_default()
+}
@@ -0,0 +1,442 @@
+use anyhow::Context;
+use anyhow::Result;
+
+use bytes::BytesMut;
+
+use chrono::Local;
+
+use crate::beast::Codec;
+use crate::beast::Feed;
+use crate::beast::Filter;
+use crate::beast::Merged;
+use crate::beast::Reception;
+use crate::beast::Receptions;
+use crate::beast::SbsWriter;
+use crate::shutdown::Shutdown;
+
+use futures_util::StreamExt;
+
+use log::debug;
+use log::info;
+
+use prometheus::core::Collector;
+use prometheus::core::Desc;
+use prometheus::proto::MetricFamily;
+use prometheus::IntCounterVec;
+use prometheus::IntGaugeVec;
+use prometheus::Opts;
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Instant;
+
+use tokio::io::AsyncWriteExt;
+use tokio::net::TcpListener;
+use tokio::net::TcpStream;
+use tokio::sync::broadcast;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::mpsc;
+
+use tokio_util::codec::Encoder;
+use tokio_util::codec::FramedRead;
+
+/// Metrics of BEAST and SBS servers and BEAST inputs
+#[derive(Clone)]
+pub struct ServerMetrics {
+    clients: IntGaugeVec,
+    sent: IntCounterVec,
+    dropped: IntCounterVec,
+    received: IntCounterVec,
+}
+
+impl ServerMetrics {
+    /// Unregistered metrics of BEAST and SBS servers and BEAST inputs
+    pub fn new() -> Result<Self> {
+        Ok(ServerMetrics {
+            clients: IntGaugeVec::new(
+                Opts::new(
+                    "adsb_beast_server_clients",
+                    "Number of clients connected to a BEAST server",
+                ),
+                &["address", "direction"],
+            )?,
+            sent: IntCounterVec::new(
+                Opts::new(
+                    "adsb_beast_server_messages_sent_total",
+                    "Number of BEAST messages sent to clients",
+                ),
+                &["address"],
+            )?,
+            dropped: IntCounterVec::new(
+                Opts::new(
+                    "adsb_beast_server_messages_dropped_total",
+                    "Number of BEAST messages dropped for clients that fell behind",
+                ),
+                &["address"],
+            )?,
+            received: IntCounterVec::new(
+                Opts::new(
+                    "adsb_beast_server_messages_received_total",
+                    "Number of BEAST messages pushed by clients",
+                ),
+                &["address"],
+            )?,
+        })
+    }
+
+    fn collectors(&self) -> Vec<&dyn Collector> {
+        vec![&self.clients, &self.sent, &self.dropped, &self.received]
+    }
+}
+
+impl Collector for ServerMetrics {
+    fn desc(&self) -> Vec<&Desc> {
+        self.collectors()
+            .into_iter()
+            .flat_map(|c| c.desc())
+            .collect()
+    }
+
+    fn collect(&self) -> Vec<MetricFamily> {
+        self.collectors()
+            .into_iter()
+            .flat_map(|c| c.collect())
+            .collect()
+    }
+}
+
+/// Serves messages from a feed to BEAST clients like mlat-client or feeders
+pub struct Server {
+    address: SocketAddr,
+    filter: Filter,
+    metrics: ServerMetrics,
+}
+
+impl Server {
+    pub fn new(address: SocketAddr, filter: Filter, metrics: ServerMetrics) -> Self {
+        Server {
+            address,
+            filter,
+            metrics,
+        }
+    }
+
+    /// Serve clients until a shutdown is requested
+    ///
+    /// Connected clients are sent the rest of the feed until every sender of the feed is dropped.
+    pub async fn run(self, feed: Feed, mut shutdown: Shutdown) -> Result<()> {
+        let listener = TcpListener::bind(self.address)
+            .await
+            .with_context(|| format!("Unable to listen for BEAST clients on {}", self.address))?;
+
+        info!("Serving BEAST on {}", self.address);
+
+        loop {
+            let (stream, peer) = tokio::select! {
+                accepted = listener.accept() => match accepted {
+                    Ok(accepted) => accepted,
+                    Err(e) => {
+                        debug!("accept error on {}: {:?}", self.address, e);
+                        continue;
+                    }
+                },
+                _ = shutdown.requested() => return Ok(()),
+            };
+
+            debug!("BEAST client {} connected to {}", peer, self.address);
+
+            let address = self.address.to_string();
+            let filter = self.filter.clone();
+            let metrics = self.metrics.clone();
+            let messages = feed.subscribe();
+            let shutdown = shutdown.clone();
+
+            crate::spawn_named(
+                async move {
+                    let clients = metrics.clients.with_label_values(&[&address, "output"]);
+
+                    clients.inc();
+
+                    if let Err(e) = serve(stream, &address, &metrics, filter, messages).await {
+                        debug!("BEAST client {} disconnected: {:?}", peer, e);
+                    }
+
+                    clients.dec();
+
+                    drop(shutdown);
+                },
+                &format!("beast::server::{}", peer),
+            );
+        }
+    }
+}
+
+async fn serve(
+    mut stream: TcpStream,
+    address: &str,
+    metrics: &ServerMetrics,
+    filter: Filter,
+    mut messages: broadcast::Receiver<Arc<Merged>>,
+) -> Result<()> {
+    let mut codec = Codec::new();
+    let mut buf = BytesMut::new();
+
+    loop {
+        let message = match messages.recv().await {
+            Ok(merged) => merged,
+            Err(RecvError::Lagged(count)) => {
+                metrics.dropped.with_label_values(&[address]).inc_by(count);
+                continue;
+            }
+            Err(RecvError::Closed) => break,
+        };
+
+        if !filter.matches(&message.message) {
+            continue;
+        }
+
+        codec.encode(&message.message, &mut buf)?;
+
+        stream.write_all_buf(&mut buf).await?;
+
+        metrics.sent.with_label_values(&[address]).inc();
+    }
+
+    // Every message published before the feed closed was written, let the client see the end
+    stream.shutdown().await?;
+
+    Ok(())
+}
+
+/// Serves messages from a feed to SBS-1 BaseStation clients, like dump1090 port 30003
+pub struct SbsServer {
+    address: SocketAddr,
+    metrics: ServerMetrics,
+}
+
+impl SbsServer {
+    pub fn new(address: SocketAddr, metrics: ServerMetrics) -> Self {
+        SbsServer { address, metrics }
+    }
+
+    /// Serve clients until a shutdown is requested, like Server::run
+    pub async fn run(self, feed: Feed, mut shutdown: Shutdown) -> Result<()> {
+        let listener = TcpListener::bind(self.address)
+            .await
+            .with_context(|| format!("Unable to listen for SBS clients on {}", self.address))?;
+
+        info!("Serving SBS on {}", self.address);
+
+        loop {
+            let (stream, peer) = tokio::select! {
+                accepted = listener.accept() => match accepted {
+                    Ok(accepted) => accepted,
+                    Err(e) => {
+                        debug!("accept error on {}: {:?}", self.address, e);
+                        continue;
+                    }
+                },
+                _ = shutdown.requested() => return Ok(()),
+            };
+
+            debug!("SBS client {} connected to {}", peer, self.address);
+
+            let address = self.address.to_string();
+            let metrics = self.metrics.clone();
+            let messages = feed.subscribe();
+            let shutdown = shutdown.clone();
+
+            crate::spawn_named(
+                async move {
+                    let clients = metrics.clients.with_label_values(&[&address, "output"]);
+
+                    clients.inc();
+
+                    if let Err(e) = serve_sbs(stream, &address, &metrics, messages).await {
+                        debug!("SBS client {} disconnected: {:?}", peer, e);
+                    }
+
+                    clients.dec();
+
+                    drop(shutdown);
+                },
+                &format!("beast::sbs::{}", peer),
+            );
+        }
+    }
+}
+
+async fn serve_sbs(
+    mut stream: TcpStream,
+    address: &str,
+    metrics: &ServerMetrics,
+    mut messages: broadcast::Receiver<Arc<Merged>>,
+) -> Result<()> {
+    // Each client tracks aircraft from the time it connects
+    let mut writer = SbsWriter::new();
+
+    loop {
+        let message = match messages.recv().await {
+            Ok(merged) => merged,
+            Err(RecvError::Lagged(count)) => {
+                metrics.dropped.with_label_values(&[address]).inc_by(count);
+                continue;
+            }
+            Err(RecvError::Closed) => break,
+        };
+
+        let line = match writer.write(&message.message, Instant::now(), &Local::now()) {
+            Some(line) => line,
+            None => continue,
+        };
+
+        stream.write_all(format!("{}\r\n", line).as_bytes()).await?;
+
+        metrics.sent.with_label_values(&[address]).inc();
+    }
+
+    stream.shutdown().await?;
+
+    Ok(())
+}
+
+/// A receiver pushing BEAST input connected or disconnected
+#[derive(Debug, PartialEq)]
+pub enum Connection {
+    Connected(String),
+    Disconnected(String),
+}
+
+/// Connection changes of receivers pushing BEAST input, named like their receptions
+pub type Connections = mpsc::UnboundedSender<Connection>;
+
+/// Accepts BEAST messages pushed by remote receivers, like readsb's `--net-bi-port`
+///
+/// Receivers are named by the address of the remote end so a receiver keeps its name when it
+/// reconnects from a new port.  Connections from the same address are one receiver, which is
+/// connected until the last of them disconnects.
+pub struct Input {
+    address: SocketAddr,
+    metrics: ServerMetrics,
+}
+
+impl Input {
+    pub fn new(address: SocketAddr, metrics: ServerMetrics) -> Self {
+        Input { address, metrics }
+    }
+
+    /// Accept messages until a shutdown is requested, which also disconnects every receiver
+    ///
+    /// Each receiver's first connection and last disconnection are sent to `connections` so its
+    /// state can be removed once it is gone.
+    pub async fn run(
+        self,
+        receptions: Receptions,
+        connections: Connections,
+        mut shutdown: Shutdown,
+    ) -> Result<()> {
+        let listener = TcpListener::bind(self.address)
+            .await
+            .with_context(|| format!("Unable to listen for BEAST input on {}", self.address))?;
+
+        info!("Accepting BEAST input on {}", self.address);
+
+        // Connections by receiver, changes are sent while locked so they arrive in order
+        let connected: Arc<Mutex<HashMap<String, usize>>> = Arc::default();
+
+        loop {
+            let (stream, peer) = tokio::select! {
+                accepted = listener.accept() => match accepted {
+                    Ok(accepted) => accepted,
+                    Err(e) => {
+                        debug!("accept error on {}: {:?}", self.address, e);
+                        continue;
+                    }
+                },
+                _ = shutdown.requested() => return Ok(()),
+            };
+
+            info!("BEAST input from {} connected to {}", peer, self.address);
+
+            let address = self.address.to_string();
+            let metrics = self.metrics.clone();
+            let receptions = receptions.clone();
+            let receiver = peer.ip().to_string();
+            let connected = connected.clone();
+            let connections = connections.clone();
+            let mut shutdown = shutdown.clone();
+
+            {
+                let mut connected = connected.lock().unwrap();
+                let count = connected.entry(receiver.clone()).or_insert(0);
+
+                *count += 1;
+
+                if *count == 1 {
+                    let _ = connections.send(Connection::Connected(receiver.clone()));
+                }
+            }
+
+            crate::spawn_named(
+                async move {
+                    let clients = metrics.clients.with_label_values(&[&address, "input"]);
+                    let received = metrics.received.with_label_values(&[&address]);
+
+                    clients.inc();
+
+                    let mut reader = FramedRead::new(stream, Codec::new());
+
+                    loop {
+                        let result = tokio::select! {
+                            result = reader.next() => result,
+                            _ = shutdown.requested() => break,
+                        };
+
+                        let result = match result {
+                            Some(result) => result,
+                            None => break,
+                        };
+
+                        match result {
+                            Ok(message) => {
+                                received.inc();
+
+                                let reception = Reception {
+                                    receiver: receiver.clone(),
+                                    message,
+                                };
+
+                                if receptions.send(reception).await.is_err() {
+                                    break;
+                                }
+                            }
+                            Err(e) => {
+                                debug!("error reading BEAST input from {}: {:?}", peer, e);
+                                break;
+                            }
+                        }
+                    }
+
+                    info!("BEAST input from {} disconnected", peer);
+
+                    clients.dec();
+
+                    let mut connected = connected.lock().unwrap();
+
+                    if let Some(count) = connected.get_mut(&receiver) {
+                        *count -= 1;
+
+                        if *count == 0 {
+                            connected.remove(&receiver);
+
+                            let _ = connections.send(Connection::Disconnected(receiver));
+                        }
+                    }
+                },
+                &format!("beast::input::{}", peer),
+            );
+        }
+    }
+}
@@ -0,0 +1,360 @@
+use crate::beast::encoder;
+use crate::beast::parser::decode_frame;
+use crate::beast::CPRFormat;
+use crate::beast::Message;
+use crate::beast::Position;
+
+use geo::algorithm::bearing::Bearing;
+use geo::algorithm::haversine_distance::HaversineDistance;
+use geo::Point;
+
+use rand::rngs::StdRng;
+use rand::Rng;
+use rand::SeedableRng;
+
+/// Meters in a nautical mile
+const NAUTICAL_MILE: f64 = 1852.0;
+
+/// Mean earth radius in nautical miles
+const EARTH_RADIUS: f64 = 3440.065;
+
+/// Signal level of an aircraft 10 NM from the receiver in dBFS
+const SIGNAL_AT_10_NM: f64 = -3.0;
+
+/// Frames weaker than this in dBFS are not received
+const MINIMUM_SIGNAL: f64 = -40.0;
+
+/// Seconds between transmissions of each kind of frame
+///
+/// Extended squitter rates follow DO-260B for airborne aircraft, reply rates are typical of a
+/// receiver within range of a few interrogating radars.
+const INTERVALS: [(Transmission, f64); 7] = [
+    (Transmission::Position, 0.5),
+    (Transmission::Velocity, 0.5),
+    (Transmission::Identification, 5.0),
+    (Transmission::Status, 5.0),
+    (Transmission::AllCall, 1.0),
+    (Transmission::AltitudeReply, 2.0),
+    (Transmission::IdentityReply, 4.0),
+];
+
+/// Squawks assigned to simulated aircraft, some rare ones are emergencies
+const SQUAWKS: [u16; 6] = [0x1200, 0x2000, 0x4521, 0x6214, 0x3346, 0x7700];
+
+const AIRLINES: [&str; 8] = ["UAL", "DAL", "AAL", "SWA", "ASA", "KLM", "BAW", "JBU"];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Transmission {
+    Position,
+    Velocity,
+    Identification,
+    Status,
+    AllCall,
+    AltitudeReply,
+    IdentityReply,
+}
+
+/// An aircraft flying a great-circle route
+#[derive(Debug)]
+pub struct SimulatedAircraft {
+    pub icao: u32,
+    pub call_sign: String,
+    pub squawk: u16,
+    pub position: Position,
+    /// Course in degrees clockwise from true north, changes along the great circle
+    pub track: f64,
+    /// Ground speed in knots
+    pub ground_speed: f64,
+    /// Altitude in feet
+    pub altitude: f64,
+    /// Altitude the aircraft is climbing or descending to in feet
+    pub target_altitude: f64,
+    /// Vertical rate in feet per minute
+    pub vertical_rate: f64,
+    /// Simulation time of the next transmission of each kind in seconds
+    next: [f64; INTERVALS.len()],
+    /// Format of the next airborne position
+    odd: bool,
+}
+
+impl SimulatedAircraft {
+    fn random(rng: &mut StdRng, receiver: &Position, radius: f64, time: f64) -> Self {
+        let position = destination(
+            receiver,
+            rng.gen_range(0.0..360.0),
+            radius * rng.gen::<f64>().sqrt(),
+        );
+
+        let altitude = (rng.gen_range(10.0..41.0_f64) * 1000.0).round();
+        let target_altitude = if rng.gen_bool(0.7) {
+            altitude
+        } else {
+            (rng.gen_range(3.0..41.0_f64) * 1000.0).round()
+        };
+
+        let squawk = if rng.gen_bool(0.02) {
+            SQUAWKS[SQUAWKS.len() - 1]
+        } else {
+            SQUAWKS[rng.gen_range(0..SQUAWKS.len() - 1)]
+        };
+
+        let call_sign = format!(
+            "{}{}",
+            AIRLINES[rng.gen_range(0..AIRLINES.len())],
+            rng.gen_range(1..10000)
+        );
+
+        let mut next = [0.0; INTERVALS.len()];
+        for (next, (_, interval)) in next.iter_mut().zip(INTERVALS.iter()) {
+            *next = time + rng.gen_range(0.0..*interval);
+        }
+
+        SimulatedAircraft {
+            icao: rng.gen_range(0xa0_0000..0xad_ffff),
+            call_sign,
+            squawk,
+            position,
+            track: rng.gen_range(0.0..360.0),
+            ground_speed: rng.gen_range(250.0..520.0),
+            altitude,
+            target_altitude,
+            vertical_rate: 0.0,
+            next,
+            odd: rng.gen_bool(0.5),
+        }
+    }
+
+    /// Fly for `seconds` along the great circle and toward the target altitude
+    fn fly(&mut self, seconds: f64) {
+        let next = destination(
+            &self.position,
+            self.track,
+            self.ground_speed * seconds / 3600.0,
+        );
+
+        // The course at the new position is the reverse of the bearing back to the old one
+        let back = point(&next).bearing(point(&self.position));
+        if seconds > 0.0 {
+            self.track = (back + 180.0).rem_euclid(360.0);
+        }
+        self.position = next;
+
+        let climb = self.target_altitude - self.altitude;
+
+        self.vertical_rate = match climb {
+            c if c > 0.0 => 1500.0,
+            c if c < 0.0 => -1500.0,
+            _ => 0.0,
+        };
+
+        let change = self.vertical_rate * seconds / 60.0;
+
+        self.altitude = if change.abs() >= climb.abs() {
+            self.target_altitude
+        } else {
+            self.altitude + change
+        };
+    }
+
+    fn frame(&mut self, transmission: Transmission) -> Vec<u8> {
+        let altitude = self.altitude.round() as i32;
+
+        match transmission {
+            Transmission::Position => {
+                self.odd = !self.odd;
+
+                let format = if self.odd {
+                    CPRFormat::Odd
+                } else {
+                    CPRFormat::Even
+                };
+
+                encoder::airborne_position(self.icao, altitude, &self.position, format)
+            }
+            Transmission::Velocity => {
+                let track = self.track.to_radians();
+
+                encoder::velocity(
+                    self.icao,
+                    self.ground_speed * track.sin(),
+                    self.ground_speed * track.cos(),
+                    self.vertical_rate.round() as i32,
+                )
+            }
+            Transmission::Identification => encoder::identification(self.icao, &self.call_sign),
+            Transmission::Status => {
+                let emergency = if self.squawk == 0x7700 { 1 } else { 0 };
+
+                encoder::status(self.icao, emergency, self.squawk)
+            }
+            Transmission::AllCall => encoder::all_call_reply(self.icao),
+            Transmission::AltitudeReply => encoder::altitude_reply(self.icao, altitude, false),
+            Transmission::IdentityReply => encoder::identity_reply(self.icao, self.squawk, false),
+        }
+    }
+}
+
+/// Generates BEAST messages from simulated aircraft around a receiver
+///
+/// Aircraft that fly beyond the radius are replaced by new aircraft so the amount of traffic
+/// stays steady.  Weak signals from distant aircraft are received less often.
+pub struct Simulator {
+    receiver: Position,
+    /// Radius of the simulated airspace in nautical miles
+    radius: f64,
+    /// Fraction of received frames followed by a garbage frame
+    noise: f64,
+    aircraft: Vec<SimulatedAircraft>,
+    rng: StdRng,
+    /// Simulation time in seconds
+    time: f64,
+}
+
+impl Simulator {
+    /// Simulate `count` aircraft within `radius` NM of `receiver`, a `seed` repeats a simulation
+    pub fn new(
+        receiver: Position,
+        count: usize,
+        radius: f64,
+        noise: f64,
+        seed: Option<u64>,
+    ) -> Self {
+        let mut rng = match seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        };
+
+        let aircraft = (0..count)
+            .map(|_| SimulatedAircraft::random(&mut rng, &receiver, radius, 0.0))
+            .collect();
+
+        Simulator {
+            receiver,
+            radius,
+            noise,
+            aircraft,
+            rng,
+            time: 0.0,
+        }
+    }
+
+    pub fn aircraft(&self) -> &[SimulatedAircraft] {
+        &self.aircraft
+    }
+
+    /// Advance the simulation by `seconds`, returns the messages received in timestamp order
+    pub fn advance(&mut self, seconds: f64) -> Vec<Message> {
+        let end = self.time + seconds;
+        let mut messages = vec![];
+
+        for index in 0..self.aircraft.len() {
+            let mut time = self.time;
+
+            loop {
+                let aircraft = &self.aircraft[index];
+
+                let (kind, next) = aircraft
+                    .next
+                    .iter()
+                    .enumerate()
+                    .min_by(|a, b| a.1.partial_cmp(b.1).unwrap())
+                    .map(|(kind, next)| (kind, *next))
+                    .unwrap();
+
+                if next >= end {
+                    break;
+                }
+
+                let aircraft = &mut self.aircraft[index];
+                aircraft.fly(next - time);
+                time = next;
+
+                let (transmission, interval) = INTERVALS[kind];
+                let jitter = interval * self.rng.gen_range(-0.1..0.1);
+                aircraft.next[kind] = next + interval + jitter;
+
+                let frame = aircraft.frame(transmission);
+                let distance = point(&self.receiver).haversine_distance(&point(&aircraft.position))
+                    / NAUTICAL_MILE;
+
+                if let Some(signal_level) = self.signal_level(distance) {
+                    messages.push(decode_frame(time * 1_000_000.0, signal_level, frame));
+
+                    if self.noise > 0.0 && self.rng.gen_bool(self.noise.min(1.0)) {
+                        messages.push(self.garbage(time));
+                    }
+                }
+            }
+
+            let aircraft = &mut self.aircraft[index];
+            aircraft.fly(end - time);
+
+            let distance = point(&self.receiver).haversine_distance(&point(&aircraft.position))
+                / NAUTICAL_MILE;
+
+            if distance > self.radius {
+                self.aircraft[index] =
+                    SimulatedAircraft::random(&mut self.rng, &self.receiver, self.radius, end);
+            }
+        }
+
+        self.time = end;
+
+        messages.sort_by(|a, b| a.timestamp.partial_cmp(&b.timestamp).unwrap());
+
+        messages
+    }
+
+    /// Signal level in dBFS of a frame from `distance` NM, None if the frame was not received
+    fn signal_level(&mut self, distance: f64) -> Option<f64> {
+        // Free space loss falls 20 dB per decade of distance
+        let signal_level = SIGNAL_AT_10_NM - 20.0 * (distance.max(0.5) / 10.0).log10()
+            + self.rng.gen_range(-2.0..2.0);
+
+        if signal_level < MINIMUM_SIGNAL {
+            return None;
+        }
+
+        // Weak signals are more likely to be lost to interference
+        let reception = (signal_level - MINIMUM_SIGNAL) / 20.0;
+
+        if self.rng.gen::<f64>() > reception {
+            return None;
+        }
+
+        Some(signal_level.min(0.0))
+    }
+
+    /// A weak frame of random bits that fails its CRC
+    fn garbage(&mut self, time: f64) -> Message {
+        let length = if self.rng.gen_bool(0.5) { 7 } else { 14 };
+        let frame: Vec<u8> = (0..length).map(|_| self.rng.gen()).collect();
+        let signal_level = self.rng.gen_range(MINIMUM_SIGNAL..-20.0);
+
+        decode_frame(time * 1_000_000.0 + 1.0, signal_level, frame)
+    }
+}
+
+fn point(position: &Position) -> Point<f64> {
+    Point::new(position.longitude, position.latitude)
+}
+
+/// Position `distance` NM from `start` along the great circle with initial `bearing` degrees
+fn destination(start: &Position, bearing: f64, distance: f64) -> Position {
+    let latitude = start.latitude.to_radians();
+    let longitude = start.longitude.to_radians();
+    let bearing = bearing.to_radians();
+    let angle = distance / EARTH_RADIUS;
+
+    let destination_latitude =
+        (latitude.sin() * angle.cos() + latitude.cos() * angle.sin() * bearing.cos()).asin();
+
+    let destination_longitude = longitude
+        + (bearing.sin() * angle.sin() * latitude.cos())
+            .atan2(angle.cos() - latitude.sin() * destination_latitude.sin());
+
+    Position {
+        latitude: destination_latitude.to_degrees(),
+        longitude: (destination_longitude.to_degrees() + 540.0).rem_euclid(360.0) - 180.0,
+    }
+}
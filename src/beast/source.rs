@@ -0,0 +1,368 @@
+use anyhow::anyhow;
+use anyhow::Context;
+use anyhow::Result;
+
+use async_compression::tokio::bufread::GzipDecoder;
+use async_compression::tokio::bufread::ZstdDecoder;
+
+use bytes::Bytes;
+use bytes::BytesMut;
+
+use futures_util::stream;
+use futures_util::StreamExt;
+use futures_util::TryStreamExt;
+
+use std::fs::Metadata;
+use std::io;
+use std::path::Path;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::time::Duration;
+
+use tokio::fs::File;
+use tokio::io::AsyncBufReadExt;
+use tokio::io::AsyncRead;
+use tokio::io::AsyncReadExt;
+use tokio::io::BufReader;
+use tokio::time::sleep;
+
+use tokio_util::io::ReaderStream;
+use tokio_util::io::StreamReader;
+
+/// How often a followed file is checked for new data after reaching its end
+const FOLLOW_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Size of reads from a followed file
+const FOLLOW_CHUNK: usize = 8 * 1024;
+
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+
+/// Decompressed bytes from a file, directory or standard input
+pub type Source = Pin<Box<dyn AsyncRead + Send>>;
+
+/// Compression of a capture file
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Compression {
+    None,
+    Gzip,
+    Zstd,
+}
+
+impl Compression {
+    /// Compression indicated by a file name extension, None when the extension is unknown
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("gz") => Some(Compression::Gzip),
+            Some("zst") | Some("zstd") => Some(Compression::Zstd),
+            _ => None,
+        }
+    }
+
+    /// Compression indicated by the first bytes of a file
+    ///
+    /// BEAST data starts with 0x1a and AVR data starts with `*` or `@` so neither is mistaken for
+    /// compressed data.
+    pub fn from_magic(data: &[u8]) -> Self {
+        if data.starts_with(GZIP_MAGIC) {
+            Compression::Gzip
+        } else if data.starts_with(ZSTD_MAGIC) {
+            Compression::Zstd
+        } else {
+            Compression::None
+        }
+    }
+}
+
+/// Open a source of BEAST or AVR data
+///
+/// `-` reads standard input.  A directory reads each file in it in file name order, like hourly
+/// rotated captures.  Files ending in `.gz`, `.zst` or `.zstd`, or starting with gzip or zstd
+/// magic bytes are decompressed.
+///
+/// With `follow` the file, or the last file of a directory, is read as it is appended to
+/// instead of ending.  A followed file that is replaced or truncated, like by log rotation, is
+/// read again from the start.  When following a directory, files added after the last file are
+/// read in turn.
+pub async fn open_source(path: &str, follow: bool) -> Result<Source> {
+    if path == "-" {
+        let source = decompress(tokio::io::stdin(), None).await?;
+
+        return Ok(source);
+    }
+
+    let path = Path::new(path);
+
+    let metadata = tokio::fs::metadata(path)
+        .await
+        .with_context(|| format!("Unable to open {}", path.display()))?;
+
+    if metadata.is_dir() {
+        open_directory(path, follow).await
+    } else {
+        open_file(path.to_path_buf(), follow, None)
+            .await
+            .with_context(|| format!("Unable to open {}", path.display()))
+    }
+}
+
+async fn open_directory(directory: &Path, follow: bool) -> Result<Source> {
+    let mut paths = captures(directory).await?;
+
+    if paths.is_empty() {
+        return Err(anyhow!("No files found in {}", directory.display()));
+    }
+
+    let followed = if follow {
+        paths.pop().map(|path| (path, false))
+    } else {
+        None
+    };
+
+    let files = stream::iter(paths).then(|path| async move {
+        open_file(path.clone(), false, None)
+            .await
+            .map_err(|e| annotate(&path, e))
+    });
+
+    let directory = directory.to_path_buf();
+
+    // The last file and then each file added after it, as the file before it ends
+    let followed = stream::unfold(followed, move |state| {
+        let directory = directory.clone();
+
+        async move {
+            let (path, read) = state?;
+
+            let path = if read {
+                match next_capture(&directory, &path).await {
+                    Ok(path) => path,
+                    Err(e) => return Some((Err(annotate(&directory, e)), None)),
+                }
+            } else {
+                path
+            };
+
+            let source = open_file(path.clone(), true, Some(directory))
+                .await
+                .map_err(|e| annotate(&path, e));
+
+            Some((source, Some((path, true))))
+        }
+    });
+
+    let files = files
+        .chain(followed)
+        .map_ok(ReaderStream::new)
+        .try_flatten();
+
+    Ok(Box::pin(StreamReader::new(files)))
+}
+
+fn annotate(path: &Path, error: io::Error) -> io::Error {
+    io::Error::new(error.kind(), format!("{}: {}", path.display(), error))
+}
+
+/// The first file in `directory` after `path`, waiting for one to appear
+async fn next_capture(directory: &Path, path: &Path) -> io::Result<PathBuf> {
+    loop {
+        let paths = read_captures(directory).await?;
+
+        if let Some(next) = paths.into_iter().find(|p| p.as_path() > path) {
+            return Ok(next);
+        }
+
+        sleep(FOLLOW_INTERVAL).await;
+    }
+}
+
+/// Files in `directory` in file name order, skipping hidden files
+pub(crate) async fn captures(directory: &Path) -> Result<Vec<PathBuf>> {
+    read_captures(directory)
+        .await
+        .with_context(|| format!("Unable to read {}", directory.display()))
+}
+
+async fn read_captures(directory: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut entries = tokio::fs::read_dir(directory).await?;
+
+    let mut paths = vec![];
+
+    while let Some(entry) = entries.next_entry().await? {
+        let hidden = entry.file_name().to_string_lossy().starts_with('.');
+
+        if !hidden && entry.file_type().await?.is_file() {
+            paths.push(entry.path());
+        }
+    }
+
+    paths.sort();
+
+    Ok(paths)
+}
+
+/// Open `path`, a followed file of `directory` ends once a later file appears in the directory
+async fn open_file(path: PathBuf, follow: bool, directory: Option<PathBuf>) -> io::Result<Source> {
+    let file = File::open(&path).await?;
+    let compression = Compression::from_path(&path);
+
+    if follow {
+        let follow = Follow {
+            identity: identity(&file.metadata().await?),
+            file,
+            path,
+            directory,
+            position: 0,
+        };
+
+        let chunks = Box::pin(stream::unfold(follow, Follow::next));
+
+        decompress(StreamReader::new(chunks), compression).await
+    } else {
+        decompress(file, compression).await
+    }
+}
+
+/// Decompress `reader`, detecting the compression from its first bytes when `compression` is None
+pub(crate) async fn decompress<R>(reader: R, compression: Option<Compression>) -> io::Result<Source>
+where
+    R: AsyncRead + Send + Unpin + 'static,
+{
+    let mut reader = BufReader::new(reader);
+
+    let compression = match compression {
+        Some(compression) => compression,
+        None => Compression::from_magic(reader.fill_buf().await?),
+    };
+
+    let source: Source = match compression {
+        Compression::None => Box::pin(reader),
+        Compression::Gzip => {
+            let mut decoder = GzipDecoder::new(reader);
+            decoder.multiple_members(true);
+            Box::pin(decoder)
+        }
+        Compression::Zstd => {
+            let mut decoder = ZstdDecoder::new(reader);
+            decoder.multiple_members(true);
+            Box::pin(decoder)
+        }
+    };
+
+    Ok(source)
+}
+
+/// Reads a file that is being appended to, waiting for more data at its end instead of ending
+struct Follow {
+    file: File,
+    path: PathBuf,
+    /// Directory the file is the last capture of
+    directory: Option<PathBuf>,
+    /// Device and inode of the open file
+    identity: Option<(u64, u64)>,
+    /// Bytes read from the open file
+    position: u64,
+}
+
+impl Follow {
+    /// The next chunk of the file, None when a later file appeared in the directory
+    async fn next(mut self) -> Option<(io::Result<Bytes>, Self)> {
+        let mut draining = false;
+
+        loop {
+            let mut chunk = BytesMut::with_capacity(FOLLOW_CHUNK);
+
+            match self.file.read_buf(&mut chunk).await {
+                Ok(0) => (),
+                Ok(read) => {
+                    self.position += read as u64;
+
+                    return Some((Ok(chunk.freeze()), self));
+                }
+                Err(e) => return Some((Err(e), self)),
+            }
+
+            // Data written just before the file was rotated has been read, move on
+            if draining {
+                match self.reopen().await {
+                    Ok(true) => {
+                        draining = false;
+                        continue;
+                    }
+                    Ok(false) => return None,
+                    Err(e) => return Some((Err(e), self)),
+                }
+            }
+
+            match self.rotated().await {
+                Ok(true) => {
+                    draining = true;
+                    continue;
+                }
+                Ok(false) => (),
+                Err(e) => return Some((Err(e), self)),
+            }
+
+            // At the end of the file, check again later
+            sleep(FOLLOW_INTERVAL).await;
+        }
+    }
+
+    /// True when the file was replaced or truncated, or a later file appeared in the directory
+    async fn rotated(&self) -> io::Result<bool> {
+        if let Ok(metadata) = tokio::fs::metadata(&self.path).await {
+            if self.replaced(&metadata) {
+                return Ok(true);
+            }
+        }
+
+        match self.directory {
+            Some(ref directory) => Ok(read_captures(directory)
+                .await?
+                .iter()
+                .any(|p| p > &self.path)),
+            None => Ok(false),
+        }
+    }
+
+    fn replaced(&self, metadata: &Metadata) -> bool {
+        let replaced = match (self.identity, identity(metadata)) {
+            (Some(open), Some(current)) => open != current,
+            _ => false,
+        };
+
+        replaced || metadata.len() < self.position
+    }
+
+    /// Reopen a replaced or truncated file, false when the file is done
+    async fn reopen(&mut self) -> io::Result<bool> {
+        let metadata = match tokio::fs::metadata(&self.path).await {
+            Ok(metadata) => metadata,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(false),
+            Err(e) => return Err(e),
+        };
+
+        if !self.replaced(&metadata) {
+            // a later file appeared in the directory
+            return Ok(false);
+        }
+
+        self.file = File::open(&self.path).await?;
+        self.identity = identity(&self.file.metadata().await?);
+        self.position = 0;
+
+        Ok(true)
+    }
+}
+
+#[cfg(unix)]
+fn identity(metadata: &Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+
+    Some((metadata.dev(), metadata.ino()))
+}
+
+#[cfg(not(unix))]
+fn identity(_metadata: &Metadata) -> Option<(u64, u64)> {
+    None
+}
@@ -0,0 +1,199 @@
+use crate::beast::Data;
+use crate::beast::Message;
+
+use anyhow::anyhow;
+use anyhow::Result;
+
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::fmt;
+use std::time::Duration;
+
+/// Upper bounds of the signal level buckets in dBFS, matching the signal level metric
+const SIGNAL_BUCKETS: [f64; 10] = [
+    -40.0, -35.0, -30.0, -25.0, -20.0, -15.0, -10.0, -6.0, -3.0, 0.0,
+];
+
+/// Number of most frequently heard aircraft reported
+const TOP_TALKERS: usize = 10;
+
+/// Parse a statistics interval in seconds, which must be positive
+pub fn parse_interval(secs: &str) -> Result<Duration> {
+    match secs.parse::<u64>() {
+        Ok(secs) if secs > 0 => Ok(Duration::from_secs(secs)),
+        _ => Err(anyhow!(
+            "invalid interval \"{}\", expected a positive number of seconds",
+            secs
+        )),
+    }
+}
+
+/// Aggregate counts over a stream of messages
+#[derive(Debug, Default)]
+pub struct Statistics {
+    pub frames: u64,
+    /// Frames by downlink format, Mode A/C frames have no downlink format
+    pub downlink_formats: BTreeMap<Option<u8>, u64>,
+    pub type_codes: BTreeMap<u8, u64>,
+    /// Frames that could not be decoded by error
+    pub errors: BTreeMap<String, u64>,
+    /// Frames by signal level bucket, the last bucket holds frames above 0 dBFS
+    pub signal_buckets: [u64; SIGNAL_BUCKETS.len() + 1],
+    /// Frames without a signal level, like AVR input
+    pub signal_unknown: u64,
+    signal_sum: f64,
+    signal_minimum: Option<f64>,
+    signal_maximum: Option<f64>,
+    /// Frames by announced ICAO address
+    pub aircraft: HashMap<u32, u64>,
+    /// First and last receiver timestamps in µs
+    timestamps: Option<(f64, f64)>,
+}
+
+impl Statistics {
+    pub fn new() -> Self {
+        Statistics::default()
+    }
+
+    pub fn add(&mut self, message: &Message) {
+        self.frames += 1;
+
+        *self
+            .downlink_formats
+            .entry(message.downlink_format())
+            .or_default() += 1;
+
+        if let Some(type_code) = message.type_code() {
+            *self.type_codes.entry(type_code).or_default() += 1;
+        }
+
+        if let Data::Error(error) = &message.data {
+            *self.errors.entry(error.error.clone()).or_default() += 1;
+        }
+
+        self.add_signal(message.signal_level);
+
+        if let Some(address) = message.icao().and_then(|i| u32::from_str_radix(i, 16).ok()) {
+            *self.aircraft.entry(address).or_default() += 1;
+        }
+
+        // AVR input without timestamps reports 0
+        if message.timestamp > 0.0 {
+            self.timestamps = match self.timestamps {
+                Some((first, last)) => {
+                    Some((first.min(message.timestamp), last.max(message.timestamp)))
+                }
+                None => Some((message.timestamp, message.timestamp)),
+            };
+        }
+    }
+
+    fn add_signal(&mut self, signal_level: f64) {
+        if !signal_level.is_finite() {
+            self.signal_unknown += 1;
+            return;
+        }
+
+        let bucket = SIGNAL_BUCKETS
+            .iter()
+            .position(|bound| signal_level <= *bound)
+            .unwrap_or(SIGNAL_BUCKETS.len());
+
+        self.signal_buckets[bucket] += 1;
+        self.signal_sum += signal_level;
+        self.signal_minimum = Some(
+            self.signal_minimum
+                .map_or(signal_level, |m| m.min(signal_level)),
+        );
+        self.signal_maximum = Some(
+            self.signal_maximum
+                .map_or(signal_level, |m| m.max(signal_level)),
+        );
+    }
+
+    /// Seconds of receiver time between the first and last timestamped frames
+    pub fn duration(&self) -> Option<f64> {
+        self.timestamps
+            .map(|(first, last)| (last - first) / 1_000_000.0)
+            .filter(|duration| *duration > 0.0)
+    }
+
+    /// Frames per second of receiver time
+    pub fn rate(&self) -> Option<f64> {
+        self.duration()
+            .map(|duration| self.frames as f64 / duration)
+    }
+
+    /// Most frequently heard aircraft by ICAO address, most frames first
+    pub fn top_talkers(&self, count: usize) -> Vec<(u32, u64)> {
+        let mut talkers: Vec<(u32, u64)> = self.aircraft.iter().map(|(a, c)| (*a, *c)).collect();
+
+        talkers.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        talkers.truncate(count);
+
+        talkers
+    }
+}
+
+impl fmt::Display for Statistics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Frames: {}", self.frames)?;
+
+        match (self.duration(), self.rate()) {
+            (Some(duration), Some(rate)) => {
+                writeln!(f, "Receiver time: {:.1} s, {:.1} frames/s", duration, rate)?
+            }
+            _ => writeln!(f, "Receiver time: unknown")?,
+        }
+
+        writeln!(f, "Unique ICAO addresses: {}", self.aircraft.len())?;
+
+        writeln!(f, "Downlink formats:")?;
+        for (df, count) in &self.downlink_formats {
+            match df {
+                Some(df) => writeln!(f, "  DF{}: {}", df, count)?,
+                None => writeln!(f, "  Mode A/C: {}", count)?,
+            }
+        }
+
+        writeln!(f, "ADS-B type codes:")?;
+        for (tc, count) in &self.type_codes {
+            writeln!(f, "  TC{}: {}", tc, count)?;
+        }
+
+        writeln!(f, "Decode errors:")?;
+        for (error, count) in &self.errors {
+            writeln!(f, "  {}: {}", error, count)?;
+        }
+
+        writeln!(f, "Signal level (dBFS):")?;
+        if let (Some(minimum), Some(maximum)) = (self.signal_minimum, self.signal_maximum) {
+            let known = self.frames - self.signal_unknown;
+
+            writeln!(
+                f,
+                "  minimum {:.1}, mean {:.1}, maximum {:.1}",
+                minimum,
+                self.signal_sum / known as f64,
+                maximum
+            )?;
+        }
+        for (bound, count) in SIGNAL_BUCKETS.iter().zip(self.signal_buckets.iter()) {
+            writeln!(f, "  <= {}: {}", bound, count)?;
+        }
+        writeln!(
+            f,
+            "  > {}: {}",
+            SIGNAL_BUCKETS[SIGNAL_BUCKETS.len() - 1],
+            self.signal_buckets[SIGNAL_BUCKETS.len()]
+        )?;
+        writeln!(f, "  unknown: {}", self.signal_unknown)?;
+
+        writeln!(f, "Top talkers:")?;
+        for (address, count) in self.top_talkers(TOP_TALKERS) {
+            writeln!(f, "  {:06X}: {}", address, count)?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,236 @@
+use anyhow::anyhow;
+use anyhow::Result;
+
+use crate::beast::tracker::is_emergency;
+use crate::beast::Aircraft;
+
+use geo::algorithm::bearing::Bearing;
+use geo::algorithm::haversine_distance::HaversineDistance;
+use geo::Point;
+
+use std::cmp::Ordering;
+use std::str::FromStr;
+use std::time::Instant;
+
+/// Meters in a nautical mile
+const NAUTICAL_MILE: f64 = 1852.0;
+
+/// Column headings and widths of a table row
+pub const COLUMNS: [(&str, usize); 11] = [
+    ("ICAO", 6),
+    ("Call sign", 9),
+    ("Squawk", 6),
+    ("Altitude", 8),
+    ("Speed", 5),
+    ("Track", 5),
+    ("Distance", 8),
+    ("Bearing", 7),
+    ("RSSI", 6),
+    ("Msgs", 7),
+    ("Age", 4),
+];
+
+/// Column a table of aircraft is sorted by
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Sort {
+    Icao,
+    CallSign,
+    Squawk,
+    Altitude,
+    Speed,
+    Track,
+    Distance,
+    Signal,
+    Messages,
+    Age,
+}
+
+impl Sort {
+    const ALL: [Sort; 10] = [
+        Sort::Icao,
+        Sort::CallSign,
+        Sort::Squawk,
+        Sort::Altitude,
+        Sort::Speed,
+        Sort::Track,
+        Sort::Distance,
+        Sort::Signal,
+        Sort::Messages,
+        Sort::Age,
+    ];
+
+    /// The column after this one, wrapping around
+    pub fn next(&self) -> Self {
+        let index = Sort::ALL.iter().position(|s| s == self).unwrap_or(0);
+
+        Sort::ALL[(index + 1) % Sort::ALL.len()]
+    }
+
+    /// The column before this one, wrapping around
+    pub fn previous(&self) -> Self {
+        let index = Sort::ALL.iter().position(|s| s == self).unwrap_or(0);
+
+        Sort::ALL[(index + Sort::ALL.len() - 1) % Sort::ALL.len()]
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Sort::Icao => "icao",
+            Sort::CallSign => "call-sign",
+            Sort::Squawk => "squawk",
+            Sort::Altitude => "altitude",
+            Sort::Speed => "speed",
+            Sort::Track => "track",
+            Sort::Distance => "distance",
+            Sort::Signal => "signal",
+            Sort::Messages => "messages",
+            Sort::Age => "age",
+        }
+    }
+
+    /// Order rows by this column, rows missing a value sort last and ties sort by ICAO address
+    pub fn compare(&self, a: &Row, b: &Row, reverse: bool) -> Ordering {
+        let ordering = match self {
+            Sort::Icao => Ordering::Equal,
+            Sort::CallSign => missing_last(&a.call_sign, &b.call_sign, reverse),
+            Sort::Squawk => missing_last(&a.squawk, &b.squawk, reverse),
+            Sort::Altitude => missing_last(&a.altitude, &b.altitude, reverse),
+            Sort::Speed => missing_last(&a.ground_speed, &b.ground_speed, reverse),
+            Sort::Track => missing_last(&a.track, &b.track, reverse),
+            Sort::Distance => missing_last(&a.distance, &b.distance, reverse),
+            Sort::Signal => missing_last(&a.signal_level, &b.signal_level, reverse),
+            Sort::Messages => ordered(a.messages.cmp(&b.messages), reverse),
+            Sort::Age => ordered(
+                a.age.partial_cmp(&b.age).unwrap_or(Ordering::Equal),
+                reverse,
+            ),
+        };
+
+        ordering.then_with(|| ordered(a.icao.cmp(&b.icao), reverse))
+    }
+}
+
+impl FromStr for Sort {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Sort::ALL
+            .iter()
+            .find(|sort| sort.name() == s)
+            .copied()
+            .ok_or_else(|| anyhow!("unknown sort column \"{}\"", s))
+    }
+}
+
+fn missing_last<T: PartialOrd>(a: &Option<T>, b: &Option<T>, reverse: bool) -> Ordering {
+    match (a, b) {
+        (Some(a), Some(b)) => ordered(a.partial_cmp(b).unwrap_or(Ordering::Equal), reverse),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    }
+}
+
+fn ordered(ordering: Ordering, reverse: bool) -> Ordering {
+    if reverse {
+        ordering.reverse()
+    } else {
+        ordering
+    }
+}
+
+/// An aircraft as displayed in a table
+#[derive(Clone, Debug, PartialEq)]
+pub struct Row {
+    pub icao: String,
+    pub call_sign: Option<String>,
+    pub squawk: Option<u16>,
+    /// Altitude in feet
+    pub altitude: Option<i32>,
+    /// Ground speed in knots
+    pub ground_speed: Option<f64>,
+    /// Track in degrees
+    pub track: Option<f64>,
+    /// Distance from the receiver in nautical miles
+    pub distance: Option<f64>,
+    /// Bearing from the receiver in degrees clockwise from true north
+    pub bearing: Option<f64>,
+    /// Signal level in dBFS
+    pub signal_level: Option<f64>,
+    pub messages: u64,
+    /// Seconds since the last message
+    pub age: f64,
+}
+
+impl Row {
+    pub fn new(aircraft: &Aircraft, receiver: Option<Point<f64>>, now: Instant) -> Self {
+        let position = aircraft
+            .position
+            .map(|p| Point::new(p.longitude, p.latitude));
+
+        let (distance, bearing) = match (receiver, position) {
+            (Some(receiver), Some(position)) => (
+                Some(receiver.haversine_distance(&position) / NAUTICAL_MILE),
+                Some((360.0 + receiver.bearing(position)) % 360.0),
+            ),
+            _ => (None, None),
+        };
+
+        Row {
+            icao: aircraft.icao.clone(),
+            call_sign: aircraft.call_sign.clone().filter(|c| !c.is_empty()),
+            squawk: aircraft.squawk,
+            altitude: aircraft.altitude,
+            ground_speed: aircraft.ground_speed,
+            track: aircraft.track,
+            distance,
+            bearing,
+            signal_level: aircraft.signal_level,
+            messages: aircraft.messages,
+            age: now
+                .saturating_duration_since(aircraft.last_seen)
+                .as_secs_f64(),
+        }
+    }
+
+    /// True when the aircraft is squawking 7500, 7600 or 7700
+    pub fn emergency(&self) -> bool {
+        matches!(self.squawk, Some(squawk) if is_emergency(squawk))
+    }
+
+    /// Formatted values of each column in `COLUMNS`, empty when unknown
+    pub fn cells(&self) -> Vec<String> {
+        vec![
+            format!("{:0>6}", self.icao),
+            self.call_sign.clone().unwrap_or_default(),
+            cell(self.squawk, |s| format!("{:04X}", s)),
+            cell(self.altitude, |a| a.to_string()),
+            cell(self.ground_speed, |s| format!("{:.0}", s)),
+            cell(self.track, |t| format!("{:.0}°", t)),
+            cell(self.distance, |d| format!("{:.1}", d)),
+            cell(self.bearing, |b| format!("{:.0}°", b)),
+            cell(self.signal_level, |s| format!("{:.1}", s)),
+            self.messages.to_string(),
+            format!("{:.0}", self.age),
+        ]
+    }
+}
+
+fn cell<T>(value: Option<T>, format: impl Fn(T) -> String) -> String {
+    value.map(format).unwrap_or_default()
+}
+
+/// Rows for `aircraft` ordered by `sort`, descending when `reverse`
+pub fn rows<'a>(
+    aircraft: impl Iterator<Item = &'a Aircraft>,
+    receiver: Option<Point<f64>>,
+    sort: Sort,
+    reverse: bool,
+    now: Instant,
+) -> Vec<Row> {
+    let mut rows: Vec<Row> = aircraft.map(|a| Row::new(a, receiver, now)).collect();
+
+    rows.sort_by(|a, b| sort.compare(a, b, reverse));
+
+    rows
+}
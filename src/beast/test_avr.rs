@@ -0,0 +1,110 @@
+use crate::beast::parser::decode_frame;
+use crate::beast::*;
+
+use bytes::BytesMut;
+
+use tokio_util::codec::Decoder;
+use tokio_util::codec::Encoder;
+
+#[test]
+fn test_decode() {
+    let mut buf = BytesMut::from(
+        &b"*8D4840D6202CC371C32CE0576098;\r\ngarbage\n@0B5DE6663F2E28001B9803820C;\n*8D48"[..],
+    );
+
+    let mut codec = AvrCodec::new();
+
+    let message = codec.decode(&mut buf).unwrap().unwrap();
+
+    assert_eq!(Some(17), message.downlink_format());
+    assert_eq!(Some("4840D6"), message.icao());
+    assert_eq!(0.0, message.timestamp);
+    assert_eq!(f64::NEG_INFINITY, message.signal_level);
+
+    let message = codec.decode(&mut buf).unwrap().unwrap();
+
+    assert_eq!(Some(5), message.downlink_format());
+    assert_eq!(1041493777049.1666, message.timestamp);
+
+    assert!(codec.decode(&mut buf).unwrap().is_none());
+    assert_eq!(b"*8D48"[..], buf[..]);
+}
+
+#[test]
+fn test_decode_signal() {
+    let mut buf = BytesMut::from(&b"<0B5DE6663F2E1E28001B9803820C;\n"[..]);
+
+    let message = AvrCodec::new().decode(&mut buf).unwrap().unwrap();
+
+    assert_eq!(-18.588378514285854, message.signal_level);
+}
+
+#[test]
+fn test_encode() {
+    let mut codec = AvrCodec::new();
+    let mut buf = BytesMut::new();
+
+    for line in [
+        &b"*8D4840D6202CC371C32CE0576098;\n"[..],
+        &b"@0B5DE6663F2E28001B9803820C;\n"[..],
+    ] {
+        let message = codec.decode(&mut BytesMut::from(line)).unwrap().unwrap();
+
+        codec.encode(&message, &mut buf).unwrap();
+
+        assert_eq!(line, &buf[..]);
+
+        buf.clear();
+    }
+}
+
+#[test]
+fn test_encode_signal() {
+    let mut codec = AvrCodec::new();
+    let mut buf = BytesMut::new();
+
+    let line = &b"<0B5DE6663F2E1E28001B9803820C;\n"[..];
+
+    let message = codec.decode(&mut BytesMut::from(line)).unwrap().unwrap();
+
+    codec.encode(&message, &mut buf).unwrap();
+
+    assert_eq!(line, &buf[..]);
+}
+
+#[test]
+fn test_encode_roundtrip() {
+    let mut codec = AvrCodec::new();
+    let mut buf = BytesMut::new();
+
+    let frame = vec![0x28, 0x00, 0x1b, 0x98, 0x03, 0x82, 0x0c];
+
+    for (timestamp, signal_level) in [
+        (0.0, f64::NEG_INFINITY),
+        (0.01, f64::NEG_INFINITY),
+        (1041493777049.1666, f64::NEG_INFINITY),
+        (0.0, -18.588378514285854),
+        (1041493777049.1666, -18.588378514285854),
+        (1041493777049.1666, 0.0),
+    ] {
+        let message = decode_frame(timestamp, signal_level, frame.clone());
+
+        codec.encode(&message, &mut buf).unwrap();
+
+        let decoded = codec.decode(&mut buf).unwrap().unwrap();
+
+        // timestamps below one 12MHz tick are not timestamps
+        let expected = if timestamp < 1.0 { 0.0 } else { timestamp };
+
+        assert!((decoded.timestamp - expected).abs() < 0.1);
+        assert_eq!(message.frame, decoded.frame);
+
+        if signal_level.is_finite() {
+            assert!((decoded.signal_level - signal_level).abs() < 0.01);
+        } else {
+            assert_eq!(f64::NEG_INFINITY, decoded.signal_level);
+        }
+
+        assert!(buf.is_empty());
+    }
+}
@@ -0,0 +1,83 @@
+use crate::beast::*;
+
+use bytes::BytesMut;
+
+use std::time::Duration;
+use std::time::UNIX_EPOCH;
+
+use tokio_util::codec::Decoder;
+use tokio_util::codec::Encoder;
+
+const FRAME: [u8; 23] = [
+    0x1a, 0x33, 0x0b, 0x5d, 0xe6, 0x66, 0x3f, 0x2e, 0x1e, 0x8d, 0xa6, 0xee, 0x47, 0x23, 0x05, 0x30,
+    0x76, 0xd7, 0x48, 0x20, 0x54, 0x47, 0x7b,
+];
+
+#[test]
+fn test_roundtrip() {
+    let message = Codec::new()
+        .decode(&mut BytesMut::from(&FRAME[..]))
+        .unwrap()
+        .unwrap();
+
+    // 0x1a in the receive time is escaped
+    let received = UNIX_EPOCH + Duration::from_micros(0x1a_0000_0001);
+
+    let mut codec = CaptureCodec::new();
+    let mut buf = BytesMut::new();
+
+    codec.encode((received, &message), &mut buf).unwrap();
+
+    assert_eq!(
+        &[0x1a, b'W', 0, 0, 0, 0x1a, 0x1a, 0, 0, 0, 1][..],
+        &buf[..11]
+    );
+    assert_eq!(&FRAME[..], &buf[11..]);
+
+    // Decoding waits for the whole frame
+    let mut partial = BytesMut::from(&buf[..15]);
+    assert_eq!(None, codec.decode(&mut partial).unwrap());
+
+    let captured = codec.decode(&mut buf).unwrap().unwrap();
+
+    assert_eq!(Some(received), captured.received);
+    assert_eq!(message, captured.message);
+    assert!(buf.is_empty());
+}
+
+#[test]
+fn test_decode_plain() {
+    let mut buf = BytesMut::from(&FRAME[..]);
+
+    let captured = CaptureCodec::new().decode(&mut buf).unwrap().unwrap();
+
+    assert_eq!(None, captured.received);
+    assert_eq!(Some(17), captured.message.downlink_format());
+}
+
+#[test]
+fn test_decode_annotated_plain() {
+    let message = Codec::new()
+        .decode(&mut BytesMut::from(&FRAME[..]))
+        .unwrap()
+        .unwrap();
+
+    let mut buf = BytesMut::new();
+
+    // An escaped 0x1a followed by a frame type in the receive time is not the start of a frame
+    for micros in [0x1a_0000_0001, 0x1a1a_1a1a_1a1a_1a1a, 0x0000_1a33_0000_0000] {
+        let received = UNIX_EPOCH + Duration::from_micros(micros);
+
+        CaptureCodec::new()
+            .encode((received, &message), &mut buf)
+            .unwrap();
+    }
+
+    let mut codec = Codec::new();
+
+    for _ in 0..3 {
+        assert_eq!(message, codec.decode(&mut buf).unwrap().unwrap());
+    }
+
+    assert!(buf.is_empty());
+}
@@ -0,0 +1,61 @@
+use crate::beast::*;
+
+use bytes::BytesMut;
+
+use tokio_util::codec::Decoder;
+use tokio_util::codec::Encoder;
+
+#[test]
+fn test_decode() {
+    let mut buf = BytesMut::from(
+        &[
+            0x1a, 0x32, 0x07, 0x94, 0xf8, 0x8e, 0x22, 0x26, 0x04, 0x28, 0x00, 0x1b, 0x98, 0x03,
+            0x82, 0x0c, 0x1a, 0x32,
+        ][..],
+    );
+
+    let mut codec = Codec::new();
+
+    let message = codec.decode(&mut buf).unwrap().unwrap();
+
+    assert_eq!(Some(5), message.downlink_format());
+    assert_eq!(2, buf.len());
+
+    assert!(codec.decode(&mut buf).unwrap().is_none());
+    assert_eq!(2, buf.len());
+}
+
+#[test]
+fn test_decode_resynchronize() {
+    let mut buf = BytesMut::from(
+        &[
+            0x8e, 0x22, 0x1a, 0x34, 0x00, 0x1a, 0x32, 0x07, 0x94, 0xf8, 0x8e, 0x22, 0x26, 0x04,
+            0x28, 0x00, 0x1b, 0x98, 0x03, 0x82, 0x0c,
+        ][..],
+    );
+
+    let mut codec = Codec::new();
+
+    let message = codec.decode(&mut buf).unwrap().unwrap();
+
+    assert_eq!(Some(5), message.downlink_format());
+    assert_eq!(0, buf.len());
+}
+
+#[test]
+fn test_encode() {
+    let input = [
+        0x1a, 0x33, 0x0b, 0x1a, 0x1a, 0xe6, 0x66, 0x3f, 0x2e, 0x1e, 0x8d, 0xa6, 0xee, 0x47, 0x23,
+        0x05, 0x30, 0x76, 0xd7, 0x48, 0x20, 0x54, 0x47, 0x1a, 0x1a,
+    ];
+
+    let mut buf = BytesMut::from(&input[..]);
+
+    let mut codec = Codec::new();
+
+    let message = codec.decode(&mut buf).unwrap().unwrap();
+
+    codec.encode(&message, &mut buf).unwrap();
+
+    assert_eq!(&input[..], &buf[..]);
+}
@@ -0,0 +1,170 @@
+use crate::beast::combiner::*;
+use crate::beast::parser::decode_frame;
+
+use prometheus::core::Collector;
+
+use std::time::Duration;
+
+use tokio::time::Instant;
+
+fn reception(receiver: &str, signal_level: f64, frame: &[u8]) -> Reception {
+    Reception {
+        receiver: receiver.to_string(),
+        message: decode_frame(0.0, signal_level, frame.to_vec()),
+    }
+}
+
+fn metrics() -> CombinerMetrics {
+    CombinerMetrics::new().unwrap()
+}
+
+/// Value of the `receiver` series of the metric named `name`
+fn count(metrics: &CombinerMetrics, name: &str, receiver: &str) -> Option<u64> {
+    metrics
+        .collect()
+        .into_iter()
+        .find(|family| family.get_name() == name)?
+        .get_metric()
+        .iter()
+        .find(|metric| metric.get_label()[0].get_value() == receiver)
+        .map(|metric| metric.get_counter().get_value() as u64)
+}
+
+const DF_5: [u8; 7] = [0x28, 0x00, 0x1b, 0x98, 0x03, 0x82, 0x0c];
+const DF_11: [u8; 7] = [0x5d, 0xa6, 0xa6, 0xb7, 0xfd, 0xe8, 0xb1];
+
+#[test]
+fn test_merge() {
+    let window = Duration::from_millis(500);
+    let mut combiner = Combiner::new(window, metrics());
+    let now = Instant::now();
+
+    assert!(combiner.add(reception("a", -20.0, &DF_5), now).is_empty());
+    assert!(combiner.add(reception("b", -10.0, &DF_5), now).is_empty());
+    assert!(combiner.add(reception("c", -15.0, &DF_5), now).is_empty());
+    assert!(combiner.add(reception("c", -15.0, &DF_11), now).is_empty());
+
+    assert!(combiner.expire(now + window / 2).is_empty());
+
+    let merged = combiner.expire(now + window);
+
+    assert_eq!(2, merged.len());
+    assert_eq!(vec!["b", "a", "c"], merged[0].receivers);
+    assert_eq!(-10.0, merged[0].message.signal_level);
+    assert_eq!(vec!["c"], merged[1].receivers);
+}
+
+#[test]
+fn test_merge_repeated_transmission() {
+    let window = Duration::from_millis(500);
+    let mut combiner = Combiner::new(window, metrics());
+    let now = Instant::now();
+
+    assert!(combiner.add(reception("a", -20.0, &DF_11), now).is_empty());
+
+    let later = now + window / 2;
+
+    let merged = combiner.add(reception("a", -20.0, &DF_11), later);
+
+    assert_eq!(1, merged.len());
+
+    assert!(combiner.expire(now + window).is_empty());
+    assert_eq!(1, combiner.expire(later + window).len());
+}
+
+#[test]
+fn test_merge_mode_ac() {
+    let mut combiner = Combiner::new(Duration::from_millis(500), metrics());
+
+    let merged = combiner.add(reception("a", -20.0, &[0x12, 0x34]), Instant::now());
+
+    assert_eq!(1, merged.len());
+}
+
+#[test]
+fn test_merge_keeps_best_message() {
+    let window = Duration::from_millis(500);
+    let mut combiner = Combiner::new(window, metrics());
+    let now = Instant::now();
+
+    let first = Reception {
+        receiver: "a".to_string(),
+        message: decode_frame(1000.0, -20.0, DF_5.to_vec()),
+    };
+    let best = Reception {
+        receiver: "b".to_string(),
+        message: decode_frame(987_654.0, -10.0, DF_5.to_vec()),
+    };
+
+    let worse = Reception {
+        receiver: "c".to_string(),
+        message: decode_frame(42.0, -30.0, DF_5.to_vec()),
+    };
+
+    assert!(combiner.add(first, now).is_empty());
+    assert!(combiner.add(best, now).is_empty());
+    assert!(combiner.add(worse, now).is_empty());
+
+    let merged = combiner.expire(now + window);
+
+    // The message is the best receiver's copy, timestamp and signal level together
+    assert_eq!(vec!["b", "a", "c"], merged[0].receivers);
+    assert_eq!(-10.0, merged[0].message.signal_level);
+    assert_eq!(987_654.0, merged[0].message.timestamp);
+}
+
+#[test]
+fn test_merge_zero_window() {
+    let mut combiner = Combiner::new(Duration::ZERO, metrics());
+    let now = Instant::now();
+
+    let merged = combiner.add(reception("a", -20.0, &DF_5), now);
+
+    assert_eq!(1, merged.len());
+    assert_eq!(vec!["a"], merged[0].receivers);
+
+    assert_eq!(1, combiner.add(reception("a", -20.0, &DF_5), now).len());
+    assert!(combiner.expire(now).is_empty());
+}
+
+#[test]
+fn test_metrics() {
+    let window = Duration::from_millis(500);
+    let metrics = metrics();
+    let mut combiner = Combiner::new(window, metrics.clone());
+    let mut other = Combiner::new(window, CombinerMetrics::new().unwrap());
+    let now = Instant::now();
+
+    combiner.add(reception("a", -20.0, &DF_5), now);
+    combiner.add(reception("b", -10.0, &DF_5), now);
+    combiner.expire(now + window);
+    other.add(reception("a", -20.0, &DF_11), now);
+
+    assert_eq!(
+        Some(1),
+        count(&metrics, "adsb_beast_receiver_messages_total", "a")
+    );
+    assert_eq!(
+        Some(1),
+        count(
+            &metrics,
+            "adsb_beast_receiver_best_signal_messages_total",
+            "b"
+        )
+    );
+    assert_eq!(
+        Some(1),
+        count(&metrics, "adsb_beast_receiver_overlap_messages_total", "a")
+    );
+
+    metrics.forget("a");
+
+    assert_eq!(
+        None,
+        count(&metrics, "adsb_beast_receiver_messages_total", "a")
+    );
+    assert_eq!(
+        Some(1),
+        count(&metrics, "adsb_beast_receiver_messages_total", "b")
+    );
+}
@@ -0,0 +1,145 @@
+use crate::beast::cpr::*;
+use crate::beast::*;
+
+use std::time::Duration;
+use std::time::Instant;
+
+// Frames from The 1090MHz Riddle
+// 8D40621D58C382D690C8AC2863A7 even
+// 8D40621D58C386435CC412692AD6 odd
+
+fn frames() -> (CPRFrame, CPRFrame) {
+    let now = Instant::now();
+
+    let even = CPRFrame {
+        format: CPRFormat::Even,
+        latitude: 93000,
+        longitude: 51372,
+        received: now,
+    };
+
+    let odd = CPRFrame {
+        format: CPRFormat::Odd,
+        latitude: 74158,
+        longitude: 50194,
+        received: now - Duration::from_secs(1),
+    };
+
+    (even, odd)
+}
+
+#[test]
+fn test_decode_global() {
+    let (even, odd) = frames();
+
+    let position = decode_global(&even, &odd).unwrap();
+
+    assert!((position.latitude - 52.25720).abs() < 0.00001);
+    assert!((position.longitude - 3.91937).abs() < 0.00001);
+}
+
+#[test]
+fn test_decode_global_stale() {
+    let (even, mut odd) = frames();
+
+    odd.received = even.received - Duration::from_secs(11);
+
+    assert_eq!(None, decode_global(&even, &odd));
+}
+
+#[test]
+fn test_decode_local() {
+    let (even, _) = frames();
+
+    let reference = Position {
+        latitude: 52.258,
+        longitude: 3.918,
+    };
+
+    let position = decode_local(&even, &reference);
+
+    assert!((position.latitude - 52.25720).abs() < 0.00001);
+    assert!((position.longitude - 3.91937).abs() < 0.00001);
+}
+
+#[test]
+fn test_encode() {
+    let position = Position {
+        latitude: 52.2572,
+        longitude: 3.91937,
+    };
+
+    // The odd frame was sent from a different position
+    let (even, _) = frames();
+
+    let (latitude, longitude) = encode(&position, CPRFormat::Even);
+    assert!((latitude as i64 - even.latitude as i64).abs() <= 1);
+    assert!((longitude as i64 - even.longitude as i64).abs() <= 1);
+}
+
+#[test]
+fn test_encode_roundtrip() {
+    let now = Instant::now();
+
+    for (latitude, longitude) in [(37.6188, -122.375), (-33.9461, 151.177), (0.5, -0.5)] {
+        let position = Position {
+            latitude,
+            longitude,
+        };
+
+        let (lat, lon) = encode(&position, CPRFormat::Even);
+        let even = CPRFrame {
+            format: CPRFormat::Even,
+            latitude: lat,
+            longitude: lon,
+            received: now,
+        };
+
+        let (lat, lon) = encode(&position, CPRFormat::Odd);
+        let odd = CPRFrame {
+            format: CPRFormat::Odd,
+            latitude: lat,
+            longitude: lon,
+            received: now,
+        };
+
+        let decoded = decode_global(&even, &odd).unwrap();
+
+        assert!((decoded.latitude - latitude).abs() < 0.0001);
+        assert!((decoded.longitude - longitude).abs() < 0.0001);
+    }
+}
+
+#[test]
+fn test_reachable() {
+    let reference = Position {
+        latitude: 52.258,
+        longitude: 3.918,
+    };
+
+    // About 8 NM north
+    let position = Position {
+        latitude: 52.391,
+        longitude: 3.918,
+    };
+
+    assert!(reachable(&reference, &reference, Duration::from_secs(0)));
+    assert!(reachable(&reference, &position, Duration::from_secs(60)));
+    assert!(!reachable(&reference, &position, Duration::from_secs(5)));
+}
+
+#[test]
+fn test_reachable_out_of_range() {
+    let reference = Position {
+        latitude: 52.258,
+        longitude: 3.918,
+    };
+
+    // About 240 NM north, beyond local decoding range no matter how much time passed
+    let position = Position {
+        latitude: 56.258,
+        longitude: 3.918,
+    };
+
+    assert!(!reachable(&reference, &position, Duration::from_secs(3600)));
+}
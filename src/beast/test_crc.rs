@@ -0,0 +1,12 @@
+use crate::beast::crc::*;
+
+#[test]
+fn test_checksum() {
+    let frame = [
+        0x8d, 0x48, 0x40, 0xd6, 0x20, 0x2c, 0xc3, 0x71, 0xc3, 0x2c, 0xe0, 0x57, 0x60, 0x98,
+    ];
+
+    assert_eq!(0x576098, checksum(&frame[..11]));
+    assert_eq!(0x576098, parity(&frame));
+    assert_eq!(0, checksum(&frame));
+}
@@ -0,0 +1,113 @@
+use crate::beast::crc;
+use crate::beast::encoder::*;
+use crate::beast::parser::decode_frame;
+use crate::beast::*;
+
+const ICAO: u32 = 0xA1B2C3;
+
+fn adsb(frame: Vec<u8>) -> ADSBMessage {
+    assert_eq!(0, crc::checksum(&frame));
+
+    let message = decode_frame(0.0, 0.0, frame);
+
+    assert_eq!(Some(ICAO), message.address());
+
+    match message.data {
+        Data::ExtendedSquitter(squitter) => squitter.message,
+        data => panic!("not an extended squitter: {:?}", data),
+    }
+}
+
+#[test]
+fn test_identification() {
+    match adsb(identification(ICAO, "UAL123")) {
+        ADSBMessage::AircraftIdentification(identification) => {
+            assert_eq!("UAL123  ", identification.call_sign);
+        }
+        message => panic!("unexpected {:?}", message),
+    }
+}
+
+#[test]
+fn test_airborne_position() {
+    let position = Position {
+        latitude: 37.6188,
+        longitude: -122.375,
+    };
+
+    match adsb(airborne_position(ICAO, 38000, &position, CPRFormat::Odd)) {
+        ADSBMessage::AirbornePosition(airborne) => {
+            assert_eq!(Altitude::Feet(38000), airborne.altitude);
+            assert_eq!(CPRFormat::Odd, airborne.cpr_format);
+
+            let (latitude, longitude) = cpr::encode(&position, CPRFormat::Odd);
+            assert_eq!(latitude, airborne.cpr_latitude);
+            assert_eq!(longitude, airborne.cpr_longitude);
+        }
+        message => panic!("unexpected {:?}", message),
+    }
+}
+
+#[test]
+fn test_velocity() {
+    match adsb(velocity(ICAO, -300.0, 400.0, -1280)) {
+        ADSBMessage::Velocity(velocity) => {
+            match velocity.velocity {
+                VelocityType::Ground(ground) => {
+                    let (speed, track) = tracker::ground_velocity(&ground).unwrap();
+
+                    assert!((speed - 500.0).abs() < 0.1);
+                    assert!((track - 323.13).abs() < 0.01);
+                }
+                velocity => panic!("unexpected {:?}", velocity),
+            }
+
+            assert_eq!(
+                VerticalRate::FeetPerMinute(VerticalRateSource::Barometer(-1280)),
+                velocity.vertical_rate
+            );
+        }
+        message => panic!("unexpected {:?}", message),
+    }
+}
+
+#[test]
+fn test_status() {
+    match adsb(status(ICAO, 1, 0x7700)) {
+        ADSBMessage::AircraftStatus(status) => {
+            assert_eq!(Emergency::General, status.emergency);
+            assert_eq!(0x7700, status.squawk);
+        }
+        message => panic!("unexpected {:?}", message),
+    }
+}
+
+#[test]
+fn test_replies() {
+    let message = decode_frame(0.0, 0.0, altitude_reply(ICAO, 5025, false));
+
+    assert_eq!(Some(ICAO), message.address());
+    match message.data {
+        Data::AltitudeReply(reply) => assert_eq!(Altitude::Feet(5025), reply.altitude),
+        data => panic!("unexpected {:?}", data),
+    }
+
+    let message = decode_frame(0.0, 0.0, identity_reply(ICAO, 0x1234, true));
+
+    assert_eq!(Some(ICAO), message.address());
+    match message.data {
+        Data::SurveillanceReply(reply) => {
+            assert_eq!(0x1234, reply.id);
+            assert_eq!(FlightStatus::OnGround, reply.flight_status);
+        }
+        data => panic!("unexpected {:?}", data),
+    }
+
+    let frame = all_call_reply(ICAO);
+
+    assert_eq!(0, crc::checksum(&frame));
+    match decode_frame(0.0, 0.0, frame).data {
+        Data::AllCallReply(reply) => assert_eq!("A1B2C3", reply.icao),
+        data => panic!("unexpected {:?}", data),
+    }
+}
@@ -0,0 +1,55 @@
+use crate::beast::*;
+
+use bytes::BytesMut;
+
+use tokio_util::codec::Decoder;
+
+fn message() -> Message {
+    let mut buf = BytesMut::from(
+        &[
+            0x1a, 0x33, 0x0b, 0x5d, 0xe6, 0x66, 0x3f, 0x2e, 0x1e, 0x8d, 0xa6, 0xee, 0x47, 0x23,
+            0x05, 0x30, 0x76, 0xd7, 0x48, 0x20, 0x54, 0x47, 0x7b,
+        ][..],
+    );
+
+    Codec::new().decode(&mut buf).unwrap().unwrap()
+}
+
+#[test]
+fn test_filter() {
+    let message = message();
+
+    assert!(Filter::default().matches(&message));
+    assert!("df=17".parse::<Filter>().unwrap().matches(&message));
+    assert!(!"df=11".parse::<Filter>().unwrap().matches(&message));
+    assert!("df=11,17&icao=a6ee47"
+        .parse::<Filter>()
+        .unwrap()
+        .matches(&message));
+    assert!(!"icao=A1B2C3".parse::<Filter>().unwrap().matches(&message));
+    assert!("altitude=1".parse::<Filter>().is_err());
+}
+
+#[test]
+fn test_filter_criteria() {
+    let message = message();
+
+    let matches = |filter: &str| filter.parse::<Filter>().unwrap().matches(&message);
+
+    assert!(matches("tc=4"));
+    assert!(!matches("tc=9,10"));
+    assert!(matches("signal=-20"));
+    assert!(!matches("signal=-3"));
+    assert!(matches("callsign=asa*"));
+    assert!(matches("callsign=ASA6?4"));
+    assert!(!matches("callsign=KLM*"));
+    assert!(matches("kind=position,identification"));
+    assert!(!matches("kind=error"));
+    assert!(matches(
+        "icao=A6EE47&tc=4&kind=identification&callsign=*654"
+    ));
+    assert!(!matches("icao=A6EE47&kind=position"));
+
+    assert!("kind=bogus".parse::<Filter>().is_err());
+    assert!("icao=1000000".parse::<Filter>().is_err());
+}
@@ -79,12 +79,12 @@ fn test_header_signal() {
     assert_eq!(-20.172003435238352, signal);
     assert_eq!(3, input.len());
 
-    let (input, signal) = header_signal(&input).unwrap();
+    let (input, signal) = header_signal(input).unwrap();
 
     assert_eq!(-19.831336649262745, signal);
     assert_eq!(1, input.len());
 
-    let (input, signal) = header_signal(&input).unwrap();
+    let (input, signal) = header_signal(input).unwrap();
 
     assert_eq!(-19.503528325499357, signal);
     assert_eq!(0, input.len());
@@ -147,6 +147,21 @@ fn test_parse_df_5() {
     assert_eq!(expected, data);
 }
 
+#[test]
+fn test_serialize_squawk() {
+    let data = Data::SurveillanceReply(SurveillanceReply {
+        flight_status: FlightStatus::SPI,
+        downlink_request: 20,
+        utility_message: 8,
+        id: 0x7700,
+    });
+
+    assert_eq!(
+        r#"{"SurveillanceReply":{"flight_status":"SPI","downlink_request":20,"utility_message":8,"id":"7700"}}"#,
+        serde_json::to_string(&data).unwrap()
+    );
+}
+
 #[test]
 fn test_parse_df_11() {
     let input = vec![0x5d, 0xa6, 0xa6, 0xb7, 0xfd, 0xe8, 0xb1];
@@ -232,7 +247,7 @@ fn test_parse_df_17_tc_11() {
         message: ADSBMessage::AirbornePosition(AirbornePosition {
             surveillance_status: SurveillanceStatus::NoCondition,
             single_antenna: false,
-            altitude: Altitude::Feet(500),
+            altitude: Altitude::Feet(1300),
             utc_synchronized: false,
             cpr_format: CPRFormat::Odd,
             cpr_latitude: 103214,
@@ -354,3 +369,37 @@ fn test_parse_df_17_tc_29() {
 
     assert_eq!(expected, data);
 }
+
+#[test]
+fn test_decode_frame() {
+    let frame = vec![
+        0x8d, 0xa6, 0xee, 0x47, 0x23, 0x05, 0x30, 0x76, 0xd7, 0x48, 0x20, 0x54, 0x47, 0x7b,
+    ];
+
+    let message = decode_frame(0.0, -3.0, frame.clone());
+
+    assert_eq!(Some(17), message.downlink_format());
+    assert_eq!(Some(4), message.type_code());
+    assert_eq!(Some("A6EE47"), message.icao());
+    assert_eq!(frame, message.frame);
+}
+
+#[test]
+fn test_decode_frame_wrong_length() {
+    let frame = vec![0x8d, 0xa6, 0xee, 0x47, 0x23, 0x05, 0x30];
+
+    let message = decode_frame(0.0, -3.0, frame);
+
+    match message.data {
+        Data::Error(e) => assert_eq!("downlink format 17 requires 14 bytes, got 7", e.error),
+        d => panic!("expected error, got {:?}", d),
+    }
+}
+
+#[test]
+fn test_decode_frame_mode_ac() {
+    let message = decode_frame(0.0, -3.0, vec![0x12, 0x34]);
+
+    assert_eq!(None, message.downlink_format());
+    assert_eq!(Data::Unsupported(vec![0x12, 0x34]), message.data);
+}
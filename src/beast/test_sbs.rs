@@ -0,0 +1,110 @@
+use crate::beast::crc::checksum;
+use crate::beast::parser::decode_frame;
+use crate::beast::*;
+
+use chrono::DateTime;
+use chrono::Local;
+use chrono::TimeZone;
+
+use std::time::Duration;
+use std::time::Instant;
+
+fn message(frame: &[u8]) -> Message {
+    decode_frame(0.0, -20.0, frame.to_vec())
+}
+
+fn time() -> DateTime<Local> {
+    Local.with_ymd_and_hms(2022, 1, 2, 3, 4, 5).unwrap()
+}
+
+#[test]
+fn test_identification() {
+    let mut writer = SbsWriter::new();
+
+    let ident = message(&[
+        0x8d, 0x48, 0x40, 0xd6, 0x20, 0x2c, 0xc3, 0x71, 0xc3, 0x2c, 0xe0, 0x57, 0x60, 0x98,
+    ]);
+
+    assert_eq!(
+        Some(
+            "MSG,1,1,1,4840D6,1,2022/01/02,03:04:05.000,2022/01/02,03:04:05.000,KLM1023,,,,,,,,,,,"
+                .to_string()
+        ),
+        writer.write(&ident, Instant::now(), &time())
+    );
+}
+
+#[test]
+fn test_position() {
+    let mut writer = SbsWriter::new();
+    let now = Instant::now();
+
+    let even = message(&[
+        0x8d, 0x40, 0x62, 0x1d, 0x58, 0xc3, 0x82, 0xd6, 0x90, 0xc8, 0xac, 0x28, 0x63, 0xa7,
+    ]);
+    let odd = message(&[
+        0x8d, 0x40, 0x62, 0x1d, 0x58, 0xc3, 0x86, 0x43, 0x5c, 0xc4, 0x12, 0x69, 0x2a, 0xd6,
+    ]);
+
+    assert_eq!(
+        Some(
+            "MSG,3,1,1,40621D,1,2022/01/02,03:04:05.000,2022/01/02,03:04:05.000,,38000,,,,,,,0,,0,0"
+                .to_string()
+        ),
+        writer.write(&odd, now, &time())
+    );
+
+    assert_eq!(
+        Some(
+            "MSG,3,1,1,40621D,1,2022/01/02,03:04:05.000,2022/01/02,03:04:05.000,,38000,,,52.25720,3.91937,,,0,,0,0"
+                .to_string()
+        ),
+        writer.write(&even, now + Duration::from_secs(1), &time())
+    );
+}
+
+#[test]
+fn test_velocity() {
+    let mut writer = SbsWriter::new();
+
+    let velocity = message(&[
+        0x8d, 0x48, 0x50, 0x20, 0x99, 0x44, 0x09, 0x94, 0x08, 0x38, 0x17, 0x5b, 0x28, 0x4f,
+    ]);
+
+    assert_eq!(
+        Some(
+            "MSG,4,1,1,485020,1,2022/01/02,03:04:05.000,2022/01/02,03:04:05.000,,,159,183,,,-832,,,,,"
+                .to_string()
+        ),
+        writer.write(&velocity, Instant::now(), &time())
+    );
+}
+
+#[test]
+fn test_surveillance_reply() {
+    let mut writer = SbsWriter::new();
+    let now = Instant::now();
+
+    // DF5 airborne, squawk 7700, with the address 4840D6 overlaid on the parity field
+    let mut frame = vec![0x28, 0x00, 0x0a, 0xaa];
+    let parity = checksum(&frame) ^ 0x4840d6;
+    frame.extend_from_slice(&parity.to_be_bytes()[1..]);
+
+    let surveillance = message(&frame);
+
+    assert_eq!(None, writer.write(&surveillance, now, &time()));
+
+    let ident = message(&[
+        0x8d, 0x48, 0x40, 0xd6, 0x20, 0x2c, 0xc3, 0x71, 0xc3, 0x2c, 0xe0, 0x57, 0x60, 0x98,
+    ]);
+
+    writer.write(&ident, now, &time());
+
+    assert_eq!(
+        Some(
+            "MSG,6,1,1,4840D6,1,2022/01/02,03:04:05.000,2022/01/02,03:04:05.000,,,,,,,,7700,0,-1,0,0"
+                .to_string()
+        ),
+        writer.write(&surveillance, now, &time())
+    );
+}
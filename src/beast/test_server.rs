@@ -0,0 +1,142 @@
+use crate::beast::parser::decode_frame;
+use crate::beast::*;
+use crate::shutdown::Stopper;
+
+use bytes::BytesMut;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::io::AsyncReadExt;
+use tokio::io::AsyncWriteExt;
+use tokio::net::TcpListener;
+use tokio::net::TcpStream;
+use tokio::sync::broadcast;
+use tokio::sync::mpsc;
+use tokio::time::timeout;
+
+use tokio_util::codec::Encoder;
+
+const DF_11: [u8; 7] = [0x5d, 0xa6, 0xa6, 0xb7, 0xfd, 0xe8, 0xb1];
+
+fn metrics() -> ServerMetrics {
+    ServerMetrics::new().unwrap()
+}
+
+async fn unused_address() -> std::net::SocketAddr {
+    TcpListener::bind("127.0.0.1:0")
+        .await
+        .unwrap()
+        .local_addr()
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_server_shutdown() {
+    let address = unused_address().await;
+    let (feed, _) = broadcast::channel(16);
+    let stopper = Stopper::new();
+
+    let server = tokio::spawn(
+        Server::new(address, Filter::default(), metrics()).run(feed.clone(), stopper.shutdown()),
+    );
+
+    let mut client = loop {
+        if let Ok(client) = TcpStream::connect(address).await {
+            break client;
+        }
+
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    };
+
+    while feed.receiver_count() == 0 {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+
+    let message = decode_frame(0.0, -10.0, DF_11.to_vec());
+
+    feed.send(Arc::new(Merged {
+        message,
+        receivers: vec!["roof".to_string()],
+    }))
+    .unwrap();
+
+    drop(feed);
+
+    // Messages published before the feed closed are sent, then the connection ends
+    let mut data = vec![];
+
+    timeout(Duration::from_secs(5), async {
+        tokio::join!(stopper.stop(), client.read_to_end(&mut data)).1
+    })
+    .await
+    .expect("timed out waiting for the server to stop")
+    .unwrap();
+
+    assert!(server.await.unwrap().is_ok());
+    assert_eq!(0x1a, data[0]);
+    assert_eq!(b'2', data[1]);
+}
+
+#[tokio::test]
+async fn test_input_connections() {
+    let address = unused_address().await;
+    let (receptions, mut receptions_rx) = mpsc::channel(16);
+    let (connections, mut connections_rx) = mpsc::unbounded_channel();
+    let stopper = Stopper::new();
+
+    let input = tokio::spawn(Input::new(address, metrics()).run(
+        receptions,
+        connections,
+        stopper.shutdown(),
+    ));
+
+    let connect = || async {
+        loop {
+            if let Ok(client) = TcpStream::connect(address).await {
+                break client;
+            }
+
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    };
+
+    let mut first = connect().await;
+    let second = connect().await;
+
+    let message = decode_frame(0.0, -10.0, DF_11.to_vec());
+    let mut frame = BytesMut::new();
+
+    Codec::new().encode(&message, &mut frame).unwrap();
+    first.write_all(&frame).await.unwrap();
+
+    let reception = timeout(Duration::from_secs(5), receptions_rx.recv())
+        .await
+        .unwrap()
+        .unwrap();
+
+    // Named by address alone so reconnecting from a new port is the same receiver
+    assert_eq!("127.0.0.1", reception.receiver);
+
+    let connected = connections_rx.recv().await.unwrap();
+
+    assert_eq!(Connection::Connected("127.0.0.1".to_string()), connected);
+
+    drop(first);
+    drop(second);
+
+    let disconnected = timeout(Duration::from_secs(5), connections_rx.recv())
+        .await
+        .unwrap()
+        .unwrap();
+
+    // The second connection from the same address is not a new receiver
+    assert_eq!(
+        Connection::Disconnected("127.0.0.1".to_string()),
+        disconnected
+    );
+
+    stopper.stop().await;
+
+    assert!(input.await.unwrap().is_ok());
+}
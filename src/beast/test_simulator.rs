@@ -0,0 +1,83 @@
+use crate::beast::crc;
+use crate::beast::*;
+
+use std::time::Duration;
+use std::time::Instant;
+
+const RECEIVER: Position = Position {
+    latitude: 37.6188,
+    longitude: -122.375,
+};
+
+#[test]
+fn test_advance() {
+    let mut simulator = Simulator::new(RECEIVER, 20, 50.0, 0.0, Some(1));
+
+    let start = Instant::now();
+    let mut tracker = Tracker::new();
+    let mut previous = 0.0;
+
+    for second in 1..=10 {
+        let messages = simulator.advance(1.0);
+
+        assert!(!messages.is_empty());
+
+        for message in messages {
+            assert!(message.timestamp >= previous);
+            assert!(message.timestamp < second as f64 * 1_000_000.0);
+            previous = message.timestamp;
+
+            assert!(message.signal_level <= 0.0);
+            assert!(message.address().is_some());
+
+            if let Some(17) = message.downlink_format() {
+                assert_eq!(0, crc::checksum(&message.frame));
+            }
+
+            let received = start + Duration::from_micros(message.timestamp as u64);
+            tracker.update(&message, received);
+        }
+    }
+
+    let mut compared = 0;
+
+    for aircraft in simulator.aircraft() {
+        let tracked = match tracker.get(&format!("{:X}", aircraft.icao)) {
+            Some(tracked) => tracked,
+            None => continue,
+        };
+
+        if let Some(position) = tracked.position {
+            // Aircraft move up to a quarter of a nautical mile between positions
+            assert!((position.latitude - aircraft.position.latitude).abs() < 0.01);
+            assert!((position.longitude - aircraft.position.longitude).abs() < 0.01);
+
+            compared += 1;
+        }
+
+        if let Some(call_sign) = &tracked.call_sign {
+            assert_eq!(&aircraft.call_sign, call_sign);
+        }
+    }
+
+    assert!(compared > 10);
+}
+
+#[test]
+fn test_noise() {
+    let mut simulator = Simulator::new(RECEIVER, 5, 50.0, 1.0, Some(2));
+
+    let messages = simulator.advance(5.0);
+    let icaos: Vec<u32> = simulator.aircraft().iter().map(|a| a.icao).collect();
+
+    // Garbage frames fail their CRC so they are not from a simulated aircraft
+    let garbage = messages
+        .iter()
+        .filter(|m| match m.address() {
+            Some(address) => !icaos.contains(&address),
+            None => true,
+        })
+        .count();
+
+    assert!(garbage > 0);
+}
@@ -0,0 +1,189 @@
+use crate::beast::source::captures;
+use crate::beast::source::decompress;
+use crate::beast::*;
+
+use async_compression::tokio::bufread::GzipEncoder;
+use async_compression::tokio::bufread::ZstdEncoder;
+
+use std::io::Cursor;
+use std::io::Write;
+use std::path::Path;
+use std::time::Duration;
+
+use tokio::io::AsyncReadExt;
+
+const BEAST: &[u8] = &[0x1a, 0x32, 0x07, 0x94, 0xf8, 0x8e, 0x22, 0x26];
+
+async fn gzip(data: &[u8]) -> Vec<u8> {
+    let mut compressed = vec![];
+    GzipEncoder::new(data)
+        .read_to_end(&mut compressed)
+        .await
+        .unwrap();
+
+    compressed
+}
+
+async fn read(mut source: Source) -> Vec<u8> {
+    let mut data = vec![];
+    source.read_to_end(&mut data).await.unwrap();
+
+    data
+}
+
+#[test]
+fn test_compression_from_path() {
+    assert_eq!(
+        Some(Compression::Gzip),
+        Compression::from_path(Path::new("capture.beast.gz"))
+    );
+    assert_eq!(
+        Some(Compression::Zstd),
+        Compression::from_path(Path::new("capture.beast.zst"))
+    );
+    assert_eq!(None, Compression::from_path(Path::new("capture.beast")));
+}
+
+#[test]
+fn test_compression_from_magic() {
+    assert_eq!(
+        Compression::Gzip,
+        Compression::from_magic(&[0x1f, 0x8b, 0x08, 0x00])
+    );
+    assert_eq!(
+        Compression::Zstd,
+        Compression::from_magic(&[0x28, 0xb5, 0x2f, 0xfd])
+    );
+    assert_eq!(Compression::None, Compression::from_magic(BEAST));
+    assert_eq!(Compression::None, Compression::from_magic(b"*8D4840D6;"));
+    assert_eq!(Compression::None, Compression::from_magic(&[]));
+}
+
+#[tokio::test]
+async fn test_decompress() {
+    let plain = decompress(Cursor::new(BEAST.to_vec()), None).await.unwrap();
+    assert_eq!(BEAST, read(plain).await);
+
+    // Concatenated gzip files are read completely
+    let mut compressed = gzip(BEAST).await;
+    compressed.extend(gzip(BEAST).await);
+
+    let gzip = decompress(Cursor::new(compressed), None).await.unwrap();
+    assert_eq!([BEAST, BEAST].concat(), read(gzip).await);
+
+    let mut compressed = vec![];
+    ZstdEncoder::new(BEAST)
+        .read_to_end(&mut compressed)
+        .await
+        .unwrap();
+
+    let zstd = decompress(Cursor::new(compressed), None).await.unwrap();
+    assert_eq!(BEAST, read(zstd).await);
+}
+
+#[tokio::test]
+async fn test_open_source_directory() {
+    let directory = std::env::temp_dir().join(format!("test_source_{}", std::process::id()));
+    std::fs::create_dir_all(&directory).unwrap();
+
+    std::fs::write(directory.join("2022-01-01T01.beast"), &BEAST[4..]).unwrap();
+    std::fs::write(
+        directory.join("2022-01-01T00.beast.gz"),
+        gzip(&BEAST[..4]).await,
+    )
+    .unwrap();
+    std::fs::write(directory.join(".hidden"), b"ignored").unwrap();
+
+    let paths = captures(&directory).await.unwrap();
+    let source = open_source(directory.to_str().unwrap(), false).await;
+    let data = read(source.unwrap()).await;
+
+    std::fs::remove_dir_all(&directory).unwrap();
+
+    assert_eq!(
+        vec![
+            directory.join("2022-01-01T00.beast.gz"),
+            directory.join("2022-01-01T01.beast")
+        ],
+        paths
+    );
+
+    assert_eq!(BEAST, data);
+}
+
+fn temp_directory(name: &str) -> std::path::PathBuf {
+    let directory = std::env::temp_dir().join(format!("{}_{}", name, std::process::id()));
+    std::fs::create_dir_all(&directory).unwrap();
+
+    directory
+}
+
+async fn read_exact(source: &mut Source, length: usize) -> Vec<u8> {
+    let mut data = vec![0; length];
+
+    tokio::time::timeout(Duration::from_secs(5), source.read_exact(&mut data))
+        .await
+        .expect("timed out waiting for followed data")
+        .unwrap();
+
+    data
+}
+
+#[tokio::test]
+async fn test_open_source_follow_rotated() {
+    let directory = temp_directory("test_source_follow_rotated");
+    let path = directory.join("capture.beast");
+
+    std::fs::write(&path, &BEAST[..4]).unwrap();
+
+    let mut source = open_source(path.to_str().unwrap(), true).await.unwrap();
+
+    assert_eq!(&BEAST[..4], &read_exact(&mut source, 4).await[..]);
+
+    // Rotated by renaming and creating a new file
+    std::fs::rename(&path, directory.join("capture.beast.1")).unwrap();
+    std::fs::write(&path, &BEAST[4..]).unwrap();
+
+    assert_eq!(&BEAST[4..], &read_exact(&mut source, 4).await[..]);
+
+    // Truncated and written again
+    std::fs::write(&path, &BEAST[..2]).unwrap();
+
+    let data = read_exact(&mut source, 2).await;
+
+    std::fs::remove_dir_all(&directory).unwrap();
+
+    assert_eq!(&BEAST[..2], &data[..]);
+}
+
+#[tokio::test]
+async fn test_open_source_follow_directory() {
+    let directory = temp_directory("test_source_follow_directory");
+
+    std::fs::write(directory.join("2022-01-01T00.beast"), &BEAST[..2]).unwrap();
+    std::fs::write(directory.join("2022-01-01T01.beast"), &BEAST[2..4]).unwrap();
+
+    let mut source = open_source(directory.to_str().unwrap(), true)
+        .await
+        .unwrap();
+
+    assert_eq!(&BEAST[..4], &read_exact(&mut source, 4).await[..]);
+
+    // Appended to the last file, then the next hour's file starts
+    std::fs::OpenOptions::new()
+        .append(true)
+        .open(directory.join("2022-01-01T01.beast"))
+        .unwrap()
+        .write_all(&BEAST[4..6])
+        .unwrap();
+
+    assert_eq!(&BEAST[4..6], &read_exact(&mut source, 2).await[..]);
+
+    std::fs::write(directory.join("2022-01-01T02.beast"), &BEAST[6..]).unwrap();
+
+    let data = read_exact(&mut source, 2).await;
+
+    std::fs::remove_dir_all(&directory).unwrap();
+
+    assert_eq!(&BEAST[6..], &data[..]);
+}
@@ -0,0 +1,61 @@
+use crate::beast::parser::decode_frame;
+use crate::beast::*;
+
+use std::time::Duration;
+
+#[test]
+fn test_add() {
+    let mut statistics = Statistics::new();
+
+    let ident = vec![
+        0x8d, 0x48, 0x40, 0xd6, 0x20, 0x2c, 0xc3, 0x71, 0xc3, 0x2c, 0xe0, 0x57, 0x60, 0x98,
+    ];
+    let velocity = vec![
+        0x8d, 0x48, 0x50, 0x20, 0x99, 0x44, 0x09, 0x94, 0x08, 0x38, 0x17, 0x5b, 0x28, 0x4f,
+    ];
+    // DF17 in a short frame
+    let short = vec![0x8d, 0x48, 0x40, 0xd6, 0x20, 0x2c, 0xc3];
+
+    statistics.add(&decode_frame(1_000_000.0, -21.0, ident.clone()));
+    statistics.add(&decode_frame(2_000_000.0, -2.0, ident));
+    statistics.add(&decode_frame(3_000_000.0, 1.0, velocity));
+    statistics.add(&decode_frame(5_000_000.0, f64::NEG_INFINITY, short));
+    statistics.add(&decode_frame(0.0, -30.0, vec![0x12, 0x34]));
+
+    assert_eq!(5, statistics.frames);
+    assert_eq!(Some(&4), statistics.downlink_formats.get(&Some(17)));
+    assert_eq!(Some(&1), statistics.downlink_formats.get(&None));
+    assert_eq!(Some(&2), statistics.type_codes.get(&4));
+    assert_eq!(Some(&1), statistics.type_codes.get(&19));
+    assert_eq!(
+        Some(&1),
+        statistics
+            .errors
+            .get("downlink format 17 requires 14 bytes, got 7")
+    );
+
+    assert_eq!(1, statistics.signal_unknown);
+    assert_eq!([0, 0, 1, 0, 1, 0, 0, 0, 0, 1, 1], statistics.signal_buckets);
+
+    assert_eq!(2, statistics.aircraft.len());
+    assert_eq!(
+        vec![(0x4840d6, 2), (0x485020, 1)],
+        statistics.top_talkers(10)
+    );
+
+    assert_eq!(Some(4.0), statistics.duration());
+    assert_eq!(Some(1.25), statistics.rate());
+}
+
+#[test]
+fn test_parse_interval() {
+    assert_eq!(Duration::from_secs(10), parse_interval("10").unwrap());
+
+    // tokio::time::interval panics on a zero period
+    assert_eq!(
+        "invalid interval \"0\", expected a positive number of seconds",
+        parse_interval("0").unwrap_err().to_string()
+    );
+    assert!(parse_interval("-1").is_err());
+    assert!(parse_interval("1.5").is_err());
+}
@@ -0,0 +1,93 @@
+use crate::beast::*;
+
+use geo::Point;
+
+use std::time::Duration;
+use std::time::Instant;
+
+fn aircraft(icao: &str, now: Instant) -> Aircraft {
+    let mut aircraft = Aircraft::new(icao.to_string(), now);
+    aircraft.messages = 1;
+
+    aircraft
+}
+
+#[test]
+fn test_row() {
+    let now = Instant::now();
+    let receiver = Point::new(-122.0, 37.0);
+
+    let mut a = aircraft("A1B2C3", now);
+    a.call_sign = Some("UAL123".to_string());
+    a.squawk = Some(0x7700);
+    a.altitude = Some(38000);
+    a.ground_speed = Some(452.4);
+    a.track = Some(271.6);
+    a.signal_level = Some(-12.34);
+    a.position = Some(Position {
+        latitude: 38.0,
+        longitude: -122.0,
+    });
+
+    let row = Row::new(&a, Some(receiver), now + Duration::from_secs(3));
+
+    assert!(row.emergency());
+    assert!((row.distance.unwrap() - 60.0).abs() < 0.1);
+    assert!(row.bearing.unwrap() < 0.1);
+
+    assert_eq!(
+        vec!["A1B2C3", "UAL123", "7700", "38000", "452", "272°", "60.0", "0°", "-12.3", "1", "3"],
+        row.cells()
+    );
+    assert_eq!(COLUMNS.len(), row.cells().len());
+
+    let row = Row::new(&aircraft("A1B2C4", now), None, now);
+
+    assert!(!row.emergency());
+    assert_eq!(
+        vec!["A1B2C4", "", "", "", "", "", "", "", "", "1", "0"],
+        row.cells()
+    );
+}
+
+#[test]
+fn test_rows_sort() {
+    let now = Instant::now();
+
+    let mut a = aircraft("A00001", now);
+    a.altitude = Some(5000);
+    let b = aircraft("A00002", now);
+    let mut c = aircraft("A00003", now);
+    c.altitude = Some(1000);
+
+    let tracked = [b, c, a];
+    let order = |sort, reverse| -> Vec<String> {
+        rows(tracked.iter(), None, sort, reverse, now)
+            .into_iter()
+            .map(|r| r.icao)
+            .collect()
+    };
+
+    assert_eq!(vec!["A00001", "A00002", "A00003"], order(Sort::Icao, false));
+    assert_eq!(vec!["A00003", "A00002", "A00001"], order(Sort::Icao, true));
+
+    // Aircraft without an altitude sort last in either direction
+    assert_eq!(
+        vec!["A00003", "A00001", "A00002"],
+        order(Sort::Altitude, false)
+    );
+    assert_eq!(
+        vec!["A00001", "A00003", "A00002"],
+        order(Sort::Altitude, true)
+    );
+}
+
+#[test]
+fn test_sort_names() {
+    assert_eq!(Sort::CallSign, "call-sign".parse().unwrap());
+    assert!("bogus".parse::<Sort>().is_err());
+
+    assert_eq!(Sort::CallSign, Sort::Icao.next());
+    assert_eq!(Sort::Icao, Sort::Age.next());
+    assert_eq!(Sort::Age, Sort::Icao.previous());
+}
@@ -0,0 +1,243 @@
+use crate::beast::cpr::decode_global;
+use crate::beast::cpr::decode_local;
+use crate::beast::cpr::reachable;
+use crate::beast::cpr::CPRFrame;
+use crate::beast::ADSBMessage;
+use crate::beast::AirbornePosition;
+use crate::beast::Aircraft;
+use crate::beast::Altitude;
+use crate::beast::CPRFormat;
+use crate::beast::Data;
+use crate::beast::EastWestDirection;
+use crate::beast::FlightStatus;
+use crate::beast::GroundVelocity;
+use crate::beast::Message;
+use crate::beast::NorthSouthDirection;
+use crate::beast::Velocity;
+use crate::beast::VelocityType;
+use crate::beast::VerticalRate;
+use crate::beast::VerticalRateSource;
+use crate::beast::VerticalStatus;
+
+use std::collections::HashMap;
+use std::time::Duration;
+use std::time::Instant;
+
+/// Maximum age of a previous position used as the reference for local CPR decoding
+const LOCAL_REFERENCE_AGE: Duration = Duration::from_secs(60);
+
+/// Squawks reserved for hijacking, radio failure and general emergencies
+const EMERGENCY_SQUAWKS: [u16; 3] = [0x7500, 0x7600, 0x7700];
+
+/// Tracks aircraft state from a stream of decoded messages
+#[derive(Debug, Default)]
+pub struct Tracker {
+    aircraft: HashMap<String, Aircraft>,
+}
+
+impl Tracker {
+    pub fn new() -> Self {
+        Tracker {
+            aircraft: HashMap::new(),
+        }
+    }
+
+    /// Update the aircraft that sent `message`, returns None if the sender is unknown
+    ///
+    /// Messages that announce their ICAO address start tracking an aircraft.  Messages that
+    /// overlay the address on the parity field only update aircraft that are already tracked as
+    /// a corrupt frame would otherwise create a bogus aircraft.
+    pub fn update(&mut self, message: &Message, now: Instant) -> Option<&Aircraft> {
+        let aircraft = match message.icao() {
+            Some(icao) => self
+                .aircraft
+                .entry(icao.to_string())
+                .or_insert_with(|| Aircraft::new(icao.to_string(), now)),
+            None => {
+                let icao = format!("{:X}", message.address()?);
+
+                self.aircraft.get_mut(&icao)?
+            }
+        };
+
+        aircraft.messages += 1;
+        aircraft.last_seen = now;
+
+        if message.signal_level.is_finite() {
+            aircraft.signal_level = Some(message.signal_level);
+        }
+
+        match &message.data {
+            Data::ExtendedSquitter(squitter) => update_adsb(aircraft, &squitter.message, now),
+            Data::AltitudeReply(reply) => {
+                update_altitude(aircraft, &reply.altitude);
+                update_flight_status(aircraft, &reply.flight_status);
+            }
+            Data::SurveillanceReply(reply) => {
+                aircraft.squawk = Some(reply.id);
+                update_flight_status(aircraft, &reply.flight_status);
+            }
+            Data::ACASSurveillanceReply(reply) => {
+                update_altitude(aircraft, &reply.altitude);
+                update_vertical_status(aircraft, &reply.vertical_status);
+            }
+            Data::ACASCoordinationReply(reply) => {
+                update_altitude(aircraft, &reply.altitude);
+                update_vertical_status(aircraft, &reply.vertical_status);
+            }
+            _ => (),
+        }
+
+        Some(aircraft)
+    }
+
+    /// Remove aircraft not seen within `max_age`
+    pub fn expire(&mut self, max_age: Duration, now: Instant) {
+        self.aircraft
+            .retain(|_, aircraft| now.duration_since(aircraft.last_seen) < max_age);
+    }
+
+    pub fn aircraft(&self) -> impl Iterator<Item = &Aircraft> {
+        self.aircraft.values()
+    }
+
+    pub fn get(&self, icao: &str) -> Option<&Aircraft> {
+        self.aircraft.get(icao)
+    }
+}
+
+fn update_adsb(aircraft: &mut Aircraft, message: &ADSBMessage, now: Instant) {
+    match message {
+        ADSBMessage::AircraftIdentification(identification) => {
+            aircraft.call_sign = Some(identification.call_sign.trim_end().to_string());
+        }
+        ADSBMessage::AircraftStatus(status) => aircraft.squawk = Some(status.squawk),
+        ADSBMessage::AirbornePosition(position) => {
+            update_altitude(aircraft, &position.altitude);
+            aircraft.on_ground = Some(false);
+            update_position(aircraft, position, now);
+        }
+        ADSBMessage::Velocity(velocity) => update_velocity(aircraft, velocity),
+        _ => (),
+    }
+}
+
+fn update_altitude(aircraft: &mut Aircraft, altitude: &Altitude) {
+    // Metric altitudes are not decoded
+    if let Altitude::Feet(feet) = altitude {
+        aircraft.altitude = Some(*feet);
+    }
+}
+
+fn update_flight_status(aircraft: &mut Aircraft, flight_status: &FlightStatus) {
+    match flight_status {
+        FlightStatus::Airborne | FlightStatus::AirborneAlert => aircraft.on_ground = Some(false),
+        FlightStatus::OnGround | FlightStatus::OnGroundAlert => aircraft.on_ground = Some(true),
+        _ => (),
+    }
+}
+
+fn update_vertical_status(aircraft: &mut Aircraft, vertical_status: &VerticalStatus) {
+    match vertical_status {
+        VerticalStatus::Airborne => aircraft.on_ground = Some(false),
+        VerticalStatus::Ground => aircraft.on_ground = Some(true),
+        VerticalStatus::Either => (),
+    }
+}
+
+fn update_velocity(aircraft: &mut Aircraft, velocity: &Velocity) {
+    if let VelocityType::Ground(ground) = &velocity.velocity {
+        if let Some((ground_speed, track)) = ground_velocity(ground) {
+            aircraft.ground_speed = Some(ground_speed);
+            aircraft.track = Some(track);
+        }
+    }
+
+    if let VerticalRate::FeetPerMinute(source) = &velocity.vertical_rate {
+        aircraft.vertical_rate = match source {
+            VerticalRateSource::GNSS(rate) => Some(*rate),
+            VerticalRateSource::Barometer(rate) => Some(*rate),
+        };
+    }
+
+    aircraft.on_ground = Some(false);
+}
+
+/// True when `squawk` is 7500, 7600 or 7700
+pub(crate) fn is_emergency(squawk: u16) -> bool {
+    EMERGENCY_SQUAWKS.contains(&squawk)
+}
+
+/// Ground speed in knots and track in degrees from the east-west and north-south components
+pub(crate) fn ground_velocity(velocity: &GroundVelocity) -> Option<(f64, f64)> {
+    // A raw component of 0 means no velocity information
+    if velocity.east_west_velocity == 0 || velocity.north_south_velocity == 0 {
+        return None;
+    }
+
+    let scale = if velocity.supersonic_aircraft {
+        4.0
+    } else {
+        1.0
+    };
+
+    let east = scale * f64::from(velocity.east_west_velocity - 1);
+    let east = match velocity.east_west_direction {
+        EastWestDirection::WestToEast => east,
+        EastWestDirection::EastToWest => -east,
+    };
+
+    let north = scale * f64::from(velocity.north_south_velocity - 1);
+    let north = match velocity.north_south_direction {
+        NorthSouthDirection::SouthToNorth => north,
+        NorthSouthDirection::NorthToSouth => -north,
+    };
+
+    let track = east.atan2(north).to_degrees();
+    let track = if track < 0.0 { track + 360.0 } else { track };
+
+    Some((east.hypot(north), track))
+}
+
+fn update_position(aircraft: &mut Aircraft, position: &AirbornePosition, now: Instant) {
+    let frame = CPRFrame {
+        format: position.cpr_format,
+        latitude: position.cpr_latitude,
+        longitude: position.cpr_longitude,
+        received: now,
+    };
+
+    match frame.format {
+        CPRFormat::Even => aircraft.even_cpr = Some(frame),
+        CPRFormat::Odd => aircraft.odd_cpr = Some(frame),
+    }
+
+    let reference = match (aircraft.position, aircraft.last_position) {
+        (Some(position), Some(seen)) if now.duration_since(seen) < LOCAL_REFERENCE_AGE => {
+            Some((position, seen))
+        }
+        _ => None,
+    };
+
+    let decoded = match reference {
+        Some((reference, seen)) => {
+            let position = decode_local(&frame, &reference);
+
+            // A bad decode is dropped and the reference ages out if it was the bad one
+            if reachable(&reference, &position, now.duration_since(seen)) {
+                Some(position)
+            } else {
+                None
+            }
+        }
+        None => match (&aircraft.even_cpr, &aircraft.odd_cpr) {
+            (Some(even), Some(odd)) => decode_global(even, odd),
+            _ => None,
+        },
+    };
+
+    if let Some(decoded) = decoded {
+        aircraft.position = Some(decoded);
+        aircraft.last_position = Some(now);
+    }
+}
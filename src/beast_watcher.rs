@@ -0,0 +1,678 @@
+use anyhow::Result;
+
+use crate::adsb_exporter::ErrorSender;
+use crate::beast::Client;
+use crate::beast::Combiner;
+use crate::beast::CombinerMetrics;
+use crate::beast::Connection;
+use crate::beast::Data;
+use crate::beast::Feed;
+use crate::beast::Input;
+use crate::beast::Merged;
+use crate::beast::Reception;
+use crate::beast::Receptions;
+use crate::beast::SbsServer;
+use crate::beast::Server;
+use crate::beast::ServerMetrics;
+use crate::beast::Tracker;
+use crate::configuration::BeastOutput;
+use crate::configuration::Configuration;
+use crate::health::Freshness;
+use crate::health::Health;
+use crate::range::Observation;
+use crate::range::RangeBuckets;
+use crate::range::RangeMetrics;
+use crate::range::Ranges;
+use crate::shutdown::Stopper;
+
+use geo::Point;
+
+use log::debug;
+use log::info;
+
+use prometheus::core::Collector;
+use prometheus::core::Desc;
+use prometheus::proto::MetricFamily;
+use prometheus::GaugeVec;
+use prometheus::HistogramOpts;
+use prometheus::HistogramVec;
+use prometheus::IntCounterVec;
+use prometheus::Opts;
+use prometheus::Registry;
+
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::collections::VecDeque;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use std::time::Instant;
+
+use tokio::sync::broadcast;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::mpsc;
+use tokio::time::interval;
+use tokio::time::sleep;
+
+const FREQUENCY: &str = "1090";
+
+/// Number of messages buffered for each feed subscriber before it falls behind
+const FEED_CAPACITY: usize = 4096;
+
+/// Metrics of decoded and merged BEAST messages and the servers they are sent to
+#[derive(Clone)]
+struct BeastMetrics {
+    messages: IntCounterVec,
+    adsb_messages: IntCounterVec,
+    decode_errors: IntCounterVec,
+    signal_level: HistogramVec,
+    recent_observed: GaugeVec,
+    recent_positions: GaugeVec,
+    combiner: CombinerMetrics,
+    servers: ServerMetrics,
+}
+
+impl BeastMetrics {
+    /// Unregistered metrics of BEAST sources and servers
+    fn new() -> Result<Self> {
+        Ok(BeastMetrics {
+            messages: IntCounterVec::new(
+                Opts::new(
+                    "adsb_beast_messages_total",
+                    "Number of BEAST messages received by downlink format",
+                ),
+                &["receiver", "frequency", "downlink_format"],
+            )?,
+            adsb_messages: IntCounterVec::new(
+                Opts::new(
+                    "adsb_beast_adsb_messages_total",
+                    "Number of ADS-B extended squitter messages received by type code",
+                ),
+                &["receiver", "frequency", "type_code"],
+            )?,
+            decode_errors: IntCounterVec::new(
+                Opts::new(
+                    "adsb_beast_decode_errors_total",
+                    "Number of BEAST messages that could not be decoded",
+                ),
+                &["receiver", "frequency"],
+            )?,
+            signal_level: HistogramVec::new(
+                HistogramOpts::new(
+                    "adsb_beast_signal_level_dbfs",
+                    "Signal level of received frames in dBFS, by the receiver that heard each frame best",
+                )
+                .buckets(vec![
+                    -40.0, -35.0, -30.0, -25.0, -20.0, -15.0, -10.0, -6.0, -3.0, 0.0,
+                ]),
+                &["receiver", "frequency"],
+            )?,
+            recent_observed: GaugeVec::new(
+                Opts::new(
+                    "adsb_beast_aircraft_observed_recent",
+                    "Number of aircraft observed in the recent window",
+                ),
+                &["receiver", "frequency"],
+            )?,
+            recent_positions: GaugeVec::new(
+                Opts::new(
+                    "adsb_beast_aircraft_with_position_recent",
+                    "Number of aircraft observed with a position in the recent window",
+                ),
+                &["receiver", "frequency"],
+            )?,
+            combiner: CombinerMetrics::new()?,
+            servers: ServerMetrics::new()?,
+        })
+    }
+
+    fn collectors(&self) -> Vec<&dyn Collector> {
+        vec![
+            &self.messages,
+            &self.adsb_messages,
+            &self.decode_errors,
+            &self.signal_level,
+            &self.recent_observed,
+            &self.recent_positions,
+            &self.combiner,
+            &self.servers,
+        ]
+    }
+}
+
+impl Collector for BeastMetrics {
+    fn desc(&self) -> Vec<&Desc> {
+        self.collectors()
+            .into_iter()
+            .flat_map(|c| c.desc())
+            .collect()
+    }
+
+    fn collect(&self) -> Vec<MetricFamily> {
+        self.collectors()
+            .into_iter()
+            .flat_map(|c| c.collect())
+            .collect()
+    }
+}
+
+/// Decodes and merges messages from BEAST sources, exports metrics for them and serves them to
+/// BEAST and SBS clients
+pub struct BeastWatcher {
+    addresses: Vec<String>,
+    input_address: Option<SocketAddr>,
+    outputs: Vec<BeastOutput>,
+    sbs_outputs: Vec<SocketAddr>,
+    reconnect_interval: Duration,
+    merge_window: Duration,
+    position: Option<Point<f64>>,
+    range: RangeBuckets,
+    recent: Duration,
+    registry: Registry,
+    metrics: BeastMetrics,
+    health: Health,
+    feed: Feed,
+}
+
+/// Aircraft tracking state of a BEAST receiver for metrics
+struct Receiver {
+    tracker: Tracker,
+    observations: VecDeque<(Instant, Observation)>,
+    ranges: RangeMetrics,
+    /// When a receiver pushing input disconnected
+    disconnected: Option<Instant>,
+}
+
+impl Receiver {
+    /// Tracking state of the named receiver with its range metrics registered in `registry`
+    fn new(name: &str, registry: &Registry) -> Result<Self> {
+        let ranges = RangeMetrics::new(
+            Opts::new(
+                "adsb_beast_aircraft_observations_recent",
+                "Number of decoded aircraft positions by range and bearing in the recent window",
+            )
+            .const_label("receiver", name)
+            .const_label("frequency", FREQUENCY),
+            Opts::new(
+                "adsb_beast_aircraft_ranges_recent",
+                "Maximum range to a decoded aircraft position by bearing in the recent window",
+            )
+            .const_label("receiver", name)
+            .const_label("frequency", FREQUENCY),
+        )?;
+
+        registry.register(Box::new(ranges.clone()))?;
+
+        Ok(Receiver {
+            tracker: Tracker::new(),
+            observations: VecDeque::new(),
+            ranges,
+            disconnected: None,
+        })
+    }
+}
+
+/// Aircraft tracking state for metrics
+struct State {
+    receivers: HashMap<String, Receiver>,
+    /// Registry of the range metrics of each receiver
+    registry: Registry,
+    metrics: BeastMetrics,
+    range: RangeBuckets,
+    /// How long an aircraft or position counts as recent
+    recent: Duration,
+    /// How long messages of a disconnected receiver may still be merging
+    merge_window: Duration,
+    /// Readiness of each BEAST server
+    servers: HashMap<String, Freshness>,
+    /// Readiness of the BEAST input, from any receiver pushing to it
+    input: Option<Freshness>,
+}
+
+impl BeastWatcher {
+    /// A watcher for the BEAST sources and outputs of `configuration` with metrics registered in
+    /// `registry` and sources tracked in `health`
+    pub fn new(configuration: &Configuration, registry: Registry, health: Health) -> Result<Self> {
+        let addresses = configuration.beast_address.clone();
+        let input_address = configuration.beast_input_address;
+        let outputs = configuration.beast_output_address.clone();
+        let sbs_outputs = configuration.sbs_output_address.clone();
+        let reconnect_interval = configuration.beast_reconnect_interval;
+        let merge_window = configuration.beast_merge_window;
+
+        let position = match (configuration.latitude, configuration.longitude) {
+            (Some(latitude), Some(longitude)) => Some(Point::new(longitude, latitude)),
+            _ => None,
+        };
+
+        let range = configuration.range;
+        let recent = configuration.recent_window;
+
+        let metrics = BeastMetrics::new()?;
+        registry.register(Box::new(metrics.clone()))?;
+
+        let (feed, _) = broadcast::channel(FEED_CAPACITY);
+
+        Ok(BeastWatcher {
+            addresses,
+            input_address,
+            outputs,
+            sbs_outputs,
+            reconnect_interval,
+            merge_window,
+            position,
+            range,
+            recent,
+            registry,
+            metrics,
+            health,
+            feed,
+        })
+    }
+
+    /// Start reading, merging and serving messages, stopping the returned Stopper disconnects
+    /// every source then sends clients the messages merged before the sources disconnected
+    pub async fn start(self, error_tx: ErrorSender) -> Stopper {
+        let stopper = Stopper::new();
+        let messages = self.feed.subscribe();
+        let (connections, connections_rx) = mpsc::unbounded_channel();
+        let position = self.position;
+        let range = self.range;
+
+        // A lone BEAST server has nothing to merge with, so don't hold its messages for the window
+        let merge_window = if self.addresses.len() == 1 && self.input_address.is_none() {
+            Duration::ZERO
+        } else {
+            self.merge_window
+        };
+
+        // Sources are ready once messages from them are merged
+        let servers = self
+            .addresses
+            .iter()
+            .map(|address| {
+                let freshness = self.health.freshness(address, "beast", &tcp_url(address));
+                freshness.track();
+
+                (address.clone(), freshness)
+            })
+            .collect();
+
+        let input = self.input_address.map(|address| {
+            let address = address.to_string();
+            let freshness = self
+                .health
+                .freshness(&address, "beast_input", &tcp_url(&address));
+            freshness.track();
+
+            freshness
+        });
+
+        let state = State {
+            receivers: HashMap::new(),
+            registry: self.registry.clone(),
+            metrics: self.metrics.clone(),
+            range,
+            recent: self.recent,
+            merge_window,
+            servers,
+            input,
+        };
+
+        let metrics_error_tx = error_tx.clone();
+
+        crate::spawn_named(
+            async move {
+                if let Err(e) = update_metrics(messages, connections_rx, position, state).await {
+                    send_error(metrics_error_tx, e).await;
+                }
+            },
+            "beast::metrics",
+        );
+
+        let (receptions, receptions_rx) = mpsc::channel(FEED_CAPACITY);
+        let combiner = Combiner::new(merge_window, self.metrics.combiner.clone());
+        let feed = self.feed.clone();
+        let shutdown = stopper.shutdown();
+
+        crate::spawn_named(
+            async move {
+                combiner.run(receptions_rx, feed).await;
+
+                drop(shutdown);
+            },
+            "beast::combiner",
+        );
+
+        for address in self.addresses {
+            info!("Watching BEAST server at {}", address);
+
+            let name = format!("beast::client::{}", address);
+            let reconnect_interval = self.reconnect_interval;
+            let receptions = receptions.clone();
+            let mut shutdown = stopper.shutdown();
+
+            crate::spawn_named(
+                async move {
+                    tokio::select! {
+                        _ = read_server(address, reconnect_interval, receptions) => (),
+                        _ = shutdown.requested() => (),
+                    }
+                },
+                &name,
+            );
+        }
+
+        if let Some(address) = self.input_address {
+            let input = Input::new(address, self.metrics.servers.clone());
+            let receptions = receptions.clone();
+            let error_tx = error_tx.clone();
+            let shutdown = stopper.shutdown();
+
+            crate::spawn_named(
+                async move {
+                    if let Err(e) = input.run(receptions, connections, shutdown).await {
+                        send_error(error_tx, e).await;
+                    }
+                },
+                &format!("beast::input::{}", address),
+            );
+        }
+
+        for output in self.outputs {
+            let server = Server::new(output.address, output.filter, self.metrics.servers.clone());
+            let feed = self.feed.clone();
+            let error_tx = error_tx.clone();
+            let shutdown = stopper.shutdown();
+
+            crate::spawn_named(
+                async move {
+                    if let Err(e) = server.run(feed, shutdown).await {
+                        send_error(error_tx, e).await;
+                    }
+                },
+                &format!("beast::server::{}", output.address),
+            );
+        }
+
+        for address in self.sbs_outputs {
+            let server = SbsServer::new(address, self.metrics.servers.clone());
+            let feed = self.feed.clone();
+            let error_tx = error_tx.clone();
+            let shutdown = stopper.shutdown();
+
+            crate::spawn_named(
+                async move {
+                    if let Err(e) = server.run(feed, shutdown).await {
+                        send_error(error_tx, e).await;
+                    }
+                },
+                &format!("beast::sbs::{}", address),
+            );
+        }
+
+        stopper
+    }
+}
+
+/// URL of a TCP source for readiness
+pub(crate) fn tcp_url(address: &str) -> String {
+    format!("tcp://{}", address)
+}
+
+/// Send a failure of a watcher task to the exporter
+pub(crate) async fn send_error(error_tx: ErrorSender, error: anyhow::Error) {
+    error_tx
+        .send(error)
+        .await
+        .expect("Error channel failed unexpectedly, bug?");
+}
+
+async fn read_server(address: String, reconnect_interval: Duration, receptions: Receptions) {
+    loop {
+        match Client::new(address.clone()).await {
+            Ok(mut client) => {
+                info!("Connected to BEAST server at {}", address);
+
+                loop {
+                    match client.read().await {
+                        Some(Ok(message)) => {
+                            let reception = Reception {
+                                receiver: address.clone(),
+                                message,
+                            };
+
+                            if receptions.send(reception).await.is_err() {
+                                return;
+                            }
+                        }
+                        Some(Err(e)) => {
+                            debug!("error reading from {}: {:?}", address, e);
+                            break;
+                        }
+                        None => {
+                            info!("BEAST server at {} closed the connection", address);
+                            break;
+                        }
+                    }
+                }
+            }
+            Err(e) => info!("{:#}", e),
+        }
+
+        sleep(reconnect_interval).await;
+    }
+}
+
+async fn update_metrics(
+    mut messages: broadcast::Receiver<Arc<Merged>>,
+    mut connections: mpsc::UnboundedReceiver<Connection>,
+    position: Option<Point<f64>>,
+    mut state: State,
+) -> Result<()> {
+    if position.is_none() {
+        info!("Receiver position unknown, set --latitude and --longitude for BEAST range metrics");
+    }
+
+    let mut recent_interval = interval(Duration::from_secs(1));
+
+    // Without a BEAST input nothing sends connection changes
+    let mut inputs = true;
+
+    loop {
+        tokio::select! {
+            result = messages.recv() => match result {
+                Ok(merged) => update_message(&mut state, position, &merged)?,
+                Err(RecvError::Lagged(count)) => {
+                    debug!("BEAST metrics fell behind, skipped {} messages", count);
+                }
+                Err(RecvError::Closed) => break,
+            },
+            connection = connections.recv(), if inputs => match connection {
+                Some(connection) => update_connection(&mut state, connection, Instant::now()),
+                None => inputs = false,
+            },
+            _ = recent_interval.tick() => update_recent(&mut state)?,
+        }
+    }
+
+    Ok(())
+}
+
+fn update_message(state: &mut State, position: Option<Point<f64>>, merged: &Merged) -> Result<()> {
+    let message = &merged.message;
+
+    let downlink_format = match message.downlink_format() {
+        Some(df) => df.to_string(),
+        None => "mode_ac".to_string(),
+    };
+
+    let now = Instant::now();
+
+    // Every receiver heard the message, only the best signal level is known
+    if let Some(best) = merged.receivers.first() {
+        state
+            .metrics
+            .signal_level
+            .with_label_values(&[best, FREQUENCY])
+            .observe(message.signal_level);
+    }
+
+    for name in &merged.receivers {
+        // A message is data arriving, like a fetch without a `now` timestamp
+        if let Some(freshness) = state.servers.get(name).or(state.input.as_ref()) {
+            freshness.fetched(None);
+        }
+
+        state
+            .metrics
+            .messages
+            .with_label_values(&[name, FREQUENCY, &downlink_format])
+            .inc();
+
+        if let Some(type_code) = message.type_code() {
+            state
+                .metrics
+                .adsb_messages
+                .with_label_values(&[name, FREQUENCY, &type_code.to_string()])
+                .inc();
+        }
+
+        if let Data::Error(_) = message.data {
+            state
+                .metrics
+                .decode_errors
+                .with_label_values(&[name, FREQUENCY])
+                .inc();
+        }
+
+        let range = &state.range;
+        let receiver = match state.receivers.entry(name.clone()) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(Receiver::new(name, &state.registry)?),
+        };
+
+        let aircraft = match receiver.tracker.update(message, now) {
+            Some(aircraft) => aircraft,
+            None => continue,
+        };
+
+        if let (Some(receiver_position), Some(position), Some(seen)) =
+            (position, aircraft.position, aircraft.last_position)
+        {
+            // only positions decoded from this message
+            if seen == now {
+                let aircraft = Point::new(position.longitude, position.latitude);
+
+                receiver
+                    .observations
+                    .push_back((now, Observation::new(receiver_position, aircraft, range)));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn update_connection(state: &mut State, connection: Connection, now: Instant) {
+    match connection {
+        Connection::Connected(name) => {
+            if let Some(receiver) = state.receivers.get_mut(&name) {
+                receiver.disconnected = None;
+            }
+        }
+        Connection::Disconnected(name) => {
+            if let Some(receiver) = state.receivers.get_mut(&name) {
+                receiver.disconnected = Some(now);
+            }
+        }
+    }
+}
+
+/// Remove the state and series of receivers that disconnected, once their last messages merged
+fn remove_disconnected(state: &mut State, now: Instant) {
+    let merge_window = state.merge_window;
+
+    let gone: Vec<String> = state
+        .receivers
+        .iter()
+        .filter(|(_, receiver)| match receiver.disconnected {
+            Some(disconnected) => now.duration_since(disconnected) > merge_window,
+            None => false,
+        })
+        .map(|(name, _)| name.clone())
+        .collect();
+
+    for name in gone {
+        debug!("removing BEAST input receiver {}", name);
+
+        if let Some(receiver) = state.receivers.remove(&name) {
+            state.registry.unregister(Box::new(receiver.ranges)).ok();
+        }
+
+        let keep = |labels: &HashMap<&str, &str>| labels.get("receiver") != Some(&name.as_str());
+
+        let metrics = &state.metrics;
+
+        crate::series::retain(&metrics.messages, keep);
+        crate::series::retain(&metrics.adsb_messages, keep);
+        crate::series::retain(&metrics.decode_errors, keep);
+        crate::series::retain(&metrics.signal_level, keep);
+        crate::series::retain(&metrics.recent_observed, keep);
+        crate::series::retain(&metrics.recent_positions, keep);
+
+        metrics.combiner.forget(&name);
+    }
+}
+
+fn update_recent(state: &mut State) -> Result<()> {
+    let now = Instant::now();
+    let recent = state.recent;
+
+    remove_disconnected(state, now);
+
+    for (name, receiver) in state.receivers.iter_mut() {
+        receiver.tracker.expire(recent, now);
+
+        while let Some((seen, _)) = receiver.observations.front() {
+            if now.duration_since(*seen) < recent {
+                break;
+            }
+
+            receiver.observations.pop_front();
+        }
+
+        let observed = receiver.tracker.aircraft().count();
+
+        let positions = receiver
+            .tracker
+            .aircraft()
+            .filter(|a| match a.last_position {
+                Some(seen) => now.duration_since(seen) < recent,
+                None => false,
+            })
+            .count();
+
+        state
+            .metrics
+            .recent_observed
+            .with_label_values(&[name, FREQUENCY])
+            .set(observed as f64);
+        state
+            .metrics
+            .recent_positions
+            .with_label_values(&[name, FREQUENCY])
+            .set(positions as f64);
+
+        let mut ranges = Ranges::default();
+
+        receiver
+            .observations
+            .iter()
+            .for_each(|(_, observation)| ranges.add(observation));
+
+        receiver.ranges.update(&ranges)?;
+    }
+
+    Ok(())
+}
@@ -1,65 +1,190 @@
 use adsb_exporter::ADSBExporter;
+use adsb_exporter::BeastWatcher;
 use adsb_exporter::Configuration;
-use adsb_exporter::DumpWatcher;
+use adsb_exporter::DumpWatchers;
+use adsb_exporter::Health;
+use adsb_exporter::SbsWatcher;
+use adsb_exporter::Stopper;
 
 use anyhow::anyhow;
 use anyhow::Result;
 
 use clap::ErrorKind;
 use clap::IntoApp;
-use clap::Parser;
 
 use env_logger::Builder;
 use env_logger::Env;
 
 use log::error;
+use log::info;
+use log::warn;
 
+use prometheus::Registry;
+
+use std::path::Path;
+use std::time::Duration;
+use std::time::SystemTime;
+
+use tokio::signal::unix::signal;
+use tokio::signal::unix::SignalKind;
 use tokio::sync::mpsc;
+use tokio::time::interval;
+use tokio::time::timeout;
+
+/// How often the configuration file is checked for changes with --watch-config
+const WATCH_INTERVAL: Duration = Duration::from_secs(5);
+
+/// How long in-flight scrapes and BEAST and SBS clients have to finish after a shutdown is
+/// requested
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Exit codes for a second SIGINT or SIGTERM received while shutting down, 128 + signal number
+const INTERRUPTED: i32 = 130;
+const TERMINATED: i32 = 143;
 
 #[tokio::main]
 async fn main() -> Result<()> {
     Builder::from_env(Env::default().default_filter_or("info")).init();
 
-    let configuration = Configuration::parse();
+    let mut configuration = Configuration::load()?;
 
     if configuration.enable_console_subscriber {
         console_subscriber::init();
     }
 
-    if configuration.dump1090_url.is_none() && configuration.dump978_url.is_none() {
+    if configuration.receivers.is_empty()
+        && configuration.beast_address.is_empty()
+        && configuration.beast_input_address.is_none()
+        && configuration.sbs_address.is_empty()
+    {
         let mut app = Configuration::into_app();
         app.error(
             ErrorKind::MissingRequiredArgument,
-            "You must provide at least one dump URL",
+            "You must provide at least one dump URL, receiver, BEAST or SBS source",
         )
         .exit();
     }
 
-    if let Some(ref base_uri) = configuration.dump1090_url {
-        DumpWatcher::new(&configuration, 1090, base_uri.to_string())
-            .start()
-            .await;
+    let (error_tx, mut error_rx) = mpsc::channel(1);
+
+    let health = Health::new(configuration.readiness_window);
+
+    // Every metric is registered here and nothing is registered in the default registry
+    let registry = Registry::new();
+
+    let sbs = if configuration.sbs_address.is_empty() {
+        None
+    } else {
+        Some(
+            SbsWatcher::new(&configuration, registry.clone(), health.clone())?
+                .start(error_tx.clone())
+                .await,
+        )
     };
 
-    if let Some(ref base_uri) = configuration.dump978_url {
-        DumpWatcher::new(&configuration, 978, base_uri.to_string())
-            .start()
-            .await;
+    let mut watchers = DumpWatchers::new(
+        registry.clone(),
+        health.clone(),
+        configuration.collection(),
+        error_tx.clone(),
+    )?;
+    watchers.update(&configuration.receivers).await?;
+
+    let beast =
+        if !configuration.beast_address.is_empty() || configuration.beast_input_address.is_some() {
+            Some(
+                BeastWatcher::new(&configuration, registry.clone(), health.clone())?
+                    .start(error_tx.clone())
+                    .await,
+            )
+        } else {
+            None
+        };
+
+    let exporter = ADSBExporter::new(
+        configuration.bind_address,
+        registry,
+        health,
+        watchers.scrape_targets(),
+    )?;
+    let shutdown = exporter.shutdown();
+    let server = exporter.start(error_tx.clone()).await;
+
+    let mut hangup = signal(SignalKind::hangup())?;
+    let mut interrupt = signal(SignalKind::interrupt())?;
+    let mut terminate = signal(SignalKind::terminate())?;
+    let mut watch = interval(WATCH_INTERVAL);
+    let mut modified = modified_time(&configuration.config);
+
+    // Exit 0 for a requested shutdown so supervisors don't treat it as a failure
+    let mut exit_code = loop {
+        tokio::select! {
+            error = error_rx.recv() => break report_error(error),
+            _ = interrupt.recv() => {
+                info!("Received SIGINT, shutting down");
+                break 0;
+            },
+            _ = terminate.recv() => {
+                info!("Received SIGTERM, shutting down");
+                break 0;
+            },
+            _ = hangup.recv() => {
+                info!("Received SIGHUP, reloading configuration");
+                reload(&mut configuration, &mut watchers).await;
+            },
+            _ = watch.tick(), if configuration.watch_config => {
+                let current = modified_time(&configuration.config);
+
+                if current != modified {
+                    modified = current;
+                    info!("Configuration file changed, reloading configuration");
+                    reload(&mut configuration, &mut watchers).await;
+                }
+            },
+        }
     };
 
-    let (error_tx, error_rx) = mpsc::channel(1);
+    watchers.stop();
+    shutdown.notify_one();
 
-    ADSBExporter::new(configuration.bind_address)?
-        .start(error_tx.clone())
-        .await;
+    let finished = async {
+        let (_, _, server) = tokio::join!(stop(sbs), stop(beast), server);
 
-    let exit_code = wait_for_error(error_rx).await;
+        if let Err(e) = server {
+            error!("Server task failed: {}", e);
+        }
+    };
+
+    tokio::select! {
+        result = timeout(SHUTDOWN_TIMEOUT, finished) => if result.is_err() {
+            error!(
+                "Scrapes and clients did not finish within {:?} of shutting down",
+                SHUTDOWN_TIMEOUT
+            );
+
+            exit_code = 1;
+        },
+        _ = interrupt.recv() => {
+            info!("Received SIGINT while shutting down, exiting immediately");
+            exit_code = INTERRUPTED;
+        },
+        _ = terminate.recv() => {
+            info!("Received SIGTERM while shutting down, exiting immediately");
+            exit_code = TERMINATED;
+        },
+    }
 
     std::process::exit(exit_code);
 }
 
-async fn wait_for_error(mut error_rx: mpsc::Receiver<anyhow::Error>) -> i32 {
-    let error = match error_rx.recv().await {
+async fn stop(stopper: Option<Stopper>) {
+    if let Some(stopper) = stopper {
+        stopper.stop().await;
+    }
+}
+
+fn report_error(error: Option<anyhow::Error>) -> i32 {
+    let error = match error {
         Some(e) => e,
         None => anyhow!("Error reporting channel closed unexpectedly, bug?"),
     };
@@ -68,3 +193,39 @@ async fn wait_for_error(mut error_rx: mpsc::Receiver<anyhow::Error>) -> i32 {
 
     1
 }
+
+/// Restart receivers from the reloaded configuration, keeps the current receivers if the
+/// configuration is invalid or the receivers can't be updated
+async fn reload(configuration: &mut Configuration, watchers: &mut DumpWatchers) {
+    let reloaded = match configuration.reload() {
+        Ok(reloaded) => reloaded,
+        Err(e) => {
+            error!("Keeping current configuration, reload failed: {:#}", e);
+            return;
+        }
+    };
+
+    if let Err(e) = watchers.update(&reloaded.receivers).await {
+        error!("Unable to update receivers: {:#}", e);
+        return;
+    }
+
+    info!("Watching receivers {}", watchers.names().join(", "));
+
+    let restart_required = configuration.restart_required(&reloaded);
+
+    if !restart_required.is_empty() {
+        warn!(
+            "Restart to apply changes to {}, only receivers were reloaded",
+            restart_required.join(", ")
+        );
+    }
+
+    configuration.receivers = reloaded.receivers;
+}
+
+fn modified_time(path: &Option<impl AsRef<Path>>) -> Option<SystemTime> {
+    path.as_ref()
+        .and_then(|path| std::fs::metadata(path).ok())
+        .and_then(|metadata| metadata.modified().ok())
+}
@@ -0,0 +1,208 @@
+use adsb_exporter::beast::open_source;
+use adsb_exporter::beast::CaptureCodec;
+use adsb_exporter::beast::Captured;
+use adsb_exporter::beast::Feed;
+use adsb_exporter::beast::Filter;
+use adsb_exporter::beast::Merged;
+use adsb_exporter::beast::Server;
+use adsb_exporter::beast::ServerMetrics;
+use adsb_exporter::Stopper;
+use anyhow::anyhow;
+use anyhow::Result;
+use clap::Parser;
+use futures_util::StreamExt;
+use log::info;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use std::time::UNIX_EPOCH;
+use tokio::sync::broadcast;
+use tokio::time::sleep;
+use tokio::time::sleep_until;
+use tokio::time::Instant;
+use tokio_util::codec::FramedRead;
+
+/// Number of messages buffered for each client before it falls behind
+const FEED_CAPACITY: usize = 4096;
+
+/// Replay a BEAST capture as a BEAST server
+///
+/// Messages are paced by their receiver timestamps, or by their wall-clock receive times when
+/// the receiver did not provide timestamps.  Replay starts when the first client connects.
+#[derive(Parser)]
+#[clap(about, version)]
+struct Args {
+    /// Capture written by dump_beast --format capture, or plain BEAST data
+    ///
+    /// Like dump_beast --file this may be compressed, a directory of captures, or - for standard
+    /// input.
+    #[clap(long)]
+    pub file: String,
+
+    /// Listen address for BEAST clients
+    #[clap(long, default_value = "127.0.0.1:30005")]
+    pub listen_address: SocketAddr,
+
+    /// Replay speed, 2 replays twice as fast as the capture was recorded
+    #[clap(long, default_value = "1", parse(try_from_str = positive))]
+    pub speed: f64,
+
+    /// Longest pause between messages in seconds, longer gaps in the capture are shortened
+    #[clap(long, default_value = "10", parse(try_from_str = positive))]
+    pub max_gap: f64,
+
+    /// Start over when the end of the capture is reached
+    #[clap(long)]
+    pub repeat: bool,
+
+    /// Enable console-subscriber
+    #[clap(long)]
+    pub enable_console_subscriber: bool,
+}
+
+fn positive(s: &str) -> Result<f64> {
+    match s.parse() {
+        Ok(speed) if speed > 0.0 => Ok(speed),
+        _ => Err(anyhow!("must be a number greater than 0")),
+    }
+}
+
+/// Source of a capture time
+#[derive(Clone, Copy, PartialEq)]
+enum Clock {
+    /// 12 MHz receiver timestamp
+    Receiver,
+    /// Wall-clock receive time annotation
+    WallClock,
+}
+
+/// Converts capture times into delays from the start of the replay
+struct Pacer {
+    speed: f64,
+    max_gap: f64,
+    previous: Option<(Clock, f64)>,
+    elapsed: f64,
+}
+
+impl Pacer {
+    fn new(speed: f64, max_gap: f64) -> Self {
+        Pacer {
+            speed,
+            max_gap,
+            previous: None,
+            elapsed: 0.0,
+        }
+    }
+
+    /// Delay from the start of the replay for a message captured at `time` seconds
+    ///
+    /// Times that go backwards, like after a receiver restart, or that come from a different
+    /// clock than the previous message add no delay.
+    fn delay(&mut self, time: Option<(Clock, f64)>) -> Duration {
+        if let Some((clock, time)) = time {
+            if let Some((previous_clock, previous)) = self.previous {
+                if clock == previous_clock {
+                    let gap = (time - previous).clamp(0.0, self.max_gap);
+
+                    self.elapsed += gap / self.speed;
+                }
+            }
+
+            self.previous = Some((clock, time));
+        }
+
+        Duration::from_secs_f64(self.elapsed)
+    }
+}
+
+/// Capture time of a message in seconds, from the receiver timestamp or the receive time
+fn capture_time(captured: &Captured) -> Option<(Clock, f64)> {
+    if captured.message.timestamp > 0.0 {
+        return Some((Clock::Receiver, captured.message.timestamp / 1_000_000.0));
+    }
+
+    captured
+        .received
+        .and_then(|r| r.duration_since(UNIX_EPOCH).ok())
+        .map(|r| (Clock::WallClock, r.as_secs_f64()))
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
+
+    let args = Args::parse();
+
+    if args.enable_console_subscriber {
+        console_subscriber::init();
+    }
+
+    let (feed, _) = broadcast::channel(FEED_CAPACITY);
+    let server = Server::new(
+        args.listen_address,
+        Filter::default(),
+        ServerMetrics::new()?,
+    );
+    let stopper = Stopper::new();
+
+    let server = server.run(feed.clone(), stopper.shutdown());
+    tokio::pin!(server);
+
+    let replayed = tokio::select! {
+        result = &mut server => return result,
+        result = replay(&args, &feed) => result,
+    };
+
+    // Clients are sent the rest of the replay before they are disconnected
+    drop(feed);
+
+    let (_, served) = tokio::join!(stopper.stop(), server);
+
+    served?;
+    replayed
+}
+
+async fn replay(args: &Args, feed: &Feed) -> Result<()> {
+    info!("Waiting for a client to connect to {}", args.listen_address);
+
+    while feed.receiver_count() == 0 {
+        sleep(Duration::from_millis(100)).await;
+    }
+
+    loop {
+        let source = open_source(&args.file, false).await?;
+
+        let mut reader = FramedRead::new(source, CaptureCodec::new());
+        let mut pacer = Pacer::new(args.speed, args.max_gap);
+        let start = Instant::now();
+        let mut replayed = 0;
+
+        info!("Replaying {} at {}× speed", args.file, args.speed);
+
+        while let Some(captured) = reader.next().await {
+            let captured = captured?;
+
+            sleep_until(start + pacer.delay(capture_time(&captured))).await;
+
+            let merged = Merged {
+                message: captured.message,
+                receivers: vec![args.file.clone()],
+            };
+
+            // Sending only fails when no clients are connected
+            let _ = feed.send(Arc::new(merged));
+
+            replayed += 1;
+        }
+
+        info!("Replayed {} messages from {}", replayed, args.file);
+
+        if replayed == 0 {
+            return Err(anyhow!("No messages found in {}", args.file));
+        }
+
+        if !args.repeat {
+            return Ok(());
+        }
+    }
+}
@@ -0,0 +1,160 @@
+use adsb_exporter::beast::Codec;
+use adsb_exporter::beast::Feed;
+use adsb_exporter::beast::Filter;
+use adsb_exporter::beast::Merged;
+use adsb_exporter::beast::Position;
+use adsb_exporter::beast::Server;
+use adsb_exporter::beast::ServerMetrics;
+use adsb_exporter::beast::Simulator;
+use adsb_exporter::Stopper;
+use anyhow::anyhow;
+use anyhow::Result;
+use bytes::BytesMut;
+use clap::Parser;
+use log::info;
+use std::io::Write;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tokio::time::Instant;
+use tokio_util::codec::Encoder;
+
+/// Number of messages buffered for each client before it falls behind
+const FEED_CAPACITY: usize = 16384;
+
+/// How often simulated messages are sent to clients
+const TICK: Duration = Duration::from_millis(100);
+
+/// Name of the simulated receiver in merged messages
+const RECEIVER: &str = "simulator";
+
+/// Serve simulated BEAST traffic
+///
+/// Simulated aircraft fly great-circle routes around the receiver and send DF17 identification,
+/// position, velocity and status squitters along with DF4, DF5 and DF11 replies.
+#[derive(Parser)]
+#[clap(about, version)]
+struct Args {
+    /// Receiver latitude
+    #[clap(long, allow_hyphen_values = true)]
+    pub latitude: f64,
+
+    /// Receiver longitude
+    #[clap(long, allow_hyphen_values = true)]
+    pub longitude: f64,
+
+    /// Number of aircraft in the air at once
+    #[clap(long, default_value = "50")]
+    pub aircraft: usize,
+
+    /// Radius of the simulated airspace around the receiver in nautical miles
+    #[clap(long, default_value = "150")]
+    pub radius: f64,
+
+    /// Fraction of frames followed by a garbage frame that fails its CRC, from 0 to 1
+    #[clap(long, default_value = "0", parse(try_from_str = fraction))]
+    pub noise: f64,
+
+    /// Seed for repeatable traffic
+    #[clap(long)]
+    pub seed: Option<u64>,
+
+    /// Listen address for BEAST clients
+    #[clap(long, default_value = "127.0.0.1:30005")]
+    pub listen_address: SocketAddr,
+
+    /// Write this many seconds of traffic to standard output as fast as possible instead of
+    /// serving it in real time
+    #[clap(long)]
+    pub duration: Option<u64>,
+
+    /// Enable console-subscriber
+    #[clap(long)]
+    pub enable_console_subscriber: bool,
+}
+
+fn fraction(s: &str) -> Result<f64> {
+    match s.parse() {
+        Ok(fraction) if (0.0..=1.0).contains(&fraction) => Ok(fraction),
+        _ => Err(anyhow!("must be a number from 0 to 1")),
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
+
+    let args = Args::parse();
+
+    if args.enable_console_subscriber {
+        console_subscriber::init();
+    }
+
+    let receiver = Position {
+        latitude: args.latitude,
+        longitude: args.longitude,
+    };
+
+    let simulator = Simulator::new(receiver, args.aircraft, args.radius, args.noise, args.seed);
+
+    if let Some(duration) = args.duration {
+        return write(simulator, duration);
+    }
+
+    let (feed, _) = broadcast::channel(FEED_CAPACITY);
+    let server = Server::new(
+        args.listen_address,
+        Filter::default(),
+        ServerMetrics::new()?,
+    );
+    let stopper = Stopper::new();
+
+    tokio::select! {
+        result = server.run(feed.clone(), stopper.shutdown()) => result,
+        result = simulate(simulator, &feed) => result,
+    }
+}
+
+async fn simulate(mut simulator: Simulator, feed: &Feed) -> Result<()> {
+    let mut interval = tokio::time::interval(TICK);
+    let mut previous = Instant::now();
+
+    info!("Simulating {} aircraft", simulator.aircraft().len());
+
+    loop {
+        let now = interval.tick().await;
+
+        for message in simulator.advance((now - previous).as_secs_f64()) {
+            let merged = Merged {
+                message,
+                receivers: vec![RECEIVER.to_string()],
+            };
+
+            // Sending only fails when no clients are connected
+            let _ = feed.send(Arc::new(merged));
+        }
+
+        previous = now;
+    }
+}
+
+fn write(mut simulator: Simulator, duration: u64) -> Result<()> {
+    let stdout = std::io::stdout();
+    let mut stdout = stdout.lock();
+    let mut codec = Codec::new();
+    let mut buf = BytesMut::new();
+
+    for _ in 0..duration {
+        for message in simulator.advance(1.0) {
+            codec.encode(&message, &mut buf)?;
+        }
+
+        stdout.write_all(&buf)?;
+        buf.clear();
+    }
+
+    stdout.flush()?;
+
+    Ok(())
+}
@@ -1,24 +1,91 @@
+use adsb_exporter::beast::open_source;
+use adsb_exporter::beast::parse_icao;
+use adsb_exporter::beast::parse_interval;
+use adsb_exporter::beast::rows;
+use adsb_exporter::beast::AvrCodec;
+use adsb_exporter::beast::CaptureCodec;
 use adsb_exporter::beast::Codec;
+use adsb_exporter::beast::Data;
+use adsb_exporter::beast::Filter;
+use adsb_exporter::beast::Kind;
+use adsb_exporter::beast::Message;
+use adsb_exporter::beast::SbsWriter;
+use adsb_exporter::beast::Sort;
+use adsb_exporter::beast::Statistics;
+use adsb_exporter::beast::Tracker;
+use adsb_exporter::beast::COLUMNS;
+use anyhow::anyhow;
 use anyhow::Result;
+use bytes::BytesMut;
+use chrono::DateTime;
+use chrono::Local;
+use chrono::SecondsFormat;
+use clap::ArgEnum;
 use clap::ArgGroup;
 use clap::ErrorKind;
 use clap::IntoApp;
 use clap::Parser;
+use crossterm::cursor::Hide;
+use crossterm::cursor::MoveTo;
+use crossterm::cursor::Show;
+use crossterm::event::Event;
+use crossterm::event::EventStream;
+use crossterm::event::KeyCode;
+use crossterm::event::KeyEvent;
+use crossterm::event::KeyModifiers;
+use crossterm::execute;
+use crossterm::queue;
+use crossterm::style::Attribute;
+use crossterm::style::Color;
+use crossterm::style::Print;
+use crossterm::style::ResetColor;
+use crossterm::style::SetAttribute;
+use crossterm::style::SetForegroundColor;
+use crossterm::terminal;
+use crossterm::terminal::Clear;
+use crossterm::terminal::ClearType;
+use crossterm::terminal::EnterAlternateScreen;
+use crossterm::terminal::LeaveAlternateScreen;
 use futures_util::Stream;
 use futures_util::StreamExt;
-use std::fmt::Debug;
-use std::marker::Unpin;
-use tokio::fs::File;
-use tokio_util::codec::Framed;
+use geo::Point;
+use log::error;
+use serde::Serialize;
+use std::io::Stdout;
+use std::io::Write;
+use std::pin::Pin;
+use std::time::Duration;
+use std::time::Instant;
+use std::time::SystemTime;
+use tokio::io::AsyncRead;
+use tokio::time::Interval;
+use tokio_util::codec::Encoder;
+use tokio_util::codec::FramedRead;
+
+/// Aircraft not heard from for this long are removed from the interactive table
+const INTERACTIVE_EXPIRY: Duration = Duration::from_secs(60);
+
+/// How often the interactive table is redrawn
+const INTERACTIVE_REFRESH: Duration = Duration::from_millis(500);
 
 /// Dump messages from a BEAST server
 #[derive(Parser)]
 #[clap(about, version, group(ArgGroup::new("source").required(true).args(&["file", "server"])))]
 struct Args {
     /// Process a file containing BEAST data
+    ///
+    /// `-` reads standard input and a directory reads each file in it in file name order.
+    /// gzip and zstd compressed files are decompressed.
     #[clap(long)]
     pub file: Option<String>,
 
+    /// Keep reading the file, or the last file of a directory, as it is appended to
+    ///
+    /// A rotated or truncated file is read again from the start and files added to a directory
+    /// are read in turn.
+    #[clap(long, requires = "file")]
+    pub follow: bool,
+
     /// Process messages from a BEAST server
     ///
     /// Server should be a host and port:
@@ -27,11 +94,158 @@ struct Args {
     #[clap(long)]
     pub server: Option<String>,
 
+    /// Format of the data read from the file or server
+    #[clap(long, arg_enum, default_value = "beast")]
+    pub input_format: InputFormat,
+
+    /// Format of the messages written to standard output
+    #[clap(long, arg_enum, default_value = "debug")]
+    pub format: OutputFormat,
+
+    /// Only messages from these ICAO addresses, may be repeated or comma separated
+    #[clap(long, use_delimiter = true, parse(try_from_str = parse_icao))]
+    pub icao: Vec<u32>,
+
+    /// Only messages with these downlink formats, may be repeated or comma separated
+    #[clap(long, use_delimiter = true)]
+    pub df: Vec<u8>,
+
+    /// Only ADS-B messages with these type codes, may be repeated or comma separated
+    #[clap(long, use_delimiter = true)]
+    pub type_code: Vec<u8>,
+
+    /// Only messages with at least this signal level in dBFS
+    #[clap(long, allow_hyphen_values = true)]
+    pub min_signal: Option<f64>,
+
+    /// Only identification messages with a matching call sign, may be repeated
+    ///
+    /// `*` matches any characters and `?` matches one character: KLM*
+    #[clap(long)]
+    pub call_sign: Vec<String>,
+
+    /// Only messages of these kinds, may be repeated or comma separated
+    ///
+    /// Kinds are position, identification, velocity, status, target-state, altitude, identity,
+    /// all-call, error and unsupported
+    #[clap(long, use_delimiter = true)]
+    pub kind: Vec<Kind>,
+
+    /// Print statistics instead of messages when the input ends
+    #[clap(long)]
+    pub stats: bool,
+
+    /// Also print statistics every this many seconds
+    #[clap(long, requires = "stats", parse(try_from_str = parse_interval))]
+    pub stats_interval: Option<Duration>,
+
+    /// Show a continuously refreshed table of aircraft instead of messages
+    ///
+    /// Press s or → to sort by the next column, S or ← for the previous column, r to reverse the
+    /// sort order and q to quit.  Aircraft squawking an emergency code are highlighted.  Keys are
+    /// read from standard input so it can't be the --file.
+    #[clap(long, conflicts_with = "stats")]
+    pub interactive: bool,
+
+    /// Column the interactive table is sorted by
+    ///
+    /// Columns are icao, call-sign, squawk, altitude, speed, track, distance, signal, messages
+    /// and age
+    #[clap(long, default_value = "icao")]
+    pub sort: Sort,
+
+    /// Receiver latitude for distance and bearing in the interactive table
+    #[clap(long, requires = "longitude", allow_hyphen_values = true)]
+    pub latitude: Option<f64>,
+
+    /// Receiver longitude for distance and bearing in the interactive table
+    #[clap(long, requires = "latitude", allow_hyphen_values = true)]
+    pub longitude: Option<f64>,
+
     /// Enable console-subscriber
     #[clap(long)]
     pub enable_console_subscriber: bool,
 }
 
+impl Args {
+    fn filter(&self) -> Filter {
+        Filter {
+            downlink_formats: self.df.clone(),
+            icaos: self.icao.clone(),
+            type_codes: self.type_code.clone(),
+            minimum_signal: self.min_signal,
+            call_signs: self
+                .call_sign
+                .iter()
+                .map(|c| c.to_ascii_uppercase())
+                .collect(),
+            kinds: self.kind.clone(),
+        }
+    }
+}
+
+#[derive(ArgEnum, Clone, Copy)]
+enum InputFormat {
+    /// Binary BEAST frames, like dump1090 port 30005
+    Beast,
+    /// AVR text frames, like dump1090 port 30002
+    Avr,
+    /// BEAST frames annotated with wall-clock receive times, plain BEAST is also accepted
+    Capture,
+}
+
+#[derive(ArgEnum, Clone, Copy)]
+enum OutputFormat {
+    /// Pretty-printed debug output
+    Debug,
+    /// AVR text frames
+    Avr,
+    /// Binary BEAST frames
+    Beast,
+    /// Binary BEAST frames annotated with wall-clock receive times for recording and replay
+    Capture,
+    /// SBS-1 BaseStation lines
+    Sbs,
+    /// JSON array with one message per line
+    Json,
+    /// JSON Lines, one message object per line
+    Jsonl,
+    /// Hexadecimal frames, one per line
+    Hex,
+}
+
+/// A decoded message with reception details for JSON output
+#[derive(Serialize)]
+struct Record<'a> {
+    /// Receiver timestamp in µs
+    timestamp: f64,
+    /// Wall-clock time the message was read
+    received: String,
+    /// RSSI in dBFS, null if unknown
+    signal_level: f64,
+    downlink_format: Option<u8>,
+    /// ICAO address, announced or recovered from the parity field
+    icao: Option<String>,
+    frame: String,
+    data: &'a Data,
+}
+
+impl<'a> Record<'a> {
+    fn new(message: &'a Message, received: DateTime<Local>) -> Self {
+        Record {
+            timestamp: message.timestamp,
+            received: received.to_rfc3339_opts(SecondsFormat::Micros, false),
+            signal_level: message.signal_level,
+            downlink_format: message.downlink_format(),
+            icao: message.address().map(|a| format!("{:06X}", a)),
+            frame: hex(&message.frame),
+            data: &message.data,
+        }
+    }
+}
+
+type Messages = Pin<Box<dyn Stream<Item = Result<Message>>>>;
+
 #[tokio::main]
 async fn main() -> Result<()> {
     env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
@@ -42,10 +256,14 @@ async fn main() -> Result<()> {
         console_subscriber::init();
     }
 
-    if let Some(file) = args.file {
-        read_file(file).await?
+    let filter = args.filter();
+
+    let stdin = args.file.as_deref() == Some("-");
+
+    let reader = if let Some(file) = args.file {
+        read_file(file, args.follow, args.input_format).await?
     } else if let Some(server) = args.server {
-        read_socket(server).await?
+        read_socket(server, args.input_format).await?
     } else {
         let mut app = Args::into_app();
         app.error(
@@ -55,36 +273,365 @@ async fn main() -> Result<()> {
         .exit();
     };
 
+    if args.stats {
+        stats(reader, filter, args.stats_interval).await
+    } else if args.interactive {
+        let receiver = match (args.latitude, args.longitude) {
+            (Some(latitude), Some(longitude)) => Some(Point::new(longitude, latitude)),
+            _ => None,
+        };
+
+        interactive(reader, filter, receiver, args.sort, stdin).await
+    } else {
+        read(reader, args.format, filter).await
+    }
+}
+
+async fn read_file(file: String, follow: bool, input_format: InputFormat) -> Result<Messages> {
+    let stream = open_source(&file, follow).await?;
+
+    Ok(decode(stream, input_format))
+}
+
+async fn read_socket(server: String, input_format: InputFormat) -> Result<Messages> {
+    let std_socket = std::net::TcpStream::connect(server)?;
+    let stream = tokio::net::TcpStream::from_std(std_socket)?;
+
+    Ok(decode(stream, input_format))
+}
+
+fn decode<R>(stream: R, input_format: InputFormat) -> Messages
+where
+    R: AsyncRead + 'static,
+{
+    match input_format {
+        InputFormat::Beast => Box::pin(FramedRead::new(stream, Codec::new())),
+        InputFormat::Avr => Box::pin(FramedRead::new(stream, AvrCodec::new())),
+        InputFormat::Capture => {
+            Box::pin(FramedRead::new(stream, CaptureCodec::new()).map(|c| c.map(|c| c.message)))
+        }
+    }
+}
+
+async fn read(mut reader: Messages, format: OutputFormat, filter: Filter) -> Result<()> {
+    let stdout = std::io::stdout();
+    let mut stdout = stdout.lock();
+    let mut buf = BytesMut::new();
+    let mut avr = AvrCodec::new();
+    let mut beast = Codec::new();
+    let mut capture = CaptureCodec::new();
+    let mut sbs = SbsWriter::new();
+    let mut first = true;
+
+    while let Some(message) = reader.next().await {
+        let message = match message {
+            Ok(m) => m,
+            Err(e) => {
+                error!("{:#}", e);
+                break;
+            }
+        };
+
+        if !filter.matches(&message) {
+            continue;
+        }
+
+        match format {
+            OutputFormat::Debug => writeln!(stdout, "{:#?}", message)?,
+            OutputFormat::Avr => avr.encode(&message, &mut buf)?,
+            OutputFormat::Beast => beast.encode(&message, &mut buf)?,
+            OutputFormat::Capture => capture.encode((SystemTime::now(), &message), &mut buf)?,
+            OutputFormat::Sbs => {
+                if let Some(line) = sbs.write(&message, Instant::now(), &Local::now()) {
+                    writeln!(stdout, "{}", line)?
+                }
+            }
+            OutputFormat::Json => {
+                stdout.write_all(if first { b"[\n" } else { b",\n" })?;
+                serde_json::to_writer(&mut stdout, &Record::new(&message, Local::now()))?;
+            }
+            OutputFormat::Jsonl => {
+                serde_json::to_writer(&mut stdout, &Record::new(&message, Local::now()))?;
+                writeln!(stdout)?
+            }
+            OutputFormat::Hex => writeln!(stdout, "{}", hex(&message.frame))?,
+        }
+
+        first = false;
+
+        stdout.write_all(&buf)?;
+        buf.clear();
+    }
+
+    if let OutputFormat::Json = format {
+        stdout.write_all(if first { b"[]\n" } else { b"\n]\n" })?;
+    }
+
+    stdout.flush()?;
+
     Ok(())
 }
 
-async fn read_file(file: String) -> Result<()> {
-    let stream = File::open(file).await?;
+async fn stats(mut reader: Messages, filter: Filter, interval: Option<Duration>) -> Result<()> {
+    let mut statistics = Statistics::new();
+    let mut ticker = interval.map(tokio::time::interval);
 
-    let reader = Framed::new(stream, Codec::new());
+    // the first tick completes immediately
+    tick(&mut ticker).await;
 
-    read(reader).await;
+    loop {
+        tokio::select! {
+            message = reader.next() => match message {
+                Some(Ok(message)) => {
+                    if filter.matches(&message) {
+                        statistics.add(&message);
+                    }
+                }
+                Some(Err(e)) => {
+                    error!("{:#}", e);
+                    break;
+                }
+                None => break,
+            },
+            _ = tick(&mut ticker), if ticker.is_some() => println!("{}", statistics),
+        }
+    }
+
+    print!("{}", statistics);
 
     Ok(())
 }
 
-async fn read_socket(server: String) -> Result<()> {
-    let std_socket = std::net::TcpStream::connect(server)?;
-    let stream = tokio::net::TcpStream::from_std(std_socket)?;
+async fn tick(ticker: &mut Option<Interval>) {
+    if let Some(ticker) = ticker {
+        ticker.tick().await;
+    }
+}
+
+async fn interactive(
+    mut reader: Messages,
+    filter: Filter,
+    receiver: Option<Point<f64>>,
+    sort: Sort,
+    stdin: bool,
+) -> Result<()> {
+    // Key events are read from the terminal on standard input
+    if stdin {
+        return Err(anyhow!(
+            "--interactive can't read BEAST data from standard input, use a file or --server"
+        ));
+    }
 
-    let reader = Framed::new(stream, Codec::new());
+    let mut table = Table::new(receiver, sort);
+    let mut events = EventStream::new();
+    let mut refresh = tokio::time::interval(INTERACTIVE_REFRESH);
+    let mut stdout = std::io::stdout();
 
-    read(reader).await;
+    let _terminal = RawTerminal::enter()?;
+
+    loop {
+        tokio::select! {
+            message = reader.next(), if table.ended.is_none() => match message {
+                Some(Ok(message)) => {
+                    if filter.matches(&message) {
+                        table.messages += 1;
+                        table.tracker.update(&message, Instant::now());
+                    }
+                }
+                Some(Err(e)) => table.end(format!("{:#}", e)),
+                None => table.end("input ended".to_string()),
+            },
+            event = events.next() => match event {
+                Some(Ok(Event::Key(key))) => {
+                    if !table.key(key) {
+                        break;
+                    }
+
+                    table.draw(&mut stdout)?;
+                }
+                Some(Ok(Event::Resize(_, _))) => table.draw(&mut stdout)?,
+                Some(Ok(_)) => (),
+                Some(Err(e)) => return Err(e.into()),
+                None => break,
+            },
+            _ = refresh.tick() => {
+                if table.ended.is_none() {
+                    table.tracker.expire(INTERACTIVE_EXPIRY, Instant::now());
+                }
+
+                table.draw(&mut stdout)?;
+            }
+        }
+    }
 
     Ok(())
 }
 
-async fn read<T>(mut reader: T)
-where
-    T: Stream + Unpin,
-    <T as Stream>::Item: Debug,
-{
-    while let Some(message) = reader.next().await {
-        println!("{:#?}", message);
+/// Puts the terminal in raw mode on the alternate screen until dropped
+struct RawTerminal;
+
+impl RawTerminal {
+    fn enter() -> Result<Self> {
+        terminal::enable_raw_mode()?;
+        execute!(std::io::stdout(), EnterAlternateScreen, Hide)?;
+
+        Ok(RawTerminal)
+    }
+}
+
+impl Drop for RawTerminal {
+    fn drop(&mut self) {
+        let _ = execute!(std::io::stdout(), Show, LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
     }
 }
+
+/// State of the interactive aircraft table
+struct Table {
+    tracker: Tracker,
+    receiver: Option<Point<f64>>,
+    sort: Sort,
+    reverse: bool,
+    messages: u64,
+    /// When the input ended and why, the table stops aging aircraft
+    ended: Option<(Instant, String)>,
+}
+
+impl Table {
+    fn new(receiver: Option<Point<f64>>, sort: Sort) -> Self {
+        Table {
+            tracker: Tracker::new(),
+            receiver,
+            sort,
+            reverse: false,
+            messages: 0,
+            ended: None,
+        }
+    }
+
+    fn end(&mut self, reason: String) {
+        self.ended = Some((Instant::now(), reason));
+    }
+
+    /// Handle a key press, returns false to quit
+    fn key(&mut self, key: KeyEvent) -> bool {
+        match key.code {
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return false,
+            KeyCode::Char('q') | KeyCode::Esc => return false,
+            KeyCode::Char('s') | KeyCode::Right => self.sort = self.sort.next(),
+            KeyCode::Char('S') | KeyCode::Left => self.sort = self.sort.previous(),
+            KeyCode::Char('r') => self.reverse = !self.reverse,
+            _ => (),
+        }
+
+        true
+    }
+
+    fn draw(&self, stdout: &mut Stdout) -> Result<()> {
+        let (width, height) = terminal::size()?;
+        let width = width as usize;
+
+        let now = match &self.ended {
+            Some((ended, _)) => *ended,
+            None => Instant::now(),
+        };
+
+        let rows = rows(
+            self.tracker.aircraft(),
+            self.receiver,
+            self.sort,
+            self.reverse,
+            now,
+        );
+
+        let mut status = format!(
+            "Aircraft: {}  Messages: {}  Sort: {}{}  [s/S] sort [r] reverse [q] quit",
+            rows.len(),
+            self.messages,
+            self.sort.name(),
+            if self.reverse { " (reversed)" } else { "" },
+        );
+
+        if let Some((_, reason)) = &self.ended {
+            status = format!("{}  ({})", status, reason);
+        }
+
+        let header: Vec<&str> = COLUMNS.iter().map(|(name, _)| *name).collect();
+
+        queue!(stdout, MoveTo(0, 0), Print(fit(&status, width)))?;
+        queue!(stdout, Clear(ClearType::UntilNewLine), MoveTo(0, 1))?;
+        queue!(
+            stdout,
+            SetAttribute(Attribute::Reverse),
+            Print(fit(&line(&header), width)),
+            SetAttribute(Attribute::Reset),
+            Clear(ClearType::UntilNewLine),
+        )?;
+
+        for (y, row) in rows
+            .iter()
+            .take(height.saturating_sub(2) as usize)
+            .enumerate()
+        {
+            let cells = row.cells();
+            let cells: Vec<&str> = cells.iter().map(|c| c.as_str()).collect();
+
+            queue!(stdout, MoveTo(0, y as u16 + 2))?;
+
+            if row.emergency() {
+                queue!(
+                    stdout,
+                    SetForegroundColor(Color::Red),
+                    SetAttribute(Attribute::Bold)
+                )?;
+            }
+
+            queue!(stdout, Print(fit(&line(&cells), width)))?;
+
+            if row.emergency() {
+                queue!(stdout, SetAttribute(Attribute::Reset), ResetColor)?;
+            }
+
+            queue!(stdout, Clear(ClearType::UntilNewLine))?;
+        }
+
+        queue!(
+            stdout,
+            MoveTo(
+                0,
+                rows.len().min(height.saturating_sub(2) as usize) as u16 + 2
+            ),
+            Clear(ClearType::FromCursorDown)
+        )?;
+
+        stdout.flush()?;
+
+        Ok(())
+    }
+}
+
+/// Pad `cells` to the width of their columns, text columns are left aligned
+fn line(cells: &[&str]) -> String {
+    cells
+        .iter()
+        .zip(COLUMNS.iter())
+        .enumerate()
+        .map(|(i, (cell, (_, width)))| {
+            if i < 2 {
+                format!("{:<width$}", cell, width = width)
+            } else {
+                format!("{:>width$}", cell, width = width)
+            }
+        })
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+/// Truncate `text` to fit `width` columns of the terminal
+fn fit(text: &str, width: usize) -> String {
+    text.chars().take(width).collect()
+}
+
+fn hex(frame: &[u8]) -> String {
+    frame.iter().map(|b| format!("{:02X}", b)).collect()
+}
@@ -1,12 +1,63 @@
+use crate::beast::Filter;
+use crate::range::RangeBuckets;
+use crate::scrape::Collection;
+
+use anyhow::anyhow;
+use anyhow::Context;
+use anyhow::Result;
+
+use clap::ArgMatches;
+use clap::FromArgMatches;
+use clap::IntoApp;
 use clap::Parser;
 
+use reqwest::Url;
+
+use serde::Deserialize;
+
+use std::collections::BTreeMap;
 use std::net::SocketAddr;
+use std::path::Path;
+use std::path::PathBuf;
 use std::time::Duration;
 
-/// A Prometheus exporter for ADSB message receivers like dump1090 and dump978
+/// Label names used by exported metrics that receiver labels may not replace
+const RESERVED_LABELS: [&str; 15] = [
+    "bearing",
+    "corrections",
+    "distance",
+    "downlink_format",
+    "error_type",
+    "frequency",
+    "latitude",
+    "longitude",
+    "receiver",
+    "source",
+    "transmission_type",
+    "type_code",
+    "unit",
+    "uri",
+    "version",
+];
+
+/// A Prometheus exporter for ADSB message receivers like dump1090, dump978 and BEAST servers
 #[derive(Parser)]
 #[clap(about, version)]
 pub struct Configuration {
+    /// TOML configuration file, command line options override values from the file
+    ///
+    /// The file may set any command line option by its long name with underscores, like
+    /// aircraft_refresh_interval = 15, along with [[receivers]] and [range] tables that can
+    /// only be set in a file.  See adsb_exporter.example.toml.
+    ///
+    /// Receivers are reloaded from the file on SIGHUP, other options take effect on restart.
+    #[clap(long)]
+    pub config: Option<PathBuf>,
+
+    /// Reload the configuration file when it changes, as when receiving SIGHUP
+    #[clap(long, requires = "config")]
+    pub watch_config: bool,
+
     /// Bind address for prometheus exporter
     #[clap(long, default_value = "0.0.0.0:9190")]
     pub bind_address: SocketAddr,
@@ -19,6 +70,13 @@ pub struct Configuration {
     #[clap(long)]
     pub dump978_url: Option<String>,
 
+    /// Address of a BEAST server to decode messages from, may be repeated
+    ///
+    /// Address should be a host and port like localhost:30005.  Identical messages heard by
+    /// multiple receivers are merged.
+    #[clap(long)]
+    pub beast_address: Vec<String>,
+
     /// Refresh interval in seconds for aircraft.json
     #[clap(long, default_value = "30", parse(try_from_str = secs_to_duration))]
     pub aircraft_refresh_interval: Duration,
@@ -31,13 +89,591 @@ pub struct Configuration {
     #[clap(long, default_value = "60", parse(try_from_str = secs_to_duration))]
     pub stats_refresh_interval: Duration,
 
+    /// Window in seconds in which an aircraft last seen in aircraft.json or heard from a BEAST or
+    /// SBS source counts as recent for the recent aircraft, position and range metrics
+    #[clap(long, default_value = "60", parse(try_from_str = secs_to_duration))]
+    pub recent_window: Duration,
+
     /// Refresh timeout in milliseconds for requests to dump program URLs
     #[clap(long, default_value = "150", parse(try_from_str = millis_to_duration))]
     pub refresh_timeout: Duration,
 
+    /// Listen address for BEAST data pushed by remote receivers
+    ///
+    /// Each remote address is one receiver, its metrics are removed when it disconnects.
+    #[clap(long)]
+    pub beast_input_address: Option<SocketAddr>,
+
+    /// Listen address for serving decoded BEAST data to clients, may be repeated
+    ///
+    /// Messages served may be filtered by downlink format (df), ICAO address (icao), type code
+    /// (tc), minimum signal level (signal), call sign (callsign) and kind (kind):
+    /// * 0.0.0.0:30005
+    /// * 0.0.0.0:30015?df=17,18
+    /// * 0.0.0.0:30025?df=17&icao=A1B2C3,A4B5C6
+    /// * 0.0.0.0:30035?kind=position&signal=-20
+    #[clap(long, parse(try_from_str = beast_output))]
+    pub beast_output_address: Vec<BeastOutput>,
+
+    /// Address of an SBS-1 BaseStation server, may be repeated
+    ///
+    /// Server should be a host and port:
+    /// * localhost:30003
+    /// * 192.0.2.1:30003
+    #[clap(long)]
+    pub sbs_address: Vec<String>,
+
+    /// Listen address for serving SBS-1 BaseStation data to clients, may be repeated
+    #[clap(long)]
+    pub sbs_output_address: Vec<SocketAddr>,
+
+    /// Latitude of the receiver for BEAST range and bearing metrics
+    #[clap(long, requires = "longitude", allow_hyphen_values = true)]
+    pub latitude: Option<f64>,
+
+    /// Longitude of the receiver for BEAST range and bearing metrics
+    #[clap(long, requires = "latitude", allow_hyphen_values = true)]
+    pub longitude: Option<f64>,
+
+    /// Reconnect interval in seconds for the BEAST server
+    #[clap(long, default_value = "10", parse(try_from_str = secs_to_duration))]
+    pub beast_reconnect_interval: Duration,
+
+    /// Reconnect interval in seconds for SBS servers
+    #[clap(long, default_value = "10", parse(try_from_str = secs_to_duration))]
+    pub sbs_reconnect_interval: Duration,
+
+    /// Window in milliseconds for merging identical messages heard by multiple BEAST receivers
+    #[clap(long, default_value = "500", parse(try_from_str = millis_to_duration))]
+    pub beast_merge_window: Duration,
+
+    /// Window in seconds in which aircraft.json and stats.json of every receiver must be fetched
+    /// and their data must advance, and every BEAST and SBS source must send a message, for
+    /// /readyz to report ready
+    #[clap(long, default_value = "120", parse(try_from_str = secs_to_duration))]
+    pub readiness_window: Duration,
+
+    /// Fetch receiver JSON when metrics are scraped instead of on the refresh intervals
+    ///
+    /// Metrics then match the receiver at scrape time.  Each fetch is limited by the refresh
+    /// timeout, receiver.json is reused for the receiver refresh interval.
+    #[clap(long)]
+    pub collect_on_scrape: bool,
+
+    /// Age in milliseconds after which aircraft.json and stats.json are fetched again for a
+    /// scrape with --collect-on-scrape, scrapes within the age share one fetch
+    #[clap(long, default_value = "1000", parse(try_from_str = millis_to_duration))]
+    pub scrape_cache_age: Duration,
+
     /// Enable console-subscriber for tokio-console
     #[clap(long)]
     pub enable_console_subscriber: bool,
+
+    /// dump1090 and dump978 receivers from --dump1090-url, --dump978-url and the configuration
+    /// file
+    #[clap(skip)]
+    pub receivers: Vec<Receiver>,
+
+    /// Distance and bearing buckets for range metrics
+    #[clap(skip)]
+    pub range: RangeBuckets,
+
+    /// Command line the configuration was loaded from, for reloading
+    #[clap(skip)]
+    matches: Option<ArgMatches>,
+}
+
+impl Configuration {
+    /// Parse the command line and merge in the configuration file, exits on command line errors
+    pub fn load() -> Result<Self> {
+        let matches = Configuration::into_app().get_matches();
+
+        Configuration::from_matches(&matches)
+    }
+
+    /// Build a configuration from command line `matches` and the configuration file they name
+    pub fn from_matches(matches: &ArgMatches) -> Result<Self> {
+        let configuration = Configuration::from_arg_matches(matches)?;
+
+        let file = match configuration.config {
+            Some(ref path) => ConfigurationFile::read(path)?,
+            None => ConfigurationFile::default(),
+        };
+
+        let mut configuration = configuration.merge(file, matches)?;
+        configuration.matches = Some(matches.clone());
+
+        Ok(configuration)
+    }
+
+    /// Load the configuration again from the original command line and the configuration file
+    pub fn reload(&self) -> Result<Self> {
+        match self.matches {
+            Some(ref matches) => Configuration::from_matches(matches),
+            None => Err(anyhow!("configuration was not loaded from a command line")),
+        }
+    }
+
+    /// Options that differ in `reloaded` but only take effect on restart
+    ///
+    /// Receivers are reloaded, including the intervals, recent window and range buckets they take
+    /// from the global options, but BEAST and SBS sources use the global options as they were
+    /// loaded.
+    pub fn restart_required(&self, reloaded: &Configuration) -> Vec<&'static str> {
+        let mut changed = vec![];
+
+        macro_rules! compare {
+            ($($field:ident),*) => {
+                $(
+                    if self.$field != reloaded.$field {
+                        changed.push(stringify!($field));
+                    }
+                )*
+            };
+        }
+
+        compare!(
+            bind_address,
+            beast_address,
+            beast_input_address,
+            beast_output_address,
+            sbs_address,
+            sbs_output_address,
+            latitude,
+            longitude,
+            beast_reconnect_interval,
+            sbs_reconnect_interval,
+            beast_merge_window,
+            readiness_window,
+            collect_on_scrape,
+            scrape_cache_age,
+            recent_window,
+            range,
+            enable_console_subscriber,
+            watch_config
+        );
+
+        changed
+    }
+
+    /// How receiver JSON is collected
+    pub fn collection(&self) -> Collection {
+        if self.collect_on_scrape {
+            Collection::Scrape {
+                cache_age: self.scrape_cache_age,
+            }
+        } else {
+            Collection::Poll
+        }
+    }
+
+    /// Apply values from `file` that were not given on the command line in `matches`
+    pub(crate) fn merge(mut self, file: ConfigurationFile, matches: &ArgMatches) -> Result<Self> {
+        macro_rules! merge {
+            ($field:ident) => {
+                merge!($field, |value| value)
+            };
+            ($field:ident, $convert:expr) => {
+                if !on_command_line(matches, stringify!($field)) {
+                    if let Some(value) = file.$field {
+                        self.$field = $convert(value);
+                    }
+                }
+            };
+        }
+
+        merge!(bind_address);
+        merge!(dump1090_url, Some);
+        merge!(dump978_url, Some);
+        merge!(beast_address);
+        merge!(aircraft_refresh_interval, Duration::from_secs);
+        merge!(receiver_refresh_interval, Duration::from_secs);
+        merge!(stats_refresh_interval, Duration::from_secs);
+        merge!(recent_window, Duration::from_secs);
+        merge!(refresh_timeout, Duration::from_millis);
+        merge!(beast_input_address, Some);
+        merge!(sbs_address);
+        merge!(sbs_output_address);
+        merge!(latitude, Some);
+        merge!(longitude, Some);
+        merge!(beast_reconnect_interval, Duration::from_secs);
+        merge!(sbs_reconnect_interval, Duration::from_secs);
+        merge!(beast_merge_window, Duration::from_millis);
+        merge!(readiness_window, Duration::from_secs);
+        merge!(collect_on_scrape);
+        merge!(scrape_cache_age, Duration::from_millis);
+        merge!(enable_console_subscriber);
+        merge!(watch_config);
+
+        if !on_command_line(matches, "beast_output_address") {
+            if let Some(outputs) = file.beast_output_address {
+                self.beast_output_address = outputs
+                    .iter()
+                    .enumerate()
+                    .map(|(i, output)| {
+                        beast_output(output)
+                            .map_err(|e| anyhow!("beast_output_address[{}]: {}", i, e))
+                    })
+                    .collect::<Result<_>>()?;
+            }
+        }
+
+        if let Some(range) = file.range {
+            self.range = range;
+        }
+
+        self.validate()?;
+
+        self.receivers = vec![];
+
+        if let Some(ref url) = self.dump1090_url {
+            let receiver = self.receiver("dump1090".to_string(), url.clone(), 1090);
+            self.receivers.push(receiver);
+        }
+
+        if let Some(ref url) = self.dump978_url {
+            let receiver = self.receiver("dump978".to_string(), url.clone(), 978);
+            self.receivers.push(receiver);
+        }
+
+        for (i, file_receiver) in file.receivers.into_iter().enumerate() {
+            let receiver = self
+                .file_receiver(file_receiver, matches)
+                .with_context(|| format!("receivers[{}]", i))?;
+
+            if self.receivers.iter().any(|r| r.name == receiver.name) {
+                return Err(anyhow!(
+                    "receivers[{}]: name \"{}\" is already used by another receiver",
+                    i,
+                    receiver.name
+                ));
+            }
+
+            self.receivers.push(receiver);
+        }
+
+        for receiver in &self.receivers {
+            let mut intervals = vec![(
+                "aircraft_refresh_interval",
+                receiver.aircraft_refresh_interval,
+            )];
+
+            // dump978 has no stats.json
+            if receiver.frequency == 1090 {
+                intervals.push(("stats_refresh_interval", receiver.stats_refresh_interval));
+            }
+
+            for (name, interval) in intervals {
+                if interval > self.readiness_window {
+                    return Err(anyhow!(
+                        "readiness_window {}s must be at least the {} {}s of receiver \"{}\"",
+                        self.readiness_window.as_secs(),
+                        name,
+                        interval.as_secs(),
+                        receiver.name
+                    ));
+                }
+            }
+        }
+
+        Ok(self)
+    }
+
+    /// Check values that apply to every receiver
+    fn validate(&self) -> Result<()> {
+        let intervals = [
+            ("aircraft_refresh_interval", self.aircraft_refresh_interval),
+            ("receiver_refresh_interval", self.receiver_refresh_interval),
+            ("stats_refresh_interval", self.stats_refresh_interval),
+            ("recent_window", self.recent_window),
+            ("refresh_timeout", self.refresh_timeout),
+            ("readiness_window", self.readiness_window),
+        ];
+
+        for (name, interval) in intervals {
+            if interval.is_zero() {
+                return Err(anyhow!("{} must be greater than 0", name));
+            }
+        }
+
+        for url in self.dump1090_url.iter().chain(self.dump978_url.iter()) {
+            validate_url(url)?;
+        }
+
+        match (self.latitude, self.longitude) {
+            (Some(latitude), Some(longitude)) => {
+                if !(-90.0..=90.0).contains(&latitude) {
+                    return Err(anyhow!("latitude {} must be from -90 to 90", latitude));
+                }
+
+                if !(-180.0..=180.0).contains(&longitude) {
+                    return Err(anyhow!("longitude {} must be from -180 to 180", longitude));
+                }
+            }
+            (None, None) => (),
+            _ => return Err(anyhow!("latitude and longitude must be set together")),
+        }
+
+        validate_range(&self.range).context("range")
+    }
+
+    /// A receiver from a command line URL using the global intervals and range buckets
+    fn receiver(&self, name: String, url: String, frequency: u32) -> Receiver {
+        Receiver {
+            name,
+            url,
+            frequency,
+            aircraft_refresh_interval: self.aircraft_refresh_interval,
+            receiver_refresh_interval: self.receiver_refresh_interval,
+            stats_refresh_interval: self.stats_refresh_interval,
+            recent_window: self.recent_window,
+            refresh_timeout: self.refresh_timeout,
+            labels: BTreeMap::new(),
+            range: self.range,
+        }
+    }
+
+    /// A receiver from the configuration file, intervals given on the command line override the
+    /// receiver's own
+    fn file_receiver(&self, file: ReceiverFile, matches: &ArgMatches) -> Result<Receiver> {
+        if file.name.is_empty() {
+            return Err(anyhow!("name must not be empty"));
+        }
+
+        let name = file.name;
+
+        validate_url(&file.url).with_context(|| format!("\"{}\"", name))?;
+
+        if file.frequency != 1090 && file.frequency != 978 {
+            return Err(anyhow!(
+                "\"{}\": frequency {} must be 1090 or 978",
+                name,
+                file.frequency
+            ));
+        }
+
+        let interval = |field: &str, global: Duration, value: Option<Duration>| {
+            let interval = match value {
+                Some(value) if !on_command_line(matches, field) => value,
+                _ => global,
+            };
+
+            if interval.is_zero() {
+                Err(anyhow!("\"{}\": {} must be greater than 0", name, field))
+            } else {
+                Ok(interval)
+            }
+        };
+
+        let aircraft_refresh_interval = interval(
+            "aircraft_refresh_interval",
+            self.aircraft_refresh_interval,
+            file.aircraft_refresh_interval.map(Duration::from_secs),
+        )?;
+        let receiver_refresh_interval = interval(
+            "receiver_refresh_interval",
+            self.receiver_refresh_interval,
+            file.receiver_refresh_interval.map(Duration::from_secs),
+        )?;
+        let stats_refresh_interval = interval(
+            "stats_refresh_interval",
+            self.stats_refresh_interval,
+            file.stats_refresh_interval.map(Duration::from_secs),
+        )?;
+        let recent_window = interval(
+            "recent_window",
+            self.recent_window,
+            file.recent_window.map(Duration::from_secs),
+        )?;
+        let refresh_timeout = interval(
+            "refresh_timeout",
+            self.refresh_timeout,
+            file.refresh_timeout.map(Duration::from_millis),
+        )?;
+
+        for label in file.labels.keys() {
+            validate_label(label).with_context(|| format!("\"{}\": labels", name))?;
+        }
+
+        let range = file.range.unwrap_or(self.range);
+        validate_range(&range).with_context(|| format!("\"{}\": range", name))?;
+
+        Ok(Receiver {
+            name,
+            url: file.url,
+            frequency: file.frequency,
+            aircraft_refresh_interval,
+            receiver_refresh_interval,
+            stats_refresh_interval,
+            recent_window,
+            refresh_timeout,
+            labels: file.labels,
+            range,
+        })
+    }
+}
+
+/// A dump1090 or dump978 server to export metrics for
+#[derive(Clone, Debug, PartialEq)]
+pub struct Receiver {
+    /// Unique name of the receiver
+    pub name: String,
+    /// Base URL of the server, the JSON files are fetched from its data directory
+    pub url: String,
+    /// 1090 for dump1090 or 978 for dump978
+    pub frequency: u32,
+    pub aircraft_refresh_interval: Duration,
+    pub receiver_refresh_interval: Duration,
+    pub stats_refresh_interval: Duration,
+    /// Aircraft seen within the window are counted in recent metrics
+    pub recent_window: Duration,
+    pub refresh_timeout: Duration,
+    /// Static labels exported on adsb_receiver_info for this receiver, other metrics only have
+    /// the receiver label to join on
+    pub labels: BTreeMap<String, String>,
+    pub range: RangeBuckets,
+}
+
+/// Contents of a TOML configuration file
+///
+/// Durations use the same units as the matching command line option.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct ConfigurationFile {
+    bind_address: Option<SocketAddr>,
+    dump1090_url: Option<String>,
+    dump978_url: Option<String>,
+    beast_address: Option<Vec<String>>,
+    aircraft_refresh_interval: Option<u64>,
+    receiver_refresh_interval: Option<u64>,
+    stats_refresh_interval: Option<u64>,
+    recent_window: Option<u64>,
+    refresh_timeout: Option<u64>,
+    beast_input_address: Option<SocketAddr>,
+    beast_output_address: Option<Vec<String>>,
+    sbs_address: Option<Vec<String>>,
+    sbs_output_address: Option<Vec<SocketAddr>>,
+    latitude: Option<f64>,
+    longitude: Option<f64>,
+    beast_reconnect_interval: Option<u64>,
+    sbs_reconnect_interval: Option<u64>,
+    beast_merge_window: Option<u64>,
+    readiness_window: Option<u64>,
+    collect_on_scrape: Option<bool>,
+    scrape_cache_age: Option<u64>,
+    enable_console_subscriber: Option<bool>,
+    watch_config: Option<bool>,
+    #[serde(default)]
+    receivers: Vec<ReceiverFile>,
+    range: Option<RangeBuckets>,
+}
+
+impl ConfigurationFile {
+    fn read(path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("unable to read configuration file {}", path.display()))?;
+
+        contents
+            .parse()
+            .with_context(|| format!("invalid configuration file {}", path.display()))
+    }
+}
+
+impl std::str::FromStr for ConfigurationFile {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(toml::from_str(s)?)
+    }
+}
+
+/// A [[receivers]] entry of a configuration file
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ReceiverFile {
+    name: String,
+    url: String,
+    #[serde(default = "default_frequency")]
+    frequency: u32,
+    aircraft_refresh_interval: Option<u64>,
+    receiver_refresh_interval: Option<u64>,
+    stats_refresh_interval: Option<u64>,
+    recent_window: Option<u64>,
+    refresh_timeout: Option<u64>,
+    #[serde(default)]
+    labels: BTreeMap<String, String>,
+    range: Option<RangeBuckets>,
+}
+
+/// True when the option for configuration `field` was given on the command line instead of
+/// taking its default value
+fn on_command_line(matches: &ArgMatches, field: &str) -> bool {
+    matches.occurrences_of(field.replace('_', "-")) > 0
+}
+
+fn default_frequency() -> u32 {
+    1090
+}
+
+fn validate_url(url: &str) -> Result<()> {
+    let parsed = Url::parse(url).map_err(|e| anyhow!("invalid URL {}: {}", url, e))?;
+
+    match parsed.scheme() {
+        "http" | "https" => Ok(()),
+        scheme => Err(anyhow!("URL {} must be http or https, not {}", url, scheme)),
+    }
+}
+
+/// Label names must be valid Prometheus label names that are not already used by metrics
+fn validate_label(label: &str) -> Result<()> {
+    let mut chars = label.chars();
+
+    let valid = match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => {
+            chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        }
+        _ => false,
+    };
+
+    if !valid {
+        return Err(anyhow!("\"{}\" is not a valid label name", label));
+    }
+
+    if label.starts_with("__") {
+        return Err(anyhow!(
+            "\"{}\" is reserved, label names may not start with __",
+            label
+        ));
+    }
+
+    if RESERVED_LABELS.contains(&label) {
+        return Err(anyhow!("\"{}\" is already used by exported metrics", label));
+    }
+
+    Ok(())
+}
+
+fn validate_range(range: &RangeBuckets) -> Result<()> {
+    if range.distance == 0 {
+        return Err(anyhow!("distance must be greater than 0"));
+    }
+
+    if range.maximum_distance < range.distance {
+        return Err(anyhow!(
+            "maximum_distance {} must be at least distance {}",
+            range.maximum_distance,
+            range.distance
+        ));
+    }
+
+    if !(range.bearing > 0.0 && range.bearing <= 360.0) {
+        return Err(anyhow!(
+            "bearing {} must be greater than 0 and at most 360",
+            range.bearing
+        ));
+    }
+
+    Ok(())
 }
 
 fn millis_to_duration(s: &str) -> Result<Duration, &'static str> {
@@ -53,3 +689,21 @@ fn secs_to_duration(s: &str) -> Result<Duration, &'static str> {
         Err(_) => Err("expected duration seconds"),
     }
 }
+
+/// A BEAST server listen address and the filter for messages it serves
+#[derive(Clone, Debug, PartialEq)]
+pub struct BeastOutput {
+    pub address: SocketAddr,
+    pub filter: Filter,
+}
+
+fn beast_output(s: &str) -> Result<BeastOutput, String> {
+    let (address, filter) = s.split_once('?').unwrap_or((s, ""));
+
+    let address = address
+        .parse()
+        .map_err(|_| format!("invalid listen address {}", address))?;
+    let filter = filter.parse().map_err(|e| format!("{:#}", e))?;
+
+    Ok(BeastOutput { address, filter })
+}
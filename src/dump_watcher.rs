@@ -1,16 +1,219 @@
+use crate::adsb_exporter::ErrorSender;
 use crate::aircraft_json::AircraftJson;
-use crate::configuration::Configuration;
+use crate::aircraft_json::AircraftMetrics;
+use crate::configuration::Receiver;
+use crate::fetch::FetchMetrics;
+use crate::fetch::Fetcher;
+use crate::health::Freshness;
+use crate::health::Health;
+use crate::range::RangeBuckets;
 use crate::receiver_json::ReceiverJson;
+use crate::receiver_json::ReceiverMetrics;
+use crate::scrape;
+use crate::scrape::Collection;
+use crate::scrape::ScrapeTargets;
 use crate::stats_json::StatsJson;
+use crate::stats_json::StatsMetrics;
+use crate::supervisor::supervise;
+use crate::supervisor::Supervised;
+use crate::supervisor::TaskMetrics;
 
+use anyhow::Context;
+use anyhow::Result;
+
+use geo::Coordinate;
+
+use log::error;
 use log::info;
 
+use prometheus::core::Collector;
+use prometheus::core::Desc;
+use prometheus::proto::MetricFamily;
+use prometheus::GaugeVec;
+use prometheus::Opts;
+use prometheus::Registry;
+
 use reqwest::Client;
 
+use std::collections::BTreeSet;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::Mutex;
 use std::time::Duration;
 
+use tokio::sync::RwLock;
+
+/// Receiver position from receiver.json shared with aircraft.json range metrics
+type Position = Arc<RwLock<Option<Coordinate<f64>>>>;
+
+const RECEIVER_INFO_NAME: &str = "adsb_receiver_info";
+const RECEIVER_INFO_HELP: &str = "Receiver frequency and static labels from the configuration";
+
+/// Collects adsb_receiver_info
+///
+/// The registry only allows one set of label names for a metric name, but the static labels of
+/// receivers may change when the configuration is reloaded, so the metric is swapped out behind a
+/// collector registered once.
+#[derive(Clone)]
+pub(crate) struct ReceiverInfo {
+    desc: Desc,
+    info: Arc<Mutex<Option<GaugeVec>>>,
+}
+
+impl ReceiverInfo {
+    pub(crate) fn new() -> Result<Self> {
+        let desc = Desc::new(
+            RECEIVER_INFO_NAME.to_string(),
+            RECEIVER_INFO_HELP.to_string(),
+            vec!["receiver".to_string(), "frequency".to_string()],
+            HashMap::new(),
+        )?;
+
+        Ok(ReceiverInfo {
+            desc,
+            info: Arc::new(Mutex::new(None)),
+        })
+    }
+
+    /// Export the name, frequency and static labels of each receiver
+    ///
+    /// Receivers may have different static labels so the metric has every label name in use, a
+    /// receiver without one of the labels has an empty value.  Join the info metric on the
+    /// receiver label to add static labels to other metrics.
+    pub(crate) fn export(&self, receivers: &[Receiver]) -> Result<()> {
+        let names: BTreeSet<&str> = receivers
+            .iter()
+            .flat_map(|r| r.labels.keys())
+            .map(String::as_str)
+            .collect();
+
+        let mut label_names = vec!["receiver", "frequency"];
+        label_names.extend(names.iter());
+
+        let info = GaugeVec::new(
+            Opts::new(RECEIVER_INFO_NAME, RECEIVER_INFO_HELP),
+            &label_names,
+        )?;
+
+        for receiver in receivers {
+            let frequency = receiver.frequency.to_string();
+
+            let mut values = vec![receiver.name.as_str(), frequency.as_str()];
+            values.extend(
+                names
+                    .iter()
+                    .map(|name| receiver.labels.get(*name).map_or("", String::as_str)),
+            );
+
+            info.with_label_values(&values).set(1.0);
+        }
+
+        *self.info.lock().unwrap() = Some(info);
+
+        Ok(())
+    }
+}
+
+impl Collector for ReceiverInfo {
+    fn desc(&self) -> Vec<&Desc> {
+        vec![&self.desc]
+    }
+
+    fn collect(&self) -> Vec<MetricFamily> {
+        match *self.info.lock().unwrap() {
+            Some(ref info) => info.collect(),
+            None => vec![],
+        }
+    }
+}
+
+/// A collector that can be boxed again for registering and unregistering
+trait BoxCollector: Send + Sync {
+    fn boxed(&self) -> Box<dyn Collector>;
+}
+
+impl<C: Collector + Clone + 'static> BoxCollector for C {
+    fn boxed(&self) -> Box<dyn Collector> {
+        Box::new(self.clone())
+    }
+}
+
+/// Collectors of one receiver for a registry
+pub(crate) struct Registered {
+    registry: Registry,
+    collectors: Vec<Box<dyn BoxCollector>>,
+}
+
+impl Registered {
+    fn new(registry: &Registry) -> Self {
+        Registered {
+            registry: registry.clone(),
+            collectors: vec![],
+        }
+    }
+
+    fn add<C: Collector + Clone + 'static>(&mut self, collector: &C) {
+        self.collectors.push(Box::new(collector.clone()));
+    }
+
+    /// Register every collector, none stay registered if one can't be
+    fn register(&self) -> Result<()> {
+        for (i, collector) in self.collectors.iter().enumerate() {
+            if let Err(e) = self.registry.register(collector.boxed()) {
+                for collector in &self.collectors[..i] {
+                    self.registry.unregister(collector.boxed()).ok();
+                }
+
+                return Err(e.into());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Unregister every collector, removing the receiver's series
+    pub(crate) fn unregister(&self) {
+        for collector in &self.collectors {
+            self.registry.unregister(collector.boxed()).ok();
+        }
+    }
+}
+
+/// Metrics of one receiver
+///
+/// A watcher restarted with the same URL and frequency keeps the metrics of the watcher it
+/// replaces so counters continue from their current values.
+#[derive(Clone)]
+struct WatcherMetrics {
+    fetch: FetchMetrics,
+    receiver: ReceiverMetrics,
+    aircraft: AircraftMetrics,
+    /// dump978 has no stats.json
+    stats: Option<StatsMetrics>,
+}
+
+impl WatcherMetrics {
+    fn new(name: &str, frequency: u32) -> Result<Self> {
+        let frequency_label = frequency.to_string();
+
+        let stats = if frequency == 1090 {
+            Some(StatsMetrics::new(name, &frequency_label)?)
+        } else {
+            None
+        };
+
+        Ok(WatcherMetrics {
+            fetch: FetchMetrics::new(name)?,
+            receiver: ReceiverMetrics::new(name, &frequency_label)?,
+            aircraft: AircraftMetrics::new(name, &frequency_label)?,
+            stats,
+        })
+    }
+}
+
 #[derive(Clone)]
 pub struct DumpWatcher {
+    name: String,
     frequency: u32,
     base_uri: String,
 
@@ -19,11 +222,20 @@ pub struct DumpWatcher {
     aircraft_interval: Duration,
     receiver_interval: Duration,
     stats_interval: Duration,
+
+    recent: Duration,
+    range: RangeBuckets,
+
+    position: Position,
+    metrics: WatcherMetrics,
 }
 
 impl DumpWatcher {
-    pub fn new(configuration: &Configuration, frequency: u32, base_uri: String) -> Self {
-        let timeout = configuration.refresh_timeout;
+    pub fn new(receiver: &Receiver) -> Self {
+        let name = receiver.name.clone();
+        let frequency = receiver.frequency;
+        let base_uri = receiver.url.trim_end_matches('/').to_string();
+        let timeout = receiver.refresh_timeout;
 
         let client = Client::builder()
             .connect_timeout(timeout)
@@ -32,71 +244,446 @@ impl DumpWatcher {
             .build()
             .expect("Could not build HTTP client");
 
-        let aircraft_interval = configuration.aircraft_refresh_interval;
-        let receiver_interval = configuration.receiver_refresh_interval;
-        let stats_interval = configuration.stats_refresh_interval;
+        let aircraft_interval = receiver.aircraft_refresh_interval;
+        let receiver_interval = receiver.receiver_refresh_interval;
+        let stats_interval = receiver.stats_refresh_interval;
+        let recent = receiver.recent_window;
+        let range = receiver.range;
+        let position = Arc::new(RwLock::new(None));
+        let metrics =
+            WatcherMetrics::new(&name, frequency).expect("Receiver metrics are invalid, bug?");
 
         DumpWatcher {
+            name,
             frequency,
             base_uri,
             client,
             aircraft_interval,
             receiver_interval,
             stats_interval,
+            recent,
+            range,
+            position,
+            metrics,
         }
     }
 
-    pub async fn start(self) {
-        info!("Watching dump{} at {}", self.frequency, self.base_uri);
+    fn url(&self, file: &str) -> String {
+        format!("{}/data/{}", self.base_uri, file)
+    }
+
+    /// Build the JSON watchers of the receiver and the collectors to register in `watchers`'
+    /// registry, nothing is registered or tracked until the watcher is started
+    fn prepare(self, watchers: &DumpWatchers) -> Prepared {
+        let health = &watchers.health;
+        let fetch_metrics = self.metrics.fetch.clone();
+        let mut tracked = vec![];
+
+        let mut fetcher = |file: &str, track: bool| {
+            let url = self.url(file);
+            let freshness = if track {
+                let freshness = health.freshness(&self.name, file, &url);
+                tracked.push(freshness.clone());
+
+                Some(freshness)
+            } else {
+                None
+            };
+
+            Fetcher::new(
+                self.client.clone(),
+                self.name.clone(),
+                url,
+                fetch_metrics.clone(),
+                freshness,
+            )
+        };
 
-        let receiver_url = format!("{}/data/{}", self.base_uri, "receiver.json");
         let receiver_json = ReceiverJson::new(
-            self.client.clone(),
+            fetcher("receiver.json", false),
             self.frequency,
-            receiver_url,
             self.receiver_interval,
-        );
-
-        let position = receiver_json.position();
+            self.position.clone(),
+        )
+        .with_metrics(self.metrics.receiver.clone());
 
-        crate::spawn_named(
-            async move {
-                receiver_json.run().await;
-            },
-            &format!("receiver_json::{}", self.frequency),
-        );
-
-        let aircraft_url = format!("{}/data/{}", self.base_uri, "aircraft.json");
         let aircraft_json = AircraftJson::new(
-            self.client.clone(),
+            fetcher("aircraft.json", true),
             self.frequency,
-            aircraft_url,
             self.aircraft_interval,
-            position,
+            self.position.clone(),
+            self.recent,
+            self.range,
+        )
+        .with_metrics(self.metrics.aircraft.clone());
+
+        // dump978 has no stats.json
+        let stats_json = self.metrics.stats.clone().map(|metrics| {
+            StatsJson::new(
+                fetcher("stats.json", true),
+                self.frequency,
+                self.stats_interval,
+            )
+            .with_metrics(metrics)
+        });
+
+        let target = match watchers.collection {
+            Collection::Poll => None,
+            Collection::Scrape { cache_age } => Some(scrape::Target::new(
+                receiver_json.clone(),
+                aircraft_json.clone(),
+                stats_json.clone(),
+                self.receiver_interval,
+                cache_age,
+            )),
+        };
+
+        // The fetch metrics and either the scrape target or, when polling, the metrics updated
+        // by each JSON watcher
+        let mut registered = Registered::new(&watchers.registry);
+
+        registered.add(&fetch_metrics);
+
+        match target {
+            Some(ref target) => registered.add(target),
+            None => {
+                registered.add(receiver_json.metrics());
+                registered.add(aircraft_json.metrics());
+
+                if let Some(ref stats_json) = stats_json {
+                    registered.add(stats_json.metrics());
+                }
+            }
+        }
+
+        Prepared {
+            watcher: self,
+            tracked,
+            receiver_json,
+            aircraft_json,
+            stats_json,
+            target,
+            registered,
+        }
+    }
+}
+
+/// A DumpWatcher with its JSON watchers built, ready to register its collectors and start
+struct Prepared {
+    watcher: DumpWatcher,
+    /// Sources to track in the watchers' health once started
+    tracked: Vec<Freshness>,
+    receiver_json: ReceiverJson,
+    aircraft_json: AircraftJson,
+    stats_json: Option<StatsJson>,
+    target: Option<scrape::Target>,
+    registered: Registered,
+}
+
+impl Prepared {
+    /// Start watching `receiver` with supervised tasks fetching each JSON file
+    ///
+    /// Fetches of aircraft.json and stats.json are tracked in the watchers' health.  When
+    /// collecting on scrape the receiver is added to the scrape targets instead and no tasks are
+    /// started.  The collectors must be registered already.
+    fn start(self, receiver: Receiver, watchers: &DumpWatchers) -> Running {
+        let Prepared {
+            watcher,
+            tracked,
+            receiver_json,
+            aircraft_json,
+            stats_json,
+            target,
+            registered,
+        } = self;
+
+        let running = |tasks| Running {
+            receiver,
+            position: watcher.position.clone(),
+            metrics: watcher.metrics.clone(),
+            tasks,
+            registered,
+        };
+
+        info!(
+            "Watching {} (dump{}) at {}",
+            watcher.name, watcher.frequency, watcher.base_uri
         );
 
-        crate::spawn_named(
-            async move {
-                aircraft_json.run().await;
+        tracked.iter().for_each(Freshness::track);
+
+        if let Some(target) = target {
+            watchers.scrape_targets.add(&watcher.name, target);
+
+            return running(vec![]);
+        }
+
+        let mut tasks = vec![];
+
+        tasks.push(supervise(
+            format!("receiver_json::{}", watcher.name),
+            &watchers.task_metrics,
+            watchers.error_tx.clone(),
+            move || {
+                let receiver_json = receiver_json.clone();
+
+                async move { receiver_json.run().await }
             },
-            &format!("aircraft_json::{}", self.frequency),
-        );
+        ));
 
-        if self.frequency == 1090 {
-            let stats_url = format!("{}/data/{}", self.base_uri, "stats.json");
-            let stats_json = StatsJson::new(
-                self.client.clone(),
-                self.frequency,
-                stats_url,
-                self.stats_interval,
-            );
+        tasks.push(supervise(
+            format!("aircraft_json::{}", watcher.name),
+            &watchers.task_metrics,
+            watchers.error_tx.clone(),
+            move || {
+                let aircraft_json = aircraft_json.clone();
 
-            crate::spawn_named(
-                async move {
-                    stats_json.run().await;
+                async move { aircraft_json.run().await }
+            },
+        ));
+
+        if let Some(stats_json) = stats_json {
+            tasks.push(supervise(
+                format!("stats_json::{}", watcher.name),
+                &watchers.task_metrics,
+                watchers.error_tx.clone(),
+                move || {
+                    let stats_json = stats_json.clone();
+
+                    async move { stats_json.run().await }
                 },
-                &format!("stats_json::{}", self.frequency),
-            );
+            ));
         }
+
+        running(tasks)
+    }
+}
+
+/// Register the collectors of every prepared watcher, none stay registered if one can't be
+fn register(prepared: &[Prepared]) -> Result<()> {
+    for (i, watcher) in prepared.iter().enumerate() {
+        if let Err(e) = watcher.registered.register() {
+            for watcher in &prepared[..i] {
+                watcher.registered.unregister();
+            }
+
+            return Err(e).with_context(|| {
+                format!("unable to register metrics of {}", watcher.watcher.name)
+            });
+        }
+    }
+
+    Ok(())
+}
+
+/// True when receivers differ at most in their static labels
+fn unchanged(running: &Receiver, receiver: &Receiver) -> bool {
+    let relabeled = Receiver {
+        labels: receiver.labels.clone(),
+        ..running.clone()
+    };
+
+    relabeled == *receiver
+}
+
+/// A started DumpWatcher
+struct Running {
+    receiver: Receiver,
+    position: Position,
+    metrics: WatcherMetrics,
+    tasks: Vec<Supervised>,
+    registered: Registered,
+}
+
+impl Running {
+    /// Stop the receiver's tasks, its collectors must be unregistered already as a restarted
+    /// receiver registers collectors with the same descriptors
+    fn stop(self, scrape_targets: &ScrapeTargets) {
+        for task in self.tasks {
+            task.stop();
+        }
+
+        scrape_targets.remove(&self.receiver.name);
+    }
+}
+
+/// DumpWatchers for each configured receiver
+///
+/// Updating the receivers stops watchers for removed receivers, starts watchers for added
+/// receivers and restarts watchers for changed receivers.  A restarted watcher for the same URL
+/// keeps the receiver position so range metrics continue until receiver.json is fetched again,
+/// and with the same frequency too it keeps the receiver's metrics so counters continue.  A
+/// receiver whose static labels changed is not restarted.
+///
+/// Each receiver's metrics are registered in the registry while it is watched.
+pub struct DumpWatchers {
+    running: HashMap<String, Running>,
+    task_metrics: TaskMetrics,
+    error_tx: ErrorSender,
+    registry: Registry,
+    receiver_info: ReceiverInfo,
+    health: Health,
+    collection: Collection,
+    scrape_targets: ScrapeTargets,
+}
+
+impl DumpWatchers {
+    /// Watchers whose metrics, including the up and restart metrics of their tasks, are
+    /// registered in `registry`, whose fetches are tracked in `health` and that collect receiver
+    /// JSON by `collection`
+    ///
+    /// A task that keeps failing after restarts is sent to `error_tx` as a fatal error.
+    pub fn new(
+        registry: Registry,
+        health: Health,
+        collection: Collection,
+        error_tx: ErrorSender,
+    ) -> Result<Self> {
+        let receiver_info = ReceiverInfo::new()?;
+
+        registry.register(Box::new(receiver_info.clone()))?;
+
+        let task_metrics = TaskMetrics::new(&registry)?;
+
+        Ok(DumpWatchers {
+            running: HashMap::new(),
+            task_metrics,
+            error_tx,
+            registry,
+            receiver_info,
+            health,
+            collection,
+            scrape_targets: ScrapeTargets::new(),
+        })
+    }
+
+    /// Receivers to fetch JSON from when metrics are scraped, for the server
+    pub fn scrape_targets(&self) -> ScrapeTargets {
+        self.scrape_targets.clone()
+    }
+
+    /// Watch `receivers`, the running receivers are kept when the update fails
+    ///
+    /// Every new watcher's metrics are registered before any running watcher is stopped.
+    pub async fn update(&mut self, receivers: &[Receiver]) -> Result<()> {
+        // Running receivers to stop, or to restart with a new watcher
+        let mut replaced: Vec<String> = self
+            .running
+            .keys()
+            .filter(|name| !receivers.iter().any(|r| &r.name == *name))
+            .cloned()
+            .collect();
+
+        let mut prepared = vec![];
+
+        for receiver in receivers {
+            let previous = match self.running.get(&receiver.name) {
+                Some(running) if unchanged(&running.receiver, receiver) => continue,
+                Some(running) => {
+                    replaced.push(receiver.name.clone());
+
+                    Some(running)
+                }
+                None => None,
+            };
+
+            let mut watcher = DumpWatcher::new(receiver);
+
+            if let Some(running) = previous {
+                if running.receiver.url == receiver.url {
+                    watcher.position = running.position.clone();
+
+                    if running.receiver.frequency == receiver.frequency {
+                        watcher.metrics = running.metrics.clone();
+                    }
+                }
+            }
+
+            prepared.push(watcher.prepare(self));
+        }
+
+        let current: Vec<Receiver> = self.running.values().map(|r| r.receiver.clone()).collect();
+
+        self.receiver_info.export(receivers)?;
+
+        // A restarted receiver registers metrics with the same names as the one it replaces
+        for name in &replaced {
+            self.running[name].registered.unregister();
+        }
+
+        if let Err(e) = register(&prepared) {
+            for name in &replaced {
+                if let Err(e) = self.running[name].registered.register() {
+                    error!("Unable to restore metrics of {}: {:#}", name, e);
+                }
+            }
+
+            self.receiver_info.export(&current).ok();
+
+            return Err(e);
+        }
+
+        for name in replaced {
+            let running = match self.running.remove(&name) {
+                Some(running) => running,
+                None => continue,
+            };
+
+            match receivers.iter().find(|r| r.name == name) {
+                Some(receiver) => {
+                    info!("Restarting {}, its configuration changed", name);
+
+                    // A dump978 receiver has no stats.json
+                    if running.receiver.frequency != receiver.frequency {
+                        self.health.remove(&name);
+                    }
+                }
+                None => {
+                    info!("Stopping {}, it was removed from the configuration", name);
+
+                    self.health.remove(&name);
+                }
+            }
+
+            running.stop(&self.scrape_targets);
+        }
+
+        // Static labels are only exported in adsb_receiver_info
+        for receiver in receivers {
+            if let Some(running) = self.running.get_mut(&receiver.name) {
+                running.receiver = receiver.clone();
+            }
+        }
+
+        for prepared in prepared {
+            let receiver = receivers
+                .iter()
+                .find(|r| r.name == prepared.watcher.name)
+                .expect("prepared watcher for an unknown receiver, bug?")
+                .clone();
+
+            self.running
+                .insert(receiver.name.clone(), prepared.start(receiver, self));
+        }
+
+        Ok(())
+    }
+
+    /// Stop watching every receiver
+    pub fn stop(&mut self) {
+        for (name, running) in self.running.drain() {
+            info!("Stopping {}", name);
+
+            running.registered.unregister();
+            running.stop(&self.scrape_targets);
+        }
+    }
+
+    /// Names of the receivers being watched
+    pub fn names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.running.keys().map(String::as_str).collect();
+        names.sort_unstable();
+
+        names
     }
 }
@@ -1,41 +1,207 @@
-use lazy_static::lazy_static;
+use crate::health::Freshness;
+
+use anyhow::Result;
 
 use log::debug;
 
-use prometheus::register_histogram_vec;
-use prometheus::register_int_counter_vec;
+use prometheus::core::Collector;
+use prometheus::core::Desc;
+use prometheus::exponential_buckets;
+use prometheus::proto::MetricFamily;
+use prometheus::GaugeVec;
+use prometheus::HistogramOpts;
 use prometheus::HistogramVec;
 use prometheus::IntCounterVec;
+use prometheus::IntGaugeVec;
+use prometheus::Opts;
 
 use reqwest::Client;
 
 use serde_json::Value;
 
-lazy_static! {
-    static ref REQUESTS: IntCounterVec = register_int_counter_vec!(
-        "adsb_http_requests_total",
-        "Number of HTTP requests made to fetch metrics",
-        &["uri"],
-    )
-    .unwrap();
-    static ref ERRORS: IntCounterVec = register_int_counter_vec!(
-        "adsb_http_request_errors_total",
-        "Number of HTTP request errors returned from fetching metrics",
-        &["uri", "error_type"],
-    )
-    .unwrap();
-    static ref DURATIONS: HistogramVec = register_histogram_vec!(
-        "adsb_http_request_duration_seconds",
-        "HTTP request durations",
-        &["uri"],
-    )
-    .unwrap();
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+/// Metrics of fetches from one receiver
+#[derive(Clone)]
+pub(crate) struct FetchMetrics {
+    requests: IntCounterVec,
+    errors: IntCounterVec,
+    durations: HistogramVec,
+    sizes: HistogramVec,
+    up: IntGaugeVec,
+    last_success: GaugeVec,
+    last_data: GaugeVec,
+}
+
+impl FetchMetrics {
+    /// Unregistered metrics of the named `receiver`
+    pub(crate) fn new(receiver: &str) -> Result<Self> {
+        let opts = |name: &str, help: &str| Opts::new(name, help).const_label("receiver", receiver);
+
+        Ok(FetchMetrics {
+            requests: IntCounterVec::new(
+                opts(
+                    "adsb_http_requests_total",
+                    "Number of HTTP requests made to fetch metrics",
+                ),
+                &["uri"],
+            )?,
+            errors: IntCounterVec::new(
+                opts(
+                    "adsb_http_request_errors_total",
+                    "Number of HTTP request errors returned from fetching metrics",
+                ),
+                &["uri", "error_type"],
+            )?,
+            durations: HistogramVec::new(
+                HistogramOpts::from(opts(
+                    "adsb_http_request_duration_seconds",
+                    "HTTP request durations",
+                )),
+                &["uri"],
+            )?,
+            sizes: HistogramVec::new(
+                HistogramOpts::from(opts(
+                    "adsb_http_response_size_bytes",
+                    "HTTP response body sizes",
+                ))
+                .buckets(exponential_buckets(1024.0, 4.0, 8)?),
+                &["uri"],
+            )?,
+            up: IntGaugeVec::new(
+                opts(
+                    "adsb_http_up",
+                    "Whether the last fetch succeeded (1) or failed (0)",
+                ),
+                &["uri"],
+            )?,
+            last_success: GaugeVec::new(
+                opts(
+                    "adsb_http_last_success_timestamp_seconds",
+                    "Time of the last successful fetch",
+                ),
+                &["uri"],
+            )?,
+            last_data: GaugeVec::new(
+                opts(
+                    "adsb_http_last_data_timestamp_seconds",
+                    "The now timestamp of the last successfully fetched data",
+                ),
+                &["uri"],
+            )?,
+        })
+    }
+
+    fn collectors(&self) -> Vec<&dyn Collector> {
+        vec![
+            &self.requests,
+            &self.errors,
+            &self.durations,
+            &self.sizes,
+            &self.up,
+            &self.last_success,
+            &self.last_data,
+        ]
+    }
+}
+
+impl Collector for FetchMetrics {
+    fn desc(&self) -> Vec<&Desc> {
+        self.collectors()
+            .into_iter()
+            .flat_map(|c| c.desc())
+            .collect()
+    }
+
+    fn collect(&self) -> Vec<MetricFamily> {
+        self.collectors()
+            .into_iter()
+            .flat_map(|c| c.collect())
+            .collect()
+    }
+}
+
+/// Fetches JSON from a receiver URL
+#[derive(Clone)]
+pub struct Fetcher {
+    client: Client,
+    receiver: String,
+    url: String,
+    metrics: FetchMetrics,
+    freshness: Option<Freshness>,
+}
+
+impl Fetcher {
+    /// Fetch `url` for the named `receiver` recording fetches in `metrics`, successful fetches
+    /// are recorded in `freshness`
+    pub(crate) fn new(
+        client: Client,
+        receiver: String,
+        url: String,
+        metrics: FetchMetrics,
+        freshness: Option<Freshness>,
+    ) -> Self {
+        Fetcher {
+            client,
+            receiver,
+            url,
+            metrics,
+            freshness,
+        }
+    }
+
+    pub fn receiver(&self) -> &str {
+        &self.receiver
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    pub async fn fetch(&self) -> Option<Value> {
+        let data = fetch_json(&self.client, &self.metrics, &self.url).await;
+
+        match data {
+            Some(ref data) => self.fetched(data),
+            None => self.metrics.up.with_label_values(&[&self.url]).set(0),
+        }
+
+        data
+    }
+
+    /// Record a successful fetch in the metrics and the freshness of the source
+    fn fetched(&self, data: &Value) {
+        let url = self.url.as_str();
+
+        self.metrics.up.with_label_values(&[url]).set(1);
+
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0.0, |d| d.as_secs_f64());
+
+        self.metrics.last_success.with_label_values(&[url]).set(now);
+
+        // The same timestamp is exported and checked for readiness
+        let timestamp = data.get("now").and_then(Value::as_f64);
+
+        if let Some(timestamp) = timestamp {
+            self.metrics
+                .last_data
+                .with_label_values(&[url])
+                .set(timestamp);
+        }
+
+        if let Some(ref freshness) = self.freshness {
+            freshness.fetched(timestamp);
+        }
+    }
 }
 
-pub async fn fetch(client: &Client, url: &str) -> Option<Value> {
+async fn fetch_json(client: &Client, metrics: &FetchMetrics, url: &str) -> Option<Value> {
     debug!("Fetching {}", url);
-    REQUESTS.with_label_values(&[url]).inc();
-    let timer = DURATIONS.with_label_values(&[url]).start_timer();
+    metrics.requests.with_label_values(&[url]).inc();
+    let timer = metrics.durations.with_label_values(&[url]).start_timer();
 
     let response = client.get(url).send().await;
 
@@ -44,27 +210,52 @@ pub async fn fetch(client: &Client, url: &str) -> Option<Value> {
     let response = match response {
         Ok(r) => r,
         Err(e) => {
-            debug!("request error: {:?}", e);
-            ERRORS.with_label_values(&[url, "request"]).inc();
+            debug!("Request error from {}: {:?}", url, e);
+            metrics
+                .errors
+                .with_label_values(&[url, request_error_type(&e)])
+                .inc();
             return None;
         }
     };
 
-    let body = match response.text().await {
-        Ok(t) => t,
+    if let Err(e) = response.error_for_status_ref() {
+        debug!("Response status error from {}: {:?}", url, e);
+        metrics.errors.with_label_values(&[url, "status"]).inc();
+        return None;
+    }
+
+    let body = match response.bytes().await {
+        Ok(b) => b,
         Err(e) => {
             debug!("Response body error from {}: {:?}", url, e);
-            ERRORS.with_label_values(&[url, "text"]).inc();
+            metrics.errors.with_label_values(&[url, "text"]).inc();
             return None;
         }
     };
 
-    match serde_json::from_str(&body) {
+    metrics
+        .sizes
+        .with_label_values(&[url])
+        .observe(body.len() as f64);
+
+    match serde_json::from_slice(&body) {
         Ok(j) => Some(j),
         Err(e) => {
             debug!("JSON parsing error from {}: {:?}", url, e);
-            ERRORS.with_label_values(&[url, "json"]).inc();
+            metrics.errors.with_label_values(&[url, "json"]).inc();
             None
         }
     }
 }
+
+/// Error type label of a request that failed without a response
+fn request_error_type(error: &reqwest::Error) -> &'static str {
+    if error.is_timeout() {
+        "timeout"
+    } else if error.is_connect() {
+        "connect"
+    } else {
+        "request"
+    }
+}
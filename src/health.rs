@@ -0,0 +1,187 @@
+use serde::Serialize;
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Duration;
+use std::time::Instant;
+
+type Sources = Arc<Mutex<BTreeMap<(String, String), Source>>>;
+
+/// Fetch history of a JSON file from a receiver
+struct Source {
+    url: String,
+    last_success: Option<Instant>,
+    /// Most recent `now` timestamp from the data
+    data_timestamp: Option<f64>,
+    /// When `now` last advanced, or the data was last fetched for files without `now`
+    data_advanced: Option<Instant>,
+}
+
+/// Freshness of the JSON files fetched from receivers and of BEAST and SBS sources for readiness
+/// checks
+///
+/// The exporter is ready when every source was fetched successfully within the window and the
+/// `now` timestamp of its data advanced within the window.  A BEAST or SBS source counts each
+/// message as a fetch without a `now` timestamp.
+#[derive(Clone)]
+pub struct Health {
+    window: Duration,
+    sources: Sources,
+}
+
+impl Health {
+    pub fn new(window: Duration) -> Self {
+        Health {
+            window,
+            sources: Arc::new(Mutex::new(BTreeMap::new())),
+        }
+    }
+
+    /// Freshness of `file` of `receiver` fetched from `url`, which records nothing until it is
+    /// tracked
+    pub(crate) fn freshness(&self, receiver: &str, file: &str, url: &str) -> Freshness {
+        Freshness {
+            key: (receiver.to_string(), file.to_string()),
+            url: url.to_string(),
+            sources: self.sources.clone(),
+        }
+    }
+
+    /// Stop tracking every file of `receiver`
+    pub(crate) fn remove(&self, receiver: &str) {
+        self.sources
+            .lock()
+            .unwrap()
+            .retain(|(name, _), _| name != receiver);
+    }
+
+    pub fn readiness(&self) -> Readiness {
+        self.readiness_at(Instant::now())
+    }
+
+    pub(crate) fn readiness_at(&self, now: Instant) -> Readiness {
+        let sources: Vec<SourceStatus> = self
+            .sources
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|((receiver, file), source)| {
+                let age = |instant: Option<Instant>| {
+                    instant.map(|i| now.saturating_duration_since(i).as_secs_f64())
+                };
+
+                let last_success = age(source.last_success);
+                let data_advanced = age(source.data_advanced);
+                let window = self.window.as_secs_f64();
+
+                let reason = match (last_success, data_advanced) {
+                    (None, _) => Some("not fetched yet".to_string()),
+                    (Some(age), _) if age > window => {
+                        Some(format!("last fetched {:.0} seconds ago", age))
+                    }
+                    (_, Some(age)) if age > window => {
+                        Some(format!("now has not advanced for {:.0} seconds", age))
+                    }
+                    _ => None,
+                };
+
+                SourceStatus {
+                    receiver: receiver.clone(),
+                    file: file.clone(),
+                    url: source.url.clone(),
+                    ready: reason.is_none(),
+                    reason,
+                    last_success_seconds_ago: last_success,
+                    data_timestamp: source.data_timestamp,
+                    data_advanced_seconds_ago: data_advanced,
+                }
+            })
+            .collect();
+
+        Readiness {
+            ready: sources.iter().all(|s| s.ready),
+            window_seconds: self.window.as_secs(),
+            sources,
+        }
+    }
+}
+
+/// Records successful fetches of a source once it is tracked
+#[derive(Clone)]
+pub(crate) struct Freshness {
+    key: (String, String),
+    url: String,
+    sources: Sources,
+}
+
+impl Freshness {
+    /// Start tracking the source for readiness, keeping its history if it is tracked with the
+    /// same URL already
+    pub(crate) fn track(&self) {
+        let mut sources = self.sources.lock().unwrap();
+
+        let tracked = matches!(sources.get(&self.key), Some(source) if source.url == self.url);
+
+        if !tracked {
+            sources.insert(
+                self.key.clone(),
+                Source {
+                    url: self.url.clone(),
+                    last_success: None,
+                    data_timestamp: None,
+                    data_advanced: None,
+                },
+            );
+        }
+    }
+
+    /// Record a successful fetch of data with the `now` timestamp, if it has one
+    pub(crate) fn fetched(&self, timestamp: Option<f64>) {
+        self.fetched_at(timestamp, Instant::now());
+    }
+
+    pub(crate) fn fetched_at(&self, timestamp: Option<f64>, at: Instant) {
+        let mut sources = self.sources.lock().unwrap();
+
+        let source = match sources.get_mut(&self.key) {
+            Some(source) => source,
+            None => return,
+        };
+
+        source.last_success = Some(at);
+
+        match timestamp {
+            Some(timestamp) => {
+                if !matches!(source.data_timestamp, Some(last) if timestamp <= last) {
+                    source.data_timestamp = Some(timestamp);
+                    source.data_advanced = Some(at);
+                }
+            }
+            None => source.data_advanced = Some(at),
+        }
+    }
+}
+
+/// Body of a readiness response
+#[derive(Debug, Serialize)]
+pub struct Readiness {
+    pub ready: bool,
+    pub window_seconds: u64,
+    pub sources: Vec<SourceStatus>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SourceStatus {
+    pub receiver: String,
+    pub file: String,
+    pub url: String,
+    pub ready: bool,
+    /// Why the source is not ready
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    pub last_success_seconds_ago: Option<f64>,
+    /// Most recent `now` timestamp from the data
+    pub data_timestamp: Option<f64>,
+    pub data_advanced_seconds_ago: Option<f64>,
+}
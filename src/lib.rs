@@ -1,15 +1,62 @@
 mod adsb_exporter;
 mod aircraft_json;
 pub mod beast;
+mod beast_watcher;
 mod configuration;
 mod dump_watcher;
 mod fetch;
+mod health;
+mod range;
 mod receiver_json;
+pub mod sbs;
+mod sbs_watcher;
+mod scrape;
+mod series;
+mod shutdown;
 mod stats_json;
+mod supervisor;
 
 pub use crate::adsb_exporter::ADSBExporter;
+pub use crate::beast_watcher::BeastWatcher;
 pub use crate::configuration::Configuration;
+pub use crate::configuration::Receiver;
 pub use crate::dump_watcher::DumpWatcher;
+pub use crate::dump_watcher::DumpWatchers;
+pub use crate::health::Health;
+pub use crate::sbs_watcher::SbsWatcher;
+pub use crate::scrape::Collection;
+pub use crate::scrape::ScrapeTargets;
+pub use crate::shutdown::Shutdown;
+pub use crate::shutdown::Stopper;
+
+#[cfg(test)]
+mod test_aircraft_json;
+#[cfg(test)]
+mod test_beast_watcher;
+#[cfg(test)]
+mod test_configuration;
+#[cfg(test)]
+mod test_dump_watcher;
+#[cfg(test)]
+mod test_fetch;
+#[cfg(test)]
+mod test_health;
+#[cfg(test)]
+mod test_helpers;
+#[cfg(test)]
+mod test_range;
+#[cfg(test)]
+mod test_receiver_json;
+#[cfg(test)]
+mod test_sbs;
+#[cfg(test)]
+mod test_scrape;
+#[cfg(test)]
+mod test_shutdown;
+#[cfg(test)]
+mod test_stats_json;
+#[cfg(test)]
+mod test_supervisor;
 
 #[track_caller]
 pub(crate) fn spawn_named<T>(
@@ -0,0 +1,240 @@
+use geo::algorithm::bearing::Bearing;
+use geo::algorithm::haversine_distance::HaversineDistance;
+use geo::Point;
+
+use prometheus::core::Collector;
+use prometheus::core::Desc;
+use prometheus::proto::MetricFamily;
+use prometheus::GaugeVec;
+use prometheus::IntGaugeVec;
+use prometheus::Opts;
+
+use serde::Deserialize;
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+/// Width of a distance bucket in meters
+const DISTANCE_BUCKET: u32 = 80_000;
+
+/// Distance of the largest bucket in meters, farther observations share one bucket
+const MAXIMUM_DISTANCE_BUCKET: u32 = 400_000;
+
+/// Width of a bearing bucket in degrees
+const BEARING_BUCKET: f64 = 22.5;
+
+/// Unit of distance buckets
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum DistanceUnit {
+    Meters,
+    Kilometers,
+    NauticalMiles,
+    StatuteMiles,
+}
+
+impl DistanceUnit {
+    /// Length of one unit in meters
+    pub fn meters(&self) -> f64 {
+        match self {
+            DistanceUnit::Meters => 1.0,
+            DistanceUnit::Kilometers => 1_000.0,
+            DistanceUnit::NauticalMiles => 1_852.0,
+            DistanceUnit::StatuteMiles => 1_609.344,
+        }
+    }
+
+    /// Value of the unit label of observations
+    pub fn label(&self) -> &'static str {
+        match self {
+            DistanceUnit::Meters => "meters",
+            DistanceUnit::Kilometers => "kilometers",
+            DistanceUnit::NauticalMiles => "nautical_miles",
+            DistanceUnit::StatuteMiles => "statute_miles",
+        }
+    }
+}
+
+/// Sizes of the distance and bearing buckets observations are counted in
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct RangeBuckets {
+    /// Width of a distance bucket in `unit`
+    pub distance: u32,
+    /// Distance of the largest bucket in `unit`, farther observations share one bucket
+    pub maximum_distance: u32,
+    /// Unit of the distance buckets and their labels
+    pub unit: DistanceUnit,
+    /// Width of a bearing bucket in degrees
+    pub bearing: f64,
+}
+
+impl Default for RangeBuckets {
+    fn default() -> Self {
+        RangeBuckets {
+            distance: DISTANCE_BUCKET,
+            maximum_distance: MAXIMUM_DISTANCE_BUCKET,
+            unit: DistanceUnit::Meters,
+            bearing: BEARING_BUCKET,
+        }
+    }
+}
+
+/// An aircraft position relative to the receiver
+pub struct Observation {
+    /// Distance to the aircraft in meters
+    pub distance: f64,
+    /// Upper bound of the distance bucket in `unit`
+    pub distance_bucket: String,
+    pub unit: DistanceUnit,
+    pub bearing_bucket: String,
+}
+
+impl Observation {
+    pub fn new(receiver: Point<f64>, aircraft: Point<f64>, buckets: &RangeBuckets) -> Self {
+        let distance = receiver.haversine_distance(&aircraft);
+        let units = distance / buckets.unit.meters();
+
+        // The float to integer conversion saturates, the bucket bound may not fit
+        let distance_bucket = ((units / buckets.distance as f64) as u32)
+            .checked_add(1)
+            .and_then(|bucket| bucket.checked_mul(buckets.distance))
+            .filter(|bound| *bound <= buckets.maximum_distance);
+
+        let distance_bucket = match distance_bucket {
+            Some(bound) => bound.to_string(),
+            None => format!("> {}", buckets.maximum_distance),
+        };
+
+        // North is 0°, East is 90°, West is -90°
+        let bearing = (360.0 + receiver.bearing(aircraft)) % 360.0;
+
+        let bearing_bucket = (((bearing + buckets.bearing / 2.0) / buckets.bearing).floor()
+            * buckets.bearing)
+            % 360.0;
+
+        // Multiples of fractional bucket widths like 0.1° pick up floating point noise
+        let bearing_bucket = ((bearing_bucket * 1e6).round() / 1e6) % 360.0;
+        let bearing_bucket = bearing_bucket.to_string();
+
+        Observation {
+            distance,
+            distance_bucket,
+            unit: buckets.unit,
+            bearing_bucket,
+        }
+    }
+}
+
+/// Observation counts by bearing and distance and maximum range by bearing
+#[derive(Default)]
+pub struct Ranges {
+    /// Number of observations keyed by (distance bucket, bearing bucket)
+    pub observations: HashMap<(String, String), i64>,
+    /// Maximum distance keyed by bearing bucket
+    pub ranges: HashMap<String, f64>,
+    /// Unit of the distance buckets of the observations, None until one is added
+    pub unit: Option<DistanceUnit>,
+}
+
+impl Ranges {
+    pub fn add(&mut self, observation: &Observation) {
+        let key = (
+            observation.distance_bucket.clone(),
+            observation.bearing_bucket.clone(),
+        );
+
+        *self.observations.entry(key).or_insert(0) += 1;
+        self.unit = Some(observation.unit);
+
+        let range = self
+            .ranges
+            .entry(observation.bearing_bucket.clone())
+            .or_insert(observation.distance);
+
+        if observation.distance > *range {
+            *range = observation.distance;
+        }
+    }
+}
+
+/// Observation counts by bearing and distance and maximum range by bearing as metrics
+///
+/// Each update replaces every series at once so a concurrent scrape sees either the previous or
+/// the latest ranges, never a mix of both or none at all.
+#[derive(Clone)]
+pub struct RangeMetrics {
+    observations_opts: Opts,
+    ranges_opts: Opts,
+    // Never set, these describe the families for registration
+    observations: IntGaugeVec,
+    ranges: GaugeVec,
+    current: Arc<Mutex<(IntGaugeVec, GaugeVec)>>,
+}
+
+impl RangeMetrics {
+    /// Unregistered metrics for observation counts labeled by bearing, distance and the unit of
+    /// the distance and maximum ranges in meters labeled by bearing
+    pub fn new(observations: Opts, ranges: Opts) -> prometheus::Result<Self> {
+        let (observations_vec, ranges_vec) = families(&observations, &ranges)?;
+        let current = families(&observations, &ranges)?;
+
+        Ok(RangeMetrics {
+            observations_opts: observations,
+            ranges_opts: ranges,
+            observations: observations_vec,
+            ranges: ranges_vec,
+            current: Arc::new(Mutex::new(current)),
+        })
+    }
+
+    /// Replace the exported series with `ranges`
+    pub fn update(&self, ranges: &Ranges) -> prometheus::Result<()> {
+        let (observations, maximums) = families(&self.observations_opts, &self.ranges_opts)?;
+        let unit = ranges.unit.map_or("", |unit| unit.label());
+
+        ranges
+            .observations
+            .iter()
+            .for_each(|((distance, bearing), count)| {
+                observations
+                    .with_label_values(&[bearing, distance, unit])
+                    .set(*count)
+            });
+
+        ranges
+            .ranges
+            .iter()
+            .for_each(|(bearing, maximum)| maximums.with_label_values(&[bearing]).set(*maximum));
+
+        *self.current.lock().unwrap() = (observations, maximums);
+
+        Ok(())
+    }
+}
+
+impl Collector for RangeMetrics {
+    fn desc(&self) -> Vec<&Desc> {
+        let mut desc = self.observations.desc();
+        desc.extend(self.ranges.desc());
+
+        desc
+    }
+
+    fn collect(&self) -> Vec<MetricFamily> {
+        let current = self.current.lock().unwrap();
+
+        let mut families = current.0.collect();
+        families.extend(current.1.collect());
+
+        families
+    }
+}
+
+fn families(observations: &Opts, ranges: &Opts) -> prometheus::Result<(IntGaugeVec, GaugeVec)> {
+    Ok((
+        IntGaugeVec::new(observations.clone(), &["bearing", "distance", "unit"])?,
+        GaugeVec::new(ranges.clone(), &["bearing"])?,
+    ))
+}
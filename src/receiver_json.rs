@@ -1,18 +1,18 @@
 use anyhow::Context;
 use anyhow::Result;
 
-use crate::fetch::fetch;
+use crate::fetch::Fetcher;
+use crate::series;
 
 use geo::Coordinate;
 
-use lazy_static::lazy_static;
-
 use log::debug;
 
-use prometheus::register_gauge_vec;
+use prometheus::core::Collector;
+use prometheus::core::Desc;
+use prometheus::proto::MetricFamily;
 use prometheus::GaugeVec;
-
-use reqwest::Client;
+use prometheus::Opts;
 
 use serde_json::Value;
 
@@ -22,51 +22,100 @@ use std::time::Duration;
 use tokio::sync::RwLock;
 use tokio::time::sleep;
 
-lazy_static! {
-    static ref VERSION: GaugeVec = register_gauge_vec!(
-        "adsb_receiver_version_info",
-        "Version of the receiver software",
-        &["frequency", "version"],
-    )
-    .unwrap();
-    static ref POSITION: GaugeVec = register_gauge_vec!(
-        "adsb_receiver_position_info",
-        "Position of the receiver",
-        &["frequency", "latitude", "longitude"],
-    )
-    .unwrap();
+/// Metrics exported from receiver.json
+#[derive(Clone)]
+pub(crate) struct ReceiverMetrics {
+    version: GaugeVec,
+    position: GaugeVec,
+}
+
+impl ReceiverMetrics {
+    /// Unregistered metrics of the named `receiver` listening on `frequency`
+    pub(crate) fn new(receiver: &str, frequency: &str) -> Result<Self> {
+        Ok(ReceiverMetrics {
+            version: GaugeVec::new(
+                Opts::new(
+                    "adsb_receiver_version_info",
+                    "Version of the receiver software",
+                )
+                .const_label("receiver", receiver)
+                .const_label("frequency", frequency),
+                &["version"],
+            )?,
+            position: GaugeVec::new(
+                Opts::new("adsb_receiver_position_info", "Position of the receiver")
+                    .const_label("receiver", receiver)
+                    .const_label("frequency", frequency),
+                &["latitude", "longitude"],
+            )?,
+        })
+    }
+
+    fn collectors(&self) -> Vec<&dyn Collector> {
+        vec![&self.version, &self.position]
+    }
+}
+
+impl Collector for ReceiverMetrics {
+    fn desc(&self) -> Vec<&Desc> {
+        self.collectors()
+            .into_iter()
+            .flat_map(|c| c.desc())
+            .collect()
+    }
+
+    fn collect(&self) -> Vec<MetricFamily> {
+        self.collectors()
+            .into_iter()
+            .flat_map(|c| c.collect())
+            .collect()
+    }
 }
 
+#[derive(Clone)]
 pub struct ReceiverJson {
-    client: Client,
-    frequency: String,
-    url: String,
+    fetcher: Fetcher,
+    metrics: ReceiverMetrics,
     interval: Duration,
     position: Arc<RwLock<Option<Coordinate<f64>>>>,
 }
 
 impl ReceiverJson {
-    pub fn new(client: Client, frequency: u32, url: String, interval: Duration) -> ReceiverJson {
-        let frequency = frequency.to_string();
-        let position = Arc::new(RwLock::new(None));
+    pub fn new(
+        fetcher: Fetcher,
+        frequency: u32,
+        interval: Duration,
+        position: Arc<RwLock<Option<Coordinate<f64>>>>,
+    ) -> ReceiverJson {
+        let metrics = ReceiverMetrics::new(fetcher.receiver(), &frequency.to_string())
+            .expect("Receiver metrics are invalid, bug?");
 
         ReceiverJson {
-            client,
-            frequency,
-            url,
+            fetcher,
+            metrics,
             interval,
             position,
         }
     }
 
-    pub fn position(&self) -> Arc<RwLock<Option<Coordinate<f64>>>> {
-        self.position.clone()
+    pub(crate) fn fetcher(&self) -> &Fetcher {
+        &self.fetcher
+    }
+
+    /// Metrics updated by run(), register them to export them
+    pub(crate) fn metrics(&self) -> &ReceiverMetrics {
+        &self.metrics
+    }
+
+    /// Update `metrics` instead, like the metrics of the previous watcher of the receiver
+    pub(crate) fn with_metrics(self, metrics: ReceiverMetrics) -> Self {
+        ReceiverJson { metrics, ..self }
     }
 
     pub async fn run(&self) {
         loop {
-            if let Some(data) = fetch(&self.client, &self.url).await {
-                match self.update_receiver(data).await {
+            if let Some(data) = self.fetcher.fetch().await {
+                match self.update_receiver(&self.metrics, data).await {
                     Ok(_) => (),
                     Err(e) => {
                         debug!("error updating receiver {:?}", e);
@@ -78,16 +127,21 @@ impl ReceiverJson {
         }
     }
 
-    async fn update_receiver(&self, data: Value) -> Result<()> {
+    pub(crate) async fn update_receiver(
+        &self,
+        metrics: &ReceiverMetrics,
+        data: Value,
+    ) -> Result<()> {
         let version = data
             .get("version")
             .context("Missing field version from receiver.json")?
             .as_str()
             .context("Field version from receiver.json is not a string")?
             .to_string();
-        VERSION
-            .with_label_values(&[&self.frequency, &version])
-            .set(1.0);
+        metrics.version.with_label_values(&[&version]).set(1.0);
+
+        // Only the running version and current position are exported after an upgrade or move
+        series::retain(&metrics.version, |labels| labels["version"] == version);
 
         let latitude = data
             .get("lat")
@@ -101,17 +155,22 @@ impl ReceiverJson {
             .as_f64()
             .context("Field lon from receiver.json is not a number")?
             .to_string();
-        POSITION
-            .with_label_values(&[&self.frequency, &latitude, &longitude])
+        metrics
+            .position
+            .with_label_values(&[&latitude, &longitude])
             .set(1.0);
 
+        series::retain(&metrics.position, |labels| {
+            labels["latitude"] == latitude && labels["longitude"] == longitude
+        });
+
         let latitude = latitude.parse::<f64>().unwrap();
         let longitude = longitude.parse::<f64>().unwrap();
 
         let mut position = self.position.write().await;
         *position = Some(Coordinate {
-            x: latitude,
-            y: longitude,
+            x: longitude,
+            y: latitude,
         });
 
         Ok(())
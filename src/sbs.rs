@@ -0,0 +1,193 @@
+use anyhow::anyhow;
+use anyhow::Context;
+use anyhow::Error;
+use anyhow::Result;
+
+use chrono::DateTime;
+use chrono::Local;
+use chrono::NaiveDate;
+use chrono::NaiveDateTime;
+use chrono::NaiveTime;
+use chrono::TimeZone;
+
+use std::str::FromStr;
+
+/// Minimum number of fields in a message, trailing empty fields are sometimes omitted
+const MINIMUM_FIELDS: usize = 10;
+
+/// How the position or other data in a message was determined
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Source {
+    /// Received directly from the aircraft
+    ModeS,
+    /// Determined by multilateration, `MLAT` lines from mlat-client
+    Mlat,
+    /// Rebroadcast by a ground station, `TISB` lines
+    TisB,
+}
+
+/// Record types that carry no aircraft data, selection changes, new aircraft and IDs, status
+/// changes and clock ticks
+const OTHER_RECORD_TYPES: [&str; 5] = ["SEL", "ID", "AIR", "STA", "CLK"];
+
+/// A line from an SBS-1 BaseStation server
+#[derive(Debug, PartialEq)]
+pub enum SbsRecord {
+    /// A `MSG`, `MLAT` or `TISB` line
+    Message(SbsMessage),
+    /// A `SEL`, `ID`, `AIR`, `STA` or `CLK` line, these are ignored
+    Other,
+}
+
+impl FromStr for SbsRecord {
+    type Err = Error;
+
+    fn from_str(line: &str) -> Result<Self> {
+        let record_type = line.split(',').next().unwrap_or_default().trim();
+
+        if OTHER_RECORD_TYPES.contains(&record_type) {
+            return Ok(SbsRecord::Other);
+        }
+
+        Ok(SbsRecord::Message(line.parse()?))
+    }
+}
+
+/// An SBS-1 BaseStation `MSG` line, like dump1090 port 30003
+#[derive(Debug, PartialEq)]
+pub struct SbsMessage {
+    pub source: Source,
+    /// MSG,1 through MSG,8
+    pub transmission_type: u8,
+    pub icao: String,
+    /// Local time the message was generated
+    pub generated: Option<NaiveDateTime>,
+    /// Local time the message was logged, later than generated for delayed messages
+    pub logged: Option<NaiveDateTime>,
+    pub call_sign: Option<String>,
+    /// Altitude in feet
+    pub altitude: Option<i32>,
+    /// Ground speed in knots
+    pub ground_speed: Option<f64>,
+    /// Track over the ground in degrees
+    pub track: Option<f64>,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    /// Vertical rate in feet per minute
+    pub vertical_rate: Option<i32>,
+    pub squawk: Option<String>,
+    pub alert: Option<bool>,
+    pub emergency: Option<bool>,
+    pub spi: Option<bool>,
+    pub on_ground: Option<bool>,
+}
+
+impl SbsMessage {
+    /// Latitude and longitude if the message contains a position
+    pub fn position(&self) -> Option<(f64, f64)> {
+        match (self.latitude, self.longitude) {
+            (Some(latitude), Some(longitude)) => Some((latitude, longitude)),
+            _ => None,
+        }
+    }
+
+    /// Time the message was generated, or logged if the generated time is missing
+    pub fn time(&self) -> Option<DateTime<Local>> {
+        let time = self.generated.or(self.logged)?;
+
+        Local.from_local_datetime(&time).earliest()
+    }
+}
+
+impl FromStr for SbsMessage {
+    type Err = Error;
+
+    fn from_str(line: &str) -> Result<Self> {
+        let fields: Vec<&str> = line.trim_end().split(',').map(str::trim).collect();
+
+        if fields.len() < MINIMUM_FIELDS {
+            return Err(anyhow!(
+                "expected at least {} fields, got {}",
+                MINIMUM_FIELDS,
+                fields.len()
+            ));
+        }
+
+        let field = |index: usize| -> Option<&str> {
+            match fields.get(index) {
+                Some(&"") | None => None,
+                Some(value) => Some(value),
+            }
+        };
+
+        let source = match fields[0] {
+            "MSG" => Source::ModeS,
+            "MLAT" => Source::Mlat,
+            "TISB" | "TIS-B" => Source::TisB,
+            record_type => return Err(anyhow!("unsupported record type {}", record_type)),
+        };
+
+        let transmission_type = match fields[1].parse() {
+            Ok(tt @ 1..=8) => tt,
+            _ => return Err(anyhow!("invalid transmission type {}", fields[1])),
+        };
+
+        let icao = field(4)
+            .context("missing hex ident")?
+            .trim_start_matches('~')
+            .to_uppercase();
+
+        Ok(SbsMessage {
+            source,
+            transmission_type,
+            icao,
+            generated: date_time(field(6), field(7))?,
+            logged: date_time(field(8), field(9))?,
+            call_sign: field(10).map(str::to_string),
+            altitude: number(field(11), "altitude")?,
+            ground_speed: number(field(12), "ground speed")?,
+            track: number(field(13), "track")?,
+            latitude: number(field(14), "latitude")?,
+            longitude: number(field(15), "longitude")?,
+            vertical_rate: number(field(16), "vertical rate")?,
+            squawk: field(17).map(str::to_string),
+            alert: flag(field(18)),
+            emergency: flag(field(19)),
+            spi: flag(field(20)),
+            on_ground: flag(field(21)),
+        })
+    }
+}
+
+fn date_time(date: Option<&str>, time: Option<&str>) -> Result<Option<NaiveDateTime>> {
+    let (date, time) = match (date, time) {
+        (Some(date), Some(time)) => (date, time),
+        _ => return Ok(None),
+    };
+
+    let date = NaiveDate::parse_from_str(date, "%Y/%m/%d")
+        .with_context(|| format!("invalid date {}", date))?;
+    let time = NaiveTime::parse_from_str(time, "%H:%M:%S%.f")
+        .with_context(|| format!("invalid time {}", time))?;
+
+    Ok(Some(NaiveDateTime::new(date, time)))
+}
+
+fn number<T: FromStr>(value: Option<&str>, name: &str) -> Result<Option<T>> {
+    match value {
+        Some(value) => match value.parse() {
+            Ok(number) => Ok(Some(number)),
+            Err(_) => Err(anyhow!("invalid {} {}", name, value)),
+        },
+        None => Ok(None),
+    }
+}
+
+// BaseStation flags are -1 for true and 0 for false, some writers use 1 for true
+fn flag(value: Option<&str>) -> Option<bool> {
+    match value {
+        Some("-1") | Some("1") => Some(true),
+        Some("0") => Some(false),
+        _ => None,
+    }
+}
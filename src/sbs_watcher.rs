@@ -0,0 +1,532 @@
+use anyhow::Result;
+
+use crate::adsb_exporter::ErrorSender;
+use crate::beast_watcher::send_error;
+use crate::beast_watcher::tcp_url;
+use crate::configuration::Configuration;
+use crate::health::Freshness;
+use crate::health::Health;
+use crate::range::Observation;
+use crate::range::RangeBuckets;
+use crate::range::RangeMetrics;
+use crate::range::Ranges;
+use crate::sbs::SbsMessage;
+use crate::sbs::SbsRecord;
+use crate::sbs::Source;
+use crate::shutdown::Stopper;
+
+use chrono::DateTime;
+use chrono::Local;
+
+use futures_util::StreamExt;
+
+use geo::Point;
+
+use log::debug;
+use log::info;
+
+use prometheus::core::Collector;
+use prometheus::core::Desc;
+use prometheus::proto::MetricFamily;
+use prometheus::GaugeVec;
+use prometheus::IntCounterVec;
+use prometheus::Opts;
+use prometheus::Registry;
+
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::collections::VecDeque;
+use std::time::Duration;
+use std::time::Instant;
+
+use tokio::net::TcpStream;
+use tokio::sync::mpsc;
+use tokio::time::interval;
+use tokio::time::sleep;
+
+use tokio_util::codec::FramedRead;
+use tokio_util::codec::LinesCodec;
+
+const FREQUENCY: &str = "1090";
+
+/// Number of parsed messages buffered before readers wait for the metrics task
+const MESSAGE_CAPACITY: usize = 4096;
+
+/// Longest line accepted from an SBS server, real lines are around 120 bytes
+const MAXIMUM_LINE_LENGTH: usize = 1024;
+
+/// Metrics of messages read from SBS servers
+#[derive(Clone)]
+struct SbsMetrics {
+    messages: IntCounterVec,
+    parse_errors: IntCounterVec,
+    stale: IntCounterVec,
+    recent_observed: GaugeVec,
+    recent_positions: GaugeVec,
+    recent_mlat: GaugeVec,
+    recent_tisb: GaugeVec,
+}
+
+impl SbsMetrics {
+    /// Unregistered metrics of SBS servers
+    fn new() -> Result<Self> {
+        let labels = &["receiver", "frequency"];
+
+        Ok(SbsMetrics {
+            messages: IntCounterVec::new(
+                Opts::new(
+                    "adsb_sbs_messages_total",
+                    "Number of SBS messages received by source and transmission type",
+                ),
+                &["receiver", "frequency", "source", "transmission_type"],
+            )?,
+            parse_errors: IntCounterVec::new(
+                Opts::new(
+                    "adsb_sbs_parse_errors_total",
+                    "Number of SBS lines that could not be parsed",
+                ),
+                labels,
+            )?,
+            stale: IntCounterVec::new(
+                Opts::new(
+                    "adsb_sbs_stale_messages_total",
+                    "Number of SBS messages ignored because they were generated before the recent window",
+                ),
+                labels,
+            )?,
+            recent_observed: GaugeVec::new(
+                Opts::new(
+                    "adsb_sbs_aircraft_observed_recent",
+                    "Number of aircraft observed in the recent window",
+                ),
+                labels,
+            )?,
+            recent_positions: GaugeVec::new(
+                Opts::new(
+                    "adsb_sbs_aircraft_with_position_recent",
+                    "Number of aircraft observed with a position in the recent window",
+                ),
+                labels,
+            )?,
+            recent_mlat: GaugeVec::new(
+                Opts::new(
+                    "adsb_sbs_aircraft_mlat_recent",
+                    "Number of aircraft observed with a position determined by multilateration in the recent window",
+                ),
+                labels,
+            )?,
+            recent_tisb: GaugeVec::new(
+                Opts::new(
+                    "adsb_sbs_aircraft_tisb_recent",
+                    "Number of aircraft observed with a position rebroadcast by TIS-B in the recent window",
+                ),
+                labels,
+            )?,
+        })
+    }
+
+    fn collectors(&self) -> Vec<&dyn Collector> {
+        vec![
+            &self.messages,
+            &self.parse_errors,
+            &self.stale,
+            &self.recent_observed,
+            &self.recent_positions,
+            &self.recent_mlat,
+            &self.recent_tisb,
+        ]
+    }
+}
+
+impl Collector for SbsMetrics {
+    fn desc(&self) -> Vec<&Desc> {
+        self.collectors()
+            .into_iter()
+            .flat_map(|c| c.desc())
+            .collect()
+    }
+
+    fn collect(&self) -> Vec<MetricFamily> {
+        self.collectors()
+            .into_iter()
+            .flat_map(|c| c.collect())
+            .collect()
+    }
+}
+
+/// Reads SBS-1 BaseStation messages and exports aircraft metrics for them
+pub struct SbsWatcher {
+    addresses: Vec<String>,
+    reconnect_interval: Duration,
+    position: Option<Point<f64>>,
+    range: RangeBuckets,
+    recent: Duration,
+    registry: Registry,
+    metrics: SbsMetrics,
+    health: Health,
+}
+
+/// Last time an aircraft was seen and last time and source of its position
+struct Aircraft {
+    last_seen: Instant,
+    last_position: Option<(Instant, Source)>,
+}
+
+/// Aircraft tracking state of an SBS server for metrics
+struct Receiver {
+    aircraft: HashMap<String, Aircraft>,
+    observations: VecDeque<(Instant, Observation)>,
+    ranges: RangeMetrics,
+}
+
+impl Receiver {
+    /// Tracking state of the named server with its range metrics registered in `registry`
+    fn new(name: &str, registry: &Registry) -> Result<Self> {
+        let ranges = RangeMetrics::new(
+            Opts::new(
+                "adsb_sbs_aircraft_observations_recent",
+                "Number of aircraft positions observed by range and bearing in the recent window",
+            )
+            .const_label("receiver", name)
+            .const_label("frequency", FREQUENCY),
+            Opts::new(
+                "adsb_sbs_aircraft_ranges_recent",
+                "Maximum range to an observed aircraft by bearing in the recent window",
+            )
+            .const_label("receiver", name)
+            .const_label("frequency", FREQUENCY),
+        )?;
+
+        registry.register(Box::new(ranges.clone()))?;
+
+        Ok(Receiver {
+            aircraft: HashMap::new(),
+            observations: VecDeque::new(),
+            ranges,
+        })
+    }
+}
+
+/// Aircraft tracking state for metrics by SBS server
+struct State {
+    receivers: HashMap<String, Receiver>,
+    /// Registry of the range metrics of each server
+    registry: Registry,
+    metrics: SbsMetrics,
+    range: RangeBuckets,
+    /// How long an aircraft or position counts as recent
+    recent: Duration,
+    /// Readiness of each SBS server
+    servers: HashMap<String, Freshness>,
+}
+
+impl SbsWatcher {
+    /// A watcher for the SBS servers of `configuration` with metrics registered in `registry`
+    /// and servers tracked in `health`
+    pub fn new(configuration: &Configuration, registry: Registry, health: Health) -> Result<Self> {
+        let addresses = configuration.sbs_address.clone();
+        let reconnect_interval = configuration.sbs_reconnect_interval;
+
+        let position = match (configuration.latitude, configuration.longitude) {
+            (Some(latitude), Some(longitude)) => Some(Point::new(longitude, latitude)),
+            _ => None,
+        };
+
+        let range = configuration.range;
+        let recent = configuration.recent_window;
+
+        let metrics = SbsMetrics::new()?;
+        registry.register(Box::new(metrics.clone()))?;
+
+        Ok(SbsWatcher {
+            addresses,
+            reconnect_interval,
+            position,
+            range,
+            recent,
+            registry,
+            metrics,
+            health,
+        })
+    }
+
+    /// Start reading from each SBS server until the returned Stopper is stopped, failures to
+    /// update metrics are sent to `error_tx`
+    pub async fn start(self, error_tx: ErrorSender) -> Stopper {
+        let stopper = Stopper::new();
+        let (messages, messages_rx) = mpsc::channel(MESSAGE_CAPACITY);
+        let position = self.position;
+
+        // Servers are ready once messages from them are parsed
+        let servers = self
+            .addresses
+            .iter()
+            .map(|address| {
+                let freshness = self.health.freshness(address, "sbs", &tcp_url(address));
+                freshness.track();
+
+                (address.clone(), freshness)
+            })
+            .collect();
+
+        let state = State {
+            receivers: HashMap::new(),
+            registry: self.registry.clone(),
+            metrics: self.metrics.clone(),
+            range: self.range,
+            recent: self.recent,
+            servers,
+        };
+
+        crate::spawn_named(
+            async move {
+                if let Err(e) = update_metrics(messages_rx, position, state).await {
+                    send_error(error_tx, e).await;
+                }
+            },
+            "sbs::metrics",
+        );
+
+        for address in self.addresses {
+            info!("Watching SBS server at {}", address);
+
+            let name = format!("sbs::client::{}", address);
+            let reconnect_interval = self.reconnect_interval;
+            let messages = messages.clone();
+            let parse_errors = self.metrics.parse_errors.clone();
+            let mut shutdown = stopper.shutdown();
+
+            crate::spawn_named(
+                async move {
+                    tokio::select! {
+                        _ = read_server(address, reconnect_interval, messages, parse_errors) => (),
+                        _ = shutdown.requested() => (),
+                    }
+                },
+                &name,
+            );
+        }
+
+        stopper
+    }
+}
+
+async fn read_server(
+    address: String,
+    reconnect_interval: Duration,
+    messages: mpsc::Sender<(String, SbsMessage)>,
+    parse_errors: IntCounterVec,
+) {
+    loop {
+        match TcpStream::connect(&address).await {
+            Ok(stream) => {
+                info!("Connected to SBS server at {}", address);
+
+                let codec = LinesCodec::new_with_max_length(MAXIMUM_LINE_LENGTH);
+                let mut lines = FramedRead::new(stream, codec);
+
+                loop {
+                    match lines.next().await {
+                        Some(Ok(line)) => match line.parse() {
+                            Ok(SbsRecord::Message(message)) => {
+                                if messages.send((address.clone(), message)).await.is_err() {
+                                    return;
+                                }
+                            }
+                            Ok(SbsRecord::Other) => (),
+                            Err(e) => {
+                                debug!("error parsing {:?} from {}: {:#}", line, address, e);
+                                parse_errors.with_label_values(&[&address, FREQUENCY]).inc();
+                            }
+                        },
+                        Some(Err(e)) => {
+                            debug!("error reading from {}: {:?}", address, e);
+                            break;
+                        }
+                        None => {
+                            info!("SBS server at {} closed the connection", address);
+                            break;
+                        }
+                    }
+                }
+            }
+            Err(e) => info!("Unable to connect to SBS server at {}: {}", address, e),
+        }
+
+        sleep(reconnect_interval).await;
+    }
+}
+
+async fn update_metrics(
+    mut messages: mpsc::Receiver<(String, SbsMessage)>,
+    position: Option<Point<f64>>,
+    mut state: State,
+) -> Result<()> {
+    if position.is_none() {
+        info!("Receiver position unknown, set --latitude and --longitude for SBS range metrics");
+    }
+
+    let mut recent_interval = interval(Duration::from_secs(1));
+
+    loop {
+        tokio::select! {
+            message = messages.recv() => match message {
+                Some((receiver, message)) => update_message(
+                    &mut state,
+                    position,
+                    &receiver,
+                    &message,
+                    Instant::now(),
+                    Local::now(),
+                )?,
+                None => break,
+            },
+            _ = recent_interval.tick() => update_recent(&mut state)?,
+        }
+    }
+
+    Ok(())
+}
+
+fn update_message(
+    state: &mut State,
+    position: Option<Point<f64>>,
+    name: &str,
+    message: &SbsMessage,
+    now: Instant,
+    wall: DateTime<Local>,
+) -> Result<()> {
+    let source = match message.source {
+        Source::ModeS => "mode_s",
+        Source::Mlat => "mlat",
+        Source::TisB => "tisb",
+    };
+
+    state
+        .metrics
+        .messages
+        .with_label_values(&[
+            name,
+            FREQUENCY,
+            source,
+            &message.transmission_type.to_string(),
+        ])
+        .inc();
+
+    // Messages buffered by a slow server or replayed from a log are not recent observations
+    if let Some(time) = message.time() {
+        if wall.signed_duration_since(time).num_milliseconds() > state.recent.as_millis() as i64 {
+            state
+                .metrics
+                .stale
+                .with_label_values(&[name, FREQUENCY])
+                .inc();
+            return Ok(());
+        }
+    }
+
+    // A recent message is data arriving, like a fetch without a `now` timestamp
+    if let Some(freshness) = state.servers.get(name) {
+        freshness.fetched(None);
+    }
+
+    let range = &state.range;
+    let receiver = match state.receivers.entry(name.to_string()) {
+        Entry::Occupied(entry) => entry.into_mut(),
+        Entry::Vacant(entry) => entry.insert(Receiver::new(name, &state.registry)?),
+    };
+
+    let aircraft = receiver
+        .aircraft
+        .entry(message.icao.clone())
+        .or_insert(Aircraft {
+            last_seen: now,
+            last_position: None,
+        });
+
+    aircraft.last_seen = now;
+
+    let (latitude, longitude) = match message.position() {
+        Some(position) => position,
+        None => return Ok(()),
+    };
+
+    aircraft.last_position = Some((now, message.source));
+
+    // TIS-B positions are rebroadcast by a ground station so they say nothing about range
+    if message.source == Source::TisB {
+        return Ok(());
+    }
+
+    if let Some(receiver_position) = position {
+        let aircraft = Point::new(longitude, latitude);
+
+        receiver
+            .observations
+            .push_back((now, Observation::new(receiver_position, aircraft, range)));
+    }
+
+    Ok(())
+}
+
+fn update_recent(state: &mut State) -> Result<()> {
+    let now = Instant::now();
+    let recent = state.recent;
+    let metrics = &state.metrics;
+
+    for (name, receiver) in state.receivers.iter_mut() {
+        receiver
+            .aircraft
+            .retain(|_, aircraft| now.duration_since(aircraft.last_seen) < recent);
+
+        while let Some((seen, _)) = receiver.observations.front() {
+            if now.duration_since(*seen) < recent {
+                break;
+            }
+
+            receiver.observations.pop_front();
+        }
+
+        let positioned = |source: Option<Source>| {
+            receiver
+                .aircraft
+                .values()
+                .filter(|a| match (a.last_position, source) {
+                    (Some((seen, from)), Some(source)) => {
+                        now.duration_since(seen) < recent && from == source
+                    }
+                    (Some((seen, _)), None) => now.duration_since(seen) < recent,
+                    (None, _) => false,
+                })
+                .count() as f64
+        };
+
+        metrics
+            .recent_observed
+            .with_label_values(&[name, FREQUENCY])
+            .set(receiver.aircraft.len() as f64);
+        metrics
+            .recent_positions
+            .with_label_values(&[name, FREQUENCY])
+            .set(positioned(None));
+        metrics
+            .recent_mlat
+            .with_label_values(&[name, FREQUENCY])
+            .set(positioned(Some(Source::Mlat)));
+        metrics
+            .recent_tisb
+            .with_label_values(&[name, FREQUENCY])
+            .set(positioned(Some(Source::TisB)));
+
+        let mut ranges = Ranges::default();
+
+        receiver
+            .observations
+            .iter()
+            .for_each(|(_, observation)| ranges.add(observation));
+
+        receiver.ranges.update(&ranges)?;
+    }
+
+    Ok(())
+}
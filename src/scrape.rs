@@ -0,0 +1,262 @@
+use crate::aircraft_json::AircraftJson;
+use crate::aircraft_json::AircraftMetrics;
+use crate::fetch::Fetcher;
+use crate::receiver_json::ReceiverJson;
+use crate::receiver_json::ReceiverMetrics;
+use crate::stats_json::StatsJson;
+use crate::stats_json::StatsMetrics;
+
+use anyhow::Result;
+
+use futures_util::future::join_all;
+
+use log::debug;
+
+use prometheus::core::Collector;
+use prometheus::core::Desc;
+use prometheus::proto::MetricFamily;
+
+use serde_json::Value;
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Duration;
+use std::time::Instant;
+
+/// How receiver JSON is collected
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Collection {
+    /// Fetch each file on its refresh interval
+    Poll,
+    /// Fetch files when metrics are scraped, files fetched within `cache_age` are reused
+    Scrape { cache_age: Duration },
+}
+
+/// Metrics built from receiver JSON for one scrape
+struct Metrics {
+    aircraft: AircraftMetrics,
+    receiver: ReceiverMetrics,
+    stats: Option<StatsMetrics>,
+}
+
+impl Metrics {
+    fn new(receiver: &str, frequency: &str, stats: bool) -> Result<Self> {
+        let stats = if stats {
+            Some(StatsMetrics::new(receiver, frequency)?)
+        } else {
+            None
+        };
+
+        Ok(Metrics {
+            aircraft: AircraftMetrics::new(receiver, frequency)?,
+            receiver: ReceiverMetrics::new(receiver, frequency)?,
+            stats,
+        })
+    }
+
+    fn desc(&self) -> Vec<&Desc> {
+        let mut desc = self.aircraft.desc();
+        desc.extend(self.receiver.desc());
+
+        if let Some(ref stats) = self.stats {
+            desc.extend(stats.desc());
+        }
+
+        desc
+    }
+
+    fn collect(&self) -> Vec<MetricFamily> {
+        let mut families = self.aircraft.collect();
+        families.extend(self.receiver.collect());
+
+        if let Some(ref stats) = self.stats {
+            families.extend(stats.collect());
+        }
+
+        families
+    }
+}
+
+/// JSON fetched for a scrape, reused until it is older than `max_age`
+struct Cached {
+    fetcher: Fetcher,
+    max_age: Duration,
+    fetched: tokio::sync::Mutex<Option<(Instant, Value)>>,
+}
+
+impl Cached {
+    fn new(fetcher: &Fetcher, max_age: Duration) -> Arc<Self> {
+        Arc::new(Cached {
+            fetcher: fetcher.clone(),
+            max_age,
+            fetched: tokio::sync::Mutex::new(None),
+        })
+    }
+
+    /// Cached JSON or freshly fetched JSON, concurrent scrapes wait for the same fetch
+    async fn get(&self) -> Option<Value> {
+        let mut fetched = self.fetched.lock().await;
+
+        if let Some((at, ref data)) = *fetched {
+            if at.elapsed() < self.max_age {
+                return Some(data.clone());
+            }
+        }
+
+        *fetched = self
+            .fetcher
+            .fetch()
+            .await
+            .map(|data| (Instant::now(), data));
+
+        fetched.as_ref().map(|(_, data)| data.clone())
+    }
+}
+
+/// Receiver JSON fetched when metrics are scraped
+///
+/// Register the target to export the metrics built from the JSON fetched by the latest refresh().
+/// Collector::collect is synchronous so the server refreshes targets before gathering metrics.
+#[derive(Clone)]
+pub(crate) struct Target {
+    receiver: String,
+    frequency: String,
+    receiver_json: ReceiverJson,
+    receiver_data: Arc<Cached>,
+    aircraft_json: AircraftJson,
+    aircraft_data: Arc<Cached>,
+    stats: Option<(StatsJson, Arc<Cached>)>,
+    template: Arc<Metrics>,
+    collected: Arc<Mutex<Option<Metrics>>>,
+}
+
+impl Target {
+    /// receiver.json is reused for `receiver_interval` as the receiver rarely changes,
+    /// aircraft.json and stats.json for `cache_age`
+    pub(crate) fn new(
+        receiver_json: ReceiverJson,
+        aircraft_json: AircraftJson,
+        stats_json: Option<StatsJson>,
+        receiver_interval: Duration,
+        cache_age: Duration,
+    ) -> Self {
+        let receiver = aircraft_json.fetcher().receiver().to_string();
+        let frequency = aircraft_json.frequency().to_string();
+
+        let receiver_data = Cached::new(receiver_json.fetcher(), receiver_interval);
+        let aircraft_data = Cached::new(aircraft_json.fetcher(), cache_age);
+        let stats = stats_json.map(|stats_json| {
+            let stats_data = Cached::new(stats_json.fetcher(), cache_age);
+
+            (stats_json, stats_data)
+        });
+
+        let template = Metrics::new(&receiver, &frequency, stats.is_some())
+            .expect("Receiver metrics are invalid, bug?");
+
+        Target {
+            receiver,
+            frequency,
+            receiver_json,
+            receiver_data,
+            aircraft_json,
+            aircraft_data,
+            stats,
+            template: Arc::new(template),
+            collected: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Fetch the receiver JSON and rebuild the collected metrics
+    ///
+    /// receiver.json is fetched first as aircraft ranges need the receiver position.  Each fetch
+    /// is limited by the receiver's refresh timeout, JSON that can't be fetched has no metrics.
+    pub(crate) async fn refresh(&self) {
+        let metrics = Metrics::new(&self.receiver, &self.frequency, self.stats.is_some())
+            .expect("Receiver metrics are invalid, bug?");
+
+        if let Some(data) = self.receiver_data.get().await {
+            if let Err(e) = self
+                .receiver_json
+                .update_receiver(&metrics.receiver, data)
+                .await
+            {
+                debug!("error updating receiver {:?}", e);
+            }
+        }
+
+        let aircraft = async {
+            if let Some(data) = self.aircraft_data.get().await {
+                if let Err(e) = self
+                    .aircraft_json
+                    .update_aircraft(&metrics.aircraft, data)
+                    .await
+                {
+                    debug!("error updating aircraft {:?}", e);
+                }
+            }
+        };
+
+        let stats = async {
+            if let (Some((ref stats_json, ref stats_data)), Some(ref stats_metrics)) =
+                (&self.stats, &metrics.stats)
+            {
+                if let Some(data) = stats_data.get().await {
+                    if let Err(e) = stats_json.update_stats(stats_metrics, data) {
+                        debug!("error updating stats {:?}", e);
+                    }
+                }
+            }
+        };
+
+        tokio::join!(aircraft, stats);
+
+        *self.collected.lock().unwrap() = Some(metrics);
+    }
+}
+
+impl Collector for Target {
+    fn desc(&self) -> Vec<&Desc> {
+        self.template.desc()
+    }
+
+    fn collect(&self) -> Vec<MetricFamily> {
+        match *self.collected.lock().unwrap() {
+            Some(ref metrics) => metrics.collect(),
+            None => vec![],
+        }
+    }
+}
+
+/// Receivers whose JSON is fetched when metrics are scraped
+#[derive(Clone, Default)]
+pub struct ScrapeTargets {
+    targets: Arc<Mutex<BTreeMap<String, Target>>>,
+}
+
+impl ScrapeTargets {
+    pub fn new() -> Self {
+        ScrapeTargets::default()
+    }
+
+    /// Fetch the JSON of the receiver `name` when metrics are scraped
+    pub(crate) fn add(&self, name: &str, target: Target) {
+        self.targets
+            .lock()
+            .unwrap()
+            .insert(name.to_string(), target);
+    }
+
+    /// Stop fetching the JSON of the receiver `name`
+    pub(crate) fn remove(&self, name: &str) {
+        self.targets.lock().unwrap().remove(name);
+    }
+
+    /// Fetch the JSON of every receiver for a scrape
+    pub(crate) async fn refresh(&self) {
+        let targets: Vec<Target> = self.targets.lock().unwrap().values().cloned().collect();
+
+        join_all(targets.iter().map(Target::refresh)).await;
+    }
+}
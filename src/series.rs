@@ -0,0 +1,38 @@
+use prometheus::core::Collector;
+use prometheus::core::MetricVec;
+use prometheus::core::MetricVecBuilder;
+
+use std::collections::HashMap;
+
+/// Remove the series of `metric` that `keep` rejects
+///
+/// Resetting a metric before setting the current series would briefly export nothing to a
+/// concurrent scrape, so series whose labels vanished from the latest data are removed one by one.
+/// `keep` receives the label values of a series by label name.
+pub(crate) fn retain<T, F>(metric: &MetricVec<T>, keep: F)
+where
+    T: MetricVecBuilder,
+    F: Fn(&HashMap<&str, &str>) -> bool,
+{
+    let variable_labels = metric
+        .desc()
+        .into_iter()
+        .flat_map(|desc| desc.variable_labels.clone())
+        .collect::<Vec<String>>();
+
+    for family in metric.collect() {
+        for series in family.get_metric() {
+            // Constant labels are not part of a series' label values
+            let labels: HashMap<&str, &str> = series
+                .get_label()
+                .iter()
+                .filter(|label| variable_labels.iter().any(|name| name == label.get_name()))
+                .map(|label| (label.get_name(), label.get_value()))
+                .collect();
+
+            if !keep(&labels) {
+                metric.remove(&labels).ok();
+            }
+        }
+    }
+}
@@ -0,0 +1,72 @@
+use tokio::sync::mpsc;
+use tokio::sync::watch;
+
+/// Tells tasks holding a Shutdown to stop and waits for them to finish
+pub struct Stopper {
+    stop: watch::Sender<bool>,
+    shutdown: Shutdown,
+    done: mpsc::Receiver<()>,
+}
+
+impl Stopper {
+    pub fn new() -> Self {
+        let (stop, stop_rx) = watch::channel(false);
+        let (done_tx, done) = mpsc::channel(1);
+
+        let shutdown = Shutdown {
+            stop: stop_rx,
+            _done: done_tx,
+        };
+
+        Stopper {
+            stop,
+            shutdown,
+            done,
+        }
+    }
+
+    /// A Shutdown for a task, stop waits until it is dropped
+    pub fn shutdown(&self) -> Shutdown {
+        self.shutdown.clone()
+    }
+
+    /// Ask every task to stop and wait until all of them dropped their Shutdown
+    pub async fn stop(self) {
+        let Stopper {
+            stop,
+            shutdown,
+            mut done,
+        } = self;
+
+        drop(shutdown);
+
+        let _ = stop.send(true);
+
+        // Nothing is sent, recv returns None once every Shutdown is dropped
+        let _ = done.recv().await;
+    }
+}
+
+impl Default for Stopper {
+    fn default() -> Self {
+        Stopper::new()
+    }
+}
+
+/// Held by a task until it has finished stopping
+#[derive(Clone)]
+pub struct Shutdown {
+    stop: watch::Receiver<bool>,
+    _done: mpsc::Sender<()>,
+}
+
+impl Shutdown {
+    /// Completes once a stop is requested, never completes if the Stopper was dropped instead
+    pub async fn requested(&mut self) {
+        while !*self.stop.borrow() {
+            if self.stop.changed().await.is_err() {
+                std::future::pending::<()>().await;
+            }
+        }
+    }
+}
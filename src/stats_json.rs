@@ -1,20 +1,18 @@
-use crate::fetch::fetch;
+use crate::fetch::Fetcher;
 
 use anyhow::Context;
 use anyhow::Result;
 
-use lazy_static::lazy_static;
-
 use log::debug;
 
-use prometheus::register_counter_vec;
-use prometheus::register_gauge_vec;
-use prometheus::register_int_counter_vec;
-use prometheus::CounterVec;
-use prometheus::GaugeVec;
+use prometheus::core::Collector;
+use prometheus::core::Desc;
+use prometheus::proto::MetricFamily;
+use prometheus::Counter;
+use prometheus::Gauge;
+use prometheus::IntCounter;
 use prometheus::IntCounterVec;
-
-use reqwest::Client;
+use prometheus::Opts;
 
 use serde_json::Value;
 
@@ -24,347 +22,462 @@ use std::time::Duration;
 use tokio::time::sleep;
 
 macro_rules! update_counter {
-    ( $metric:ident, $labels:expr, $value:ident, $conversion:ident ) => {
+    ( $metric:expr, $value:ident, $conversion:ident ) => {
         if let Some(value) = $value.$conversion() {
-            let increment = Wrapping(value) - Wrapping($metric.with_label_values($labels).get());
+            let metric = &$metric;
+            let increment = Wrapping(value) - Wrapping(metric.get());
 
-            $metric.with_label_values($labels).inc_by(increment.0);
+            metric.inc_by(increment.0);
         }
     };
 }
 
 macro_rules! set_counter {
-    ( $metric:ident, $labels:expr, $source:ident, $field:literal, $conversion:ident ) => {
+    ( $metric:expr, $source:ident, $field:literal, $conversion:ident ) => {
         if let Some(value) = $source.get($field) {
-            update_counter!($metric, $labels, value, $conversion);
+            update_counter!($metric, value, $conversion);
         }
     };
 }
 
 macro_rules! set_gauge {
-    ( $metric:ident, $labels:expr, $source:ident, $field:literal, $conversion:ident ) => {
+    ( $metric:expr, $source:ident, $field:literal, $conversion:ident ) => {
         if let Some(value) = $source.get($field) {
             if let Some(value) = value.$conversion() {
-                $metric.with_label_values($labels).set(value);
+                $metric.set(value);
             }
         }
     };
 }
 
-lazy_static! {
+/// Metrics exported from stats.json
+#[derive(Clone)]
+pub(crate) struct StatsMetrics {
     // adaptive
-    static ref ADAPTIVE_GAIN: GaugeVec = register_gauge_vec!(
-        "adsb_stats_adaptive_gain_dB",
-        "Current adaptive gain setting",
-        &["frequency"],
-    )
-    .unwrap();
-    static ref ADAPTIVE_GAIN_LIMIT: GaugeVec = register_gauge_vec!(
-        "adsb_stats_adaptive_gain_dynamic_range_limit_dB",
-        "Current dynamic range gain upper limit",
-        &["frequency"],
-    )
-    .unwrap();
-    static ref ADAPTIVE_NOISE_FLOOR: GaugeVec = register_gauge_vec!(
-        "adsb_stats_adaptive_gain_noise_floor_dBFS",
-        "Current dynamic range noise floor",
-        &["frequency"],
-    )
-    .unwrap();
-    static ref ADAPTIVE_GAIN_CHANGES: IntCounterVec = register_int_counter_vec!(
-        "adsb_stats_adaptive_gain_changes_total",
-        "Number of dynamic gain changes",
-        &["frequency"],
-    )
-    .unwrap();
-    static ref ADAPTIVE_UNDECODED: IntCounterVec = register_int_counter_vec!(
-        "adsb_stats_adaptive_loud_undecoded_total",
-        "Number of loud undecoded bursts",
-        &["frequency"],
-    )
-    .unwrap();
-    static ref ADAPTIVE_DECODED: IntCounterVec = register_int_counter_vec!(
-        "adsb_stats_adaptive_loud_decoded_total",
-        "Number of loud decoded messages",
-        &["frequency"],
-    )
-    .unwrap();
-    static ref ADAPTIVE_GAIN_SECONDS: IntCounterVec = register_int_counter_vec!(
-        "adsb_stats_adaptive_gain_seconds_total",
-        "Number of seconds spent in a dB gain level",
-        &["frequency", "gain_dB"],
-    )
-    .unwrap();
+    adaptive_gain: Gauge,
+    adaptive_gain_limit: Gauge,
+    adaptive_noise_floor: Gauge,
+    adaptive_gain_changes: IntCounter,
+    adaptive_undecoded: IntCounter,
+    adaptive_decoded: IntCounter,
+    adaptive_gain_seconds: IntCounterVec,
 
     // CPR
-    static ref CPR_SURFACE: IntCounterVec = register_int_counter_vec!(
-        "adsb_stats_cpr_surface_total",
-        "Number of surface CPR messages received",
-        &["frequency"],
-    )
-    .unwrap();
-    static ref CPR_AIRBORNE: IntCounterVec = register_int_counter_vec!(
-        "adsb_stats_cpr_airborne_total",
-        "Number of airborne CPR messages received",
-        &["frequency"],
-    )
-    .unwrap();
-    static ref CPR_GLOBAL_OK: IntCounterVec = register_int_counter_vec!(
-        "adsb_stats_cpr_global_ok_total",
-        "Number of global positions derived",
-        &["frequency"],
-    )
-    .unwrap();
-    static ref CPR_GLOBAL_BAD: IntCounterVec = register_int_counter_vec!(
-        "adsb_stats_cpr_global_bad_total",
-        "Number of global positions rejected for inconsistency",
-        &["frequency"],
-    )
-    .unwrap();
-    static ref CPR_GLOBAL_RANGE: IntCounterVec = register_int_counter_vec!(
-        "adsb_stats_cpr_global_bad_range_total",
-        "Number of global bad positions exceeding the receiver maximum range",
-        &["frequency"],
-    )
-    .unwrap();
-    static ref CPR_GLOBAL_SPEED: IntCounterVec = register_int_counter_vec!(
-        "adsb_stats_cpr_global_bad_speed_total",
-        "Number of global bad positions exceeding inter-position speed checks",
-        &["frequency"],
-    )
-    .unwrap();
-    static ref CPR_GLOBAL_SKIPPED: IntCounterVec = register_int_counter_vec!(
-        "adsb_stats_cpr_global_skipped_total",
-        "Number of global position attempts skipped due to missing data",
-        &["frequency"],
-    )
-    .unwrap();
-    static ref CPR_LOCAL_OK: IntCounterVec = register_int_counter_vec!(
-        "adsb_stats_cpr_local_ok_total",
-        "Number of local (relative) positions found",
-        &["frequency"],
-    )
-    .unwrap();
-    static ref CPR_LOCAL_AIRCRAFT: IntCounterVec = register_int_counter_vec!(
-        "adsb_stats_cpr_local_aircraft_relative_total",
-        "Number of local positions relative to a previous aircraft position",
-        &["frequency"],
-    )
-    .unwrap();
-    static ref CPR_LOCAL_RECEIVER: IntCounterVec = register_int_counter_vec!(
-        "adsb_stats_cpr_local_receiver_relative_total",
-        "Number of local positions relative to the receiver position",
-        &["frequency"],
-    )
-    .unwrap();
-    static ref CPR_LOCAL_RANGE: IntCounterVec = register_int_counter_vec!(
-        "adsb_stats_cpr_local_bad_range_total",
-        "Number of local bad positions exceeding the receiver maximum range, or with an ambiguous range",
-        &["frequency"],
-    )
-    .unwrap();
-    static ref CPR_LOCAL_SPEED: IntCounterVec = register_int_counter_vec!(
-        "adsb_stats_cpr_local_bad_speed_total",
-        "Number of local bad positions exceeding inter-position speed checks",
-        &["frequency"],
-    )
-    .unwrap();
-    static ref CPR_LOCAL_SKIPPED: IntCounterVec = register_int_counter_vec!(
-        "adsb_stats_cpr_local_skipped_total",
-        "Number of local position attempts skipped due to missing data",
-        &["frequency"],
-    )
-    .unwrap();
-    static ref CPR_FILTERED: IntCounterVec = register_int_counter_vec!(
-        "adsb_stats_cpr_filtered_total",
-        "Number of CPR messages ignored for matching faulty transponder heuristics",
-        &["frequency"],
-    )
-    .unwrap();
+    cpr_surface: IntCounter,
+    cpr_airborne: IntCounter,
+    cpr_global_ok: IntCounter,
+    cpr_global_bad: IntCounter,
+    cpr_global_range: IntCounter,
+    cpr_global_speed: IntCounter,
+    cpr_global_skipped: IntCounter,
+    cpr_local_ok: IntCounter,
+    cpr_local_aircraft: IntCounter,
+    cpr_local_receiver: IntCounter,
+    cpr_local_range: IntCounter,
+    cpr_local_speed: IntCounter,
+    cpr_local_skipped: IntCounter,
+    cpr_filtered: IntCounter,
 
     // cpu
-    static ref CPU_DEMOD: CounterVec = register_counter_vec!(
-        "adsb_stats_cpu_demodulation_seconds_total",
-        "Number CPU seconds spent demodulation and decoding SDR data",
-        &["frequency"],
-    )
-    .unwrap();
-    static ref CPU_READER: CounterVec = register_counter_vec!(
-        "adsb_stats_cpu_reader_seconds_total",
-        "Number CPU seconds spent reading SDR sample data",
-        &["frequency"],
-    )
-    .unwrap();
-    static ref CPU_BACKGROUND: CounterVec = register_counter_vec!(
-        "adsb_stats_cpu_background_seconds_total",
-        "Number CPU seconds spent on network IO and periodic tasks",
-        &["frequency"],
-    )
-    .unwrap();
+    cpu_demod: Counter,
+    cpu_reader: Counter,
+    cpu_background: Counter,
 
     // local
-    static ref LOCAL_SAMPLES_PROCESSED: IntCounterVec = register_int_counter_vec!(
-        "adsb_stats_local_samples_processed_total",
-        "Number of local samples processed",
-        &["frequency"],
-    )
-    .unwrap();
-    static ref LOCAL_SAMPLES_DROPPED: IntCounterVec = register_int_counter_vec!(
-        "adsb_stats_local_samples_dropped_total",
-        "Number of local samples dropped before processing, a nonzero value means CPU overload",
-        &["frequency"],
-    )
-    .unwrap();
-    static ref LOCAL_MODEAC: IntCounterVec = register_int_counter_vec!(
-        "adsb_stats_local_modeac_decoded_total",
-        "Number of local mode A/C messages decoded",
-        &["frequency"],
-    )
-    .unwrap();
-    static ref LOCAL_MODES: IntCounterVec = register_int_counter_vec!(
-        "adsb_stats_local_modes_preambles_total",
-        "Number of local mode S preambles received",
-        &["frequency"],
-    )
-    .unwrap();
-    static ref LOCAL_MODES_BAD: IntCounterVec = register_int_counter_vec!(
-        "adsb_stats_local_modes_bad_total",
-        "Number of local mode S preambles that didn't result in a valid message",
-        &["frequency"],
-    )
-    .unwrap();
-    static ref LOCAL_UNKNOWN_ICAO: IntCounterVec = register_int_counter_vec!(
-        "adsb_stats_local_modes_unknown_icao_total",
-        "Number of local mode S preambles with an unknown ICAO address",
-        &["frequency"],
-    )
-    .unwrap();
-    static ref LOCAL_ACCEPTED: IntCounterVec = register_int_counter_vec!(
-        "adsb_stats_local_modes_accepted_total",
-        "Number of local valid mode S messages labeled with N-bit error corrections",
-        &["frequency", "corrections"],
-    )
-    .unwrap();
-    static ref LOCAL_SIGNAL: GaugeVec = register_gauge_vec!(
-        "adsb_stats_local_signal_dbfs",
-        "Mean signal power of local received messages in dBFS",
-        &["frequency"],
-    )
-    .unwrap();
-    static ref LOCAL_SIGNAL_PEAK: GaugeVec = register_gauge_vec!(
-        "adsb_stats_local_signal_dbfs_peak",
-        "Peak signal power of local received messages in dBFS",
-        &["frequency"],
-    )
-    .unwrap();
-    static ref LOCAL_NOISE: GaugeVec = register_gauge_vec!(
-        "adsb_stats_local_noise_dbfs",
-        "Mean signal noise of local received messages in dBFS",
-        &["frequency"],
-    )
-    .unwrap();
-    static ref LOCAL_STRONG_SIGNALS: IntCounterVec = register_int_counter_vec!(
-        "adsb_stats_local_strong_signals_total",
-        "Number of local messages received with a signal power above -3dBFS",
-        &["frequency", "corrections"],
-    )
-    .unwrap();
+    local_samples_processed: IntCounter,
+    local_samples_dropped: IntCounter,
+    local_modeac: IntCounter,
+    local_modes: IntCounter,
+    local_modes_bad: IntCounter,
+    local_unknown_icao: IntCounter,
+    local_accepted: IntCounterVec,
+    local_signal: Gauge,
+    local_signal_peak: Gauge,
+    local_noise: Gauge,
+    local_strong_signals: IntCounterVec,
 
     // messages
-    static ref MESSAGES: IntCounterVec = register_int_counter_vec!(
-        "adsb_stats_messages_total",
-        "Number of messages received from any source",
-        &["frequency"],
-    )
-    .unwrap();
-    static ref MESSAGES_BY_DF: IntCounterVec = register_int_counter_vec!(
-        "adsb_stats_messages_by_df_total",
-        "Number of messages received per downlink format",
-        &["frequency", "downlink_format"],
-    )
-    .unwrap();
+    messages: IntCounter,
+    messages_by_df: IntCounterVec,
 
     // remote
-    static ref REMOTE_MODEAC: IntCounterVec = register_int_counter_vec!(
-        "adsb_stats_remote_modeac_decoded_total",
-        "Number of remote mode A/C messages decoded",
-        &["frequency"],
-    )
-    .unwrap();
-    static ref REMOTE_MODES: IntCounterVec = register_int_counter_vec!(
-        "adsb_stats_remote_modes_preambles_total",
-        "Number of remote mode S preambles received",
-        &["frequency"],
-    )
-    .unwrap();
-    static ref REMOTE_MODES_BAD: IntCounterVec = register_int_counter_vec!(
-        "adsb_stats_remote_modes_bad_total",
-        "Number of remote mode S preambles that didn't result in a valid message",
-        &["frequency"],
-    )
-    .unwrap();
-    static ref REMOTE_UNKNOWN_ICAO: IntCounterVec = register_int_counter_vec!(
-        "adsb_stats_remote_modes_unknown_icao_total",
-        "Number of remote mode S preambles with an unknown ICAO address",
-        &["frequency"],
-    )
-    .unwrap();
-    static ref REMOTE_ACCEPTED: IntCounterVec = register_int_counter_vec!(
-        "adsb_stats_remote_modes_accepted_total",
-        "Number of valid remote mode S messages labeled by N-bit error corrections",
-        &["frequency", "corrections"],
-    )
-    .unwrap();
+    remote_modeac: IntCounter,
+    remote_modes: IntCounter,
+    remote_modes_bad: IntCounter,
+    remote_unknown_icao: IntCounter,
+    remote_accepted: IntCounterVec,
 
     // tracks
-    static ref TRACKS_ALL: IntCounterVec = register_int_counter_vec!(
-        "adsb_stats_tracks_total",
-        "Number of unique aircraft tracks",
-        &["frequency"],
-    )
-    .unwrap();
-    static ref TRACKS_SINGLE: IntCounterVec = register_int_counter_vec!(
-        "adsb_stats_tracks_single_message_total",
-        "Number of single message aircraft tracks",
-        &["frequency"],
-    )
-    .unwrap();
-    static ref TRACKS_UNRELIABLE: IntCounterVec = register_int_counter_vec!(
-        "adsb_stats_tracks_unreliable_total",
-        "Number of unreliable tracks marked unreliable",
-        &["frequency"],
-    )
-    .unwrap();
+    tracks_all: IntCounter,
+    tracks_single: IntCounter,
+    tracks_unreliable: IntCounter,
+}
+
+impl StatsMetrics {
+    /// Unregistered metrics of the named `receiver` listening on `frequency`
+    pub(crate) fn new(receiver: &str, frequency: &str) -> Result<Self> {
+        Ok(StatsMetrics {
+            // adaptive
+            adaptive_gain: Gauge::with_opts(
+                Opts::new("adsb_stats_adaptive_gain_dB", "Current adaptive gain setting")
+                .const_label("receiver", receiver)
+                .const_label("frequency", frequency),
+            )?,
+            adaptive_gain_limit: Gauge::with_opts(
+                Opts::new("adsb_stats_adaptive_gain_dynamic_range_limit_dB", "Current dynamic range gain upper limit")
+                .const_label("receiver", receiver)
+                .const_label("frequency", frequency),
+            )?,
+            adaptive_noise_floor: Gauge::with_opts(
+                Opts::new("adsb_stats_adaptive_gain_noise_floor_dBFS", "Current dynamic range noise floor")
+                .const_label("receiver", receiver)
+                .const_label("frequency", frequency),
+            )?,
+            adaptive_gain_changes: IntCounter::with_opts(
+                Opts::new("adsb_stats_adaptive_gain_changes_total", "Number of dynamic gain changes")
+                .const_label("receiver", receiver)
+                .const_label("frequency", frequency),
+            )?,
+            adaptive_undecoded: IntCounter::with_opts(
+                Opts::new("adsb_stats_adaptive_loud_undecoded_total", "Number of loud undecoded bursts")
+                .const_label("receiver", receiver)
+                .const_label("frequency", frequency),
+            )?,
+            adaptive_decoded: IntCounter::with_opts(
+                Opts::new("adsb_stats_adaptive_loud_decoded_total", "Number of loud decoded messages")
+                .const_label("receiver", receiver)
+                .const_label("frequency", frequency),
+            )?,
+            adaptive_gain_seconds: IntCounterVec::new(
+                Opts::new("adsb_stats_adaptive_gain_seconds_total", "Number of seconds spent in a dB gain level")
+                .const_label("receiver", receiver)
+                .const_label("frequency", frequency),
+                &["gain_dB"],
+            )?,
+
+            // CPR
+            cpr_surface: IntCounter::with_opts(
+                Opts::new("adsb_stats_cpr_surface_total", "Number of surface CPR messages received")
+                .const_label("receiver", receiver)
+                .const_label("frequency", frequency),
+            )?,
+            cpr_airborne: IntCounter::with_opts(
+                Opts::new("adsb_stats_cpr_airborne_total", "Number of airborne CPR messages received")
+                .const_label("receiver", receiver)
+                .const_label("frequency", frequency),
+            )?,
+            cpr_global_ok: IntCounter::with_opts(
+                Opts::new("adsb_stats_cpr_global_ok_total", "Number of global positions derived")
+                .const_label("receiver", receiver)
+                .const_label("frequency", frequency),
+            )?,
+            cpr_global_bad: IntCounter::with_opts(
+                Opts::new("adsb_stats_cpr_global_bad_total", "Number of global positions rejected for inconsistency")
+                .const_label("receiver", receiver)
+                .const_label("frequency", frequency),
+            )?,
+            cpr_global_range: IntCounter::with_opts(
+                Opts::new("adsb_stats_cpr_global_bad_range_total", "Number of global bad positions exceeding the receiver maximum range")
+                .const_label("receiver", receiver)
+                .const_label("frequency", frequency),
+            )?,
+            cpr_global_speed: IntCounter::with_opts(
+                Opts::new("adsb_stats_cpr_global_bad_speed_total", "Number of global bad positions exceeding inter-position speed checks")
+                .const_label("receiver", receiver)
+                .const_label("frequency", frequency),
+            )?,
+            cpr_global_skipped: IntCounter::with_opts(
+                Opts::new("adsb_stats_cpr_global_skipped_total", "Number of global position attempts skipped due to missing data")
+                .const_label("receiver", receiver)
+                .const_label("frequency", frequency),
+            )?,
+            cpr_local_ok: IntCounter::with_opts(
+                Opts::new("adsb_stats_cpr_local_ok_total", "Number of local (relative) positions found")
+                .const_label("receiver", receiver)
+                .const_label("frequency", frequency),
+            )?,
+            cpr_local_aircraft: IntCounter::with_opts(
+                Opts::new("adsb_stats_cpr_local_aircraft_relative_total", "Number of local positions relative to a previous aircraft position")
+                .const_label("receiver", receiver)
+                .const_label("frequency", frequency),
+            )?,
+            cpr_local_receiver: IntCounter::with_opts(
+                Opts::new("adsb_stats_cpr_local_receiver_relative_total", "Number of local positions relative to the receiver position")
+                .const_label("receiver", receiver)
+                .const_label("frequency", frequency),
+            )?,
+            cpr_local_range: IntCounter::with_opts(
+                Opts::new("adsb_stats_cpr_local_bad_range_total", "Number of local bad positions exceeding the receiver maximum range, or with an ambiguous range")
+                .const_label("receiver", receiver)
+                .const_label("frequency", frequency),
+            )?,
+            cpr_local_speed: IntCounter::with_opts(
+                Opts::new("adsb_stats_cpr_local_bad_speed_total", "Number of local bad positions exceeding inter-position speed checks")
+                .const_label("receiver", receiver)
+                .const_label("frequency", frequency),
+            )?,
+            cpr_local_skipped: IntCounter::with_opts(
+                Opts::new("adsb_stats_cpr_local_skipped_total", "Number of local position attempts skipped due to missing data")
+                .const_label("receiver", receiver)
+                .const_label("frequency", frequency),
+            )?,
+            cpr_filtered: IntCounter::with_opts(
+                Opts::new("adsb_stats_cpr_filtered_total", "Number of CPR messages ignored for matching faulty transponder heuristics")
+                .const_label("receiver", receiver)
+                .const_label("frequency", frequency),
+            )?,
+
+            // cpu
+            cpu_demod: Counter::with_opts(
+                Opts::new("adsb_stats_cpu_demodulation_seconds_total", "Number CPU seconds spent demodulation and decoding SDR data")
+                .const_label("receiver", receiver)
+                .const_label("frequency", frequency),
+            )?,
+            cpu_reader: Counter::with_opts(
+                Opts::new("adsb_stats_cpu_reader_seconds_total", "Number CPU seconds spent reading SDR sample data")
+                .const_label("receiver", receiver)
+                .const_label("frequency", frequency),
+            )?,
+            cpu_background: Counter::with_opts(
+                Opts::new("adsb_stats_cpu_background_seconds_total", "Number CPU seconds spent on network IO and periodic tasks")
+                .const_label("receiver", receiver)
+                .const_label("frequency", frequency),
+            )?,
+
+            // local
+            local_samples_processed: IntCounter::with_opts(
+                Opts::new("adsb_stats_local_samples_processed_total", "Number of local samples processed")
+                .const_label("receiver", receiver)
+                .const_label("frequency", frequency),
+            )?,
+            local_samples_dropped: IntCounter::with_opts(
+                Opts::new("adsb_stats_local_samples_dropped_total", "Number of local samples dropped before processing, a nonzero value means CPU overload")
+                .const_label("receiver", receiver)
+                .const_label("frequency", frequency),
+            )?,
+            local_modeac: IntCounter::with_opts(
+                Opts::new("adsb_stats_local_modeac_decoded_total", "Number of local mode A/C messages decoded")
+                .const_label("receiver", receiver)
+                .const_label("frequency", frequency),
+            )?,
+            local_modes: IntCounter::with_opts(
+                Opts::new("adsb_stats_local_modes_preambles_total", "Number of local mode S preambles received")
+                .const_label("receiver", receiver)
+                .const_label("frequency", frequency),
+            )?,
+            local_modes_bad: IntCounter::with_opts(
+                Opts::new("adsb_stats_local_modes_bad_total", "Number of local mode S preambles that didn't result in a valid message")
+                .const_label("receiver", receiver)
+                .const_label("frequency", frequency),
+            )?,
+            local_unknown_icao: IntCounter::with_opts(
+                Opts::new("adsb_stats_local_modes_unknown_icao_total", "Number of local mode S preambles with an unknown ICAO address")
+                .const_label("receiver", receiver)
+                .const_label("frequency", frequency),
+            )?,
+            local_accepted: IntCounterVec::new(
+                Opts::new("adsb_stats_local_modes_accepted_total", "Number of local valid mode S messages labeled with N-bit error corrections")
+                .const_label("receiver", receiver)
+                .const_label("frequency", frequency),
+                &["corrections"],
+            )?,
+            local_signal: Gauge::with_opts(
+                Opts::new("adsb_stats_local_signal_dbfs", "Mean signal power of local received messages in dBFS")
+                .const_label("receiver", receiver)
+                .const_label("frequency", frequency),
+            )?,
+            local_signal_peak: Gauge::with_opts(
+                Opts::new("adsb_stats_local_signal_dbfs_peak", "Peak signal power of local received messages in dBFS")
+                .const_label("receiver", receiver)
+                .const_label("frequency", frequency),
+            )?,
+            local_noise: Gauge::with_opts(
+                Opts::new("adsb_stats_local_noise_dbfs", "Mean signal noise of local received messages in dBFS")
+                .const_label("receiver", receiver)
+                .const_label("frequency", frequency),
+            )?,
+            local_strong_signals: IntCounterVec::new(
+                Opts::new("adsb_stats_local_strong_signals_total", "Number of local messages received with a signal power above -3dBFS")
+                .const_label("receiver", receiver)
+                .const_label("frequency", frequency),
+                &["corrections"],
+            )?,
+
+            // messages
+            messages: IntCounter::with_opts(
+                Opts::new("adsb_stats_messages_total", "Number of messages received from any source")
+                .const_label("receiver", receiver)
+                .const_label("frequency", frequency),
+            )?,
+            messages_by_df: IntCounterVec::new(
+                Opts::new("adsb_stats_messages_by_df_total", "Number of messages received per downlink format")
+                .const_label("receiver", receiver)
+                .const_label("frequency", frequency),
+                &["downlink_format"],
+            )?,
+
+            // remote
+            remote_modeac: IntCounter::with_opts(
+                Opts::new("adsb_stats_remote_modeac_decoded_total", "Number of remote mode A/C messages decoded")
+                .const_label("receiver", receiver)
+                .const_label("frequency", frequency),
+            )?,
+            remote_modes: IntCounter::with_opts(
+                Opts::new("adsb_stats_remote_modes_preambles_total", "Number of remote mode S preambles received")
+                .const_label("receiver", receiver)
+                .const_label("frequency", frequency),
+            )?,
+            remote_modes_bad: IntCounter::with_opts(
+                Opts::new("adsb_stats_remote_modes_bad_total", "Number of remote mode S preambles that didn't result in a valid message")
+                .const_label("receiver", receiver)
+                .const_label("frequency", frequency),
+            )?,
+            remote_unknown_icao: IntCounter::with_opts(
+                Opts::new("adsb_stats_remote_modes_unknown_icao_total", "Number of remote mode S preambles with an unknown ICAO address")
+                .const_label("receiver", receiver)
+                .const_label("frequency", frequency),
+            )?,
+            remote_accepted: IntCounterVec::new(
+                Opts::new("adsb_stats_remote_modes_accepted_total", "Number of valid remote mode S messages labeled by N-bit error corrections")
+                .const_label("receiver", receiver)
+                .const_label("frequency", frequency),
+                &["corrections"],
+            )?,
+
+            // tracks
+            tracks_all: IntCounter::with_opts(
+                Opts::new("adsb_stats_tracks_total", "Number of unique aircraft tracks")
+                .const_label("receiver", receiver)
+                .const_label("frequency", frequency),
+            )?,
+            tracks_single: IntCounter::with_opts(
+                Opts::new("adsb_stats_tracks_single_message_total", "Number of single message aircraft tracks")
+                .const_label("receiver", receiver)
+                .const_label("frequency", frequency),
+            )?,
+            tracks_unreliable: IntCounter::with_opts(
+                Opts::new("adsb_stats_tracks_unreliable_total", "Number of unreliable tracks marked unreliable")
+                .const_label("receiver", receiver)
+                .const_label("frequency", frequency),
+            )?,
+        })
+    }
+
+    fn collectors(&self) -> Vec<&dyn Collector> {
+        vec![
+            &self.adaptive_gain,
+            &self.adaptive_gain_limit,
+            &self.adaptive_noise_floor,
+            &self.adaptive_gain_changes,
+            &self.adaptive_undecoded,
+            &self.adaptive_decoded,
+            &self.adaptive_gain_seconds,
+            &self.cpr_surface,
+            &self.cpr_airborne,
+            &self.cpr_global_ok,
+            &self.cpr_global_bad,
+            &self.cpr_global_range,
+            &self.cpr_global_speed,
+            &self.cpr_global_skipped,
+            &self.cpr_local_ok,
+            &self.cpr_local_aircraft,
+            &self.cpr_local_receiver,
+            &self.cpr_local_range,
+            &self.cpr_local_speed,
+            &self.cpr_local_skipped,
+            &self.cpr_filtered,
+            &self.cpu_demod,
+            &self.cpu_reader,
+            &self.cpu_background,
+            &self.local_samples_processed,
+            &self.local_samples_dropped,
+            &self.local_modeac,
+            &self.local_modes,
+            &self.local_modes_bad,
+            &self.local_unknown_icao,
+            &self.local_accepted,
+            &self.local_signal,
+            &self.local_signal_peak,
+            &self.local_noise,
+            &self.local_strong_signals,
+            &self.messages,
+            &self.messages_by_df,
+            &self.remote_modeac,
+            &self.remote_modes,
+            &self.remote_modes_bad,
+            &self.remote_unknown_icao,
+            &self.remote_accepted,
+            &self.tracks_all,
+            &self.tracks_single,
+            &self.tracks_unreliable,
+        ]
+    }
 }
 
+impl Collector for StatsMetrics {
+    fn desc(&self) -> Vec<&Desc> {
+        self.collectors()
+            .into_iter()
+            .flat_map(|c| c.desc())
+            .collect()
+    }
+
+    fn collect(&self) -> Vec<MetricFamily> {
+        self.collectors()
+            .into_iter()
+            .flat_map(|c| c.collect())
+            .collect()
+    }
+}
+
+#[derive(Clone)]
 pub struct StatsJson {
-    client: Client,
-    frequency: String,
-    url: String,
+    fetcher: Fetcher,
+    metrics: StatsMetrics,
+    receiver: String,
     interval: Duration,
 }
 
 impl StatsJson {
-    pub fn new(client: Client, frequency: u32, url: String, interval: Duration) -> StatsJson {
+    pub fn new(fetcher: Fetcher, frequency: u32, interval: Duration) -> StatsJson {
+        let receiver = fetcher.receiver().to_string();
         let frequency = frequency.to_string();
+        let metrics =
+            StatsMetrics::new(&receiver, &frequency).expect("Receiver metrics are invalid, bug?");
 
         StatsJson {
-            client,
-            frequency,
-            url,
+            fetcher,
+            metrics,
+            receiver,
             interval,
         }
     }
 
+    pub(crate) fn fetcher(&self) -> &Fetcher {
+        &self.fetcher
+    }
+
+    /// Metrics updated by run(), register them to export them
+    pub(crate) fn metrics(&self) -> &StatsMetrics {
+        &self.metrics
+    }
+
+    /// Update `metrics` instead, like the metrics of the previous watcher of the receiver
+    pub(crate) fn with_metrics(self, metrics: StatsMetrics) -> Self {
+        StatsJson { metrics, ..self }
+    }
+
     pub async fn run(&self) {
         debug!(
-            "Watching stats dump{} at {} every {:?}",
-            self.frequency, self.url, self.interval
+            "Watching stats for {} at {} every {:?}",
+            self.receiver,
+            self.fetcher.url(),
+            self.interval
         );
 
         loop {
-            if let Some(data) = fetch(&self.client, &self.url).await {
-                match self.update_stats(data) {
+            if let Some(data) = self.fetcher.fetch().await {
+                match self.update_stats(&self.metrics, data) {
                     Ok(_) => (),
                     Err(e) => {
                         debug!("error updating stats {:?}", e);
@@ -376,11 +489,11 @@ impl StatsJson {
         }
     }
 
-    fn update_stats(&self, data: Value) -> Result<()> {
+    pub(crate) fn update_stats(&self, metrics: &StatsMetrics, data: Value) -> Result<()> {
         let total = data.get("total").context("missing total data")?;
 
         // .total
-        set_counter!(MESSAGES, &[&self.frequency], total, "messages", as_u64);
+        set_counter!(metrics.messages, total, "messages", as_u64);
 
         if let Some(messages_by_df) = total.get("messages_by_df") {
             if let Some(messages_by_df) = messages_by_df.as_array() {
@@ -389,8 +502,9 @@ impl StatsJson {
                     .enumerate()
                     .for_each(|(format, count)| {
                         update_counter!(
-                            MESSAGES_BY_DF,
-                            &[&self.frequency, &format.to_string()],
+                            metrics
+                                .messages_by_df
+                                .with_label_values(&[&format.to_string()]),
                             count,
                             as_u64
                         );
@@ -403,49 +517,28 @@ impl StatsJson {
             .get("adaptive")
             .context("Missing adaptive data in \"total\" object")?;
 
+        set_counter!(metrics.adaptive_decoded, adaptive, "loud_decoded", as_u64);
         set_counter!(
-            ADAPTIVE_DECODED,
-            &[&self.frequency],
-            adaptive,
-            "loud_decoded",
-            as_u64
-        );
-        set_counter!(
-            ADAPTIVE_GAIN_CHANGES,
-            &[&self.frequency],
+            metrics.adaptive_gain_changes,
             adaptive,
             "gain_changes",
             as_u64
         );
         set_counter!(
-            ADAPTIVE_UNDECODED,
-            &[&self.frequency],
+            metrics.adaptive_undecoded,
             adaptive,
             "loud_undecoded",
             as_u64
         );
 
+        set_gauge!(metrics.adaptive_gain, adaptive, "gain_db", as_f64);
         set_gauge!(
-            ADAPTIVE_GAIN,
-            &[&self.frequency],
-            adaptive,
-            "gain_db",
-            as_f64
-        );
-        set_gauge!(
-            ADAPTIVE_GAIN_LIMIT,
-            &[&self.frequency],
+            metrics.adaptive_gain_limit,
             adaptive,
             "dynamic_range_limit_db",
             as_f64
         );
-        set_gauge!(
-            ADAPTIVE_NOISE_FLOOR,
-            &[&self.frequency],
-            adaptive,
-            "noise_dbfs",
-            as_f64
-        );
+        set_gauge!(metrics.adaptive_noise_floor, adaptive, "noise_dbfs", as_f64);
 
         if let Some(gain_seconds) = adaptive.get("gain_seconds") {
             if let Some(gain_seconds) = gain_seconds.as_array() {
@@ -455,8 +548,9 @@ impl StatsJson {
                             let seconds = &pair[1];
 
                             update_counter!(
-                                ADAPTIVE_GAIN_SECONDS,
-                                &[&self.frequency, &gain.to_string()],
+                                metrics
+                                    .adaptive_gain_seconds
+                                    .with_label_values(&[&gain.to_string()]),
                                 seconds,
                                 as_u64
                             );
@@ -471,74 +565,30 @@ impl StatsJson {
             .get("cpr")
             .context("Missing cpr data in \"total\" object")?;
 
-        set_counter!(CPR_AIRBORNE, &[&self.frequency], cpr, "airborne", as_u64);
-        set_counter!(CPR_FILTERED, &[&self.frequency], cpr, "filtered", as_u64);
-        set_counter!(
-            CPR_GLOBAL_BAD,
-            &[&self.frequency],
-            cpr,
-            "global_bad",
-            as_u64
-        );
-        set_counter!(CPR_GLOBAL_OK, &[&self.frequency], cpr, "global_ok", as_u64);
-        set_counter!(
-            CPR_GLOBAL_RANGE,
-            &[&self.frequency],
-            cpr,
-            "global_range",
-            as_u64
-        );
+        set_counter!(metrics.cpr_airborne, cpr, "airborne", as_u64);
+        set_counter!(metrics.cpr_filtered, cpr, "filtered", as_u64);
+        set_counter!(metrics.cpr_global_bad, cpr, "global_bad", as_u64);
+        set_counter!(metrics.cpr_global_ok, cpr, "global_ok", as_u64);
+        set_counter!(metrics.cpr_global_range, cpr, "global_range", as_u64);
+        set_counter!(metrics.cpr_global_skipped, cpr, "global_bad", as_u64);
+        set_counter!(metrics.cpr_global_speed, cpr, "global_speed", as_u64);
         set_counter!(
-            CPR_GLOBAL_SKIPPED,
-            &[&self.frequency],
-            cpr,
-            "global_bad",
-            as_u64
-        );
-        set_counter!(
-            CPR_GLOBAL_SPEED,
-            &[&self.frequency],
-            cpr,
-            "global_speed",
-            as_u64
-        );
-        set_counter!(
-            CPR_LOCAL_AIRCRAFT,
-            &[&self.frequency],
+            metrics.cpr_local_aircraft,
             cpr,
             "local_aircraft_relative",
             as_u64
         );
-        set_counter!(CPR_LOCAL_OK, &[&self.frequency], cpr, "local_ok", as_u64);
+        set_counter!(metrics.cpr_local_ok, cpr, "local_ok", as_u64);
+        set_counter!(metrics.cpr_local_range, cpr, "local_range", as_u64);
         set_counter!(
-            CPR_LOCAL_RANGE,
-            &[&self.frequency],
-            cpr,
-            "local_range",
-            as_u64
-        );
-        set_counter!(
-            CPR_LOCAL_RECEIVER,
-            &[&self.frequency],
+            metrics.cpr_local_receiver,
             cpr,
             "local_receiver_relative",
             as_u64
         );
-        set_counter!(
-            CPR_LOCAL_SKIPPED,
-            &[&self.frequency],
-            cpr,
-            "local_skipped",
-            as_u64
-        );
-        set_counter!(
-            CPR_LOCAL_SPEED,
-            &[&self.frequency],
-            cpr,
-            "local_speed",
-            as_u64
-        );
-        set_counter!(CPR_SURFACE, &[&self.frequency], cpr, "surface", as_u64);
+        set_counter!(metrics.cpr_local_skipped, cpr, "local_skipped", as_u64);
+        set_counter!(metrics.cpr_local_speed, cpr, "local_speed", as_u64);
+        set_counter!(metrics.cpr_surface, cpr, "surface", as_u64);
 
         // .total.cpu
         let cpu = total
@@ -549,11 +599,9 @@ impl StatsJson {
             if let Some(value) = value.as_f64() {
                 let value = value / 1000.0; // convert to seconds
 
-                let increment = value - CPU_DEMOD.with_label_values(&[&self.frequency]).get();
+                let increment = value - metrics.cpu_demod.get();
 
-                CPU_DEMOD
-                    .with_label_values(&[&self.frequency])
-                    .inc_by(increment);
+                metrics.cpu_demod.inc_by(increment);
             }
         }
 
@@ -561,11 +609,9 @@ impl StatsJson {
             if let Some(value) = value.as_f64() {
                 let value = value / 1000.0; // convert to seconds
 
-                let increment = value - CPU_READER.with_label_values(&[&self.frequency]).get();
+                let increment = value - metrics.cpu_reader.get();
 
-                CPU_READER
-                    .with_label_values(&[&self.frequency])
-                    .inc_by(increment);
+                metrics.cpu_reader.inc_by(increment);
             }
         }
 
@@ -573,11 +619,9 @@ impl StatsJson {
             if let Some(value) = value.as_f64() {
                 let value = value / 1000.0; // convert to seconds
 
-                let increment = value - CPU_BACKGROUND.with_label_values(&[&self.frequency]).get();
+                let increment = value - metrics.cpu_background.get();
 
-                CPU_BACKGROUND
-                    .with_label_values(&[&self.frequency])
-                    .inc_by(increment);
+                metrics.cpu_background.inc_by(increment);
             }
         }
 
@@ -587,29 +631,21 @@ impl StatsJson {
             .context("Missing local data in \"total\" object")?;
 
         set_counter!(
-            LOCAL_SAMPLES_PROCESSED,
-            &[&self.frequency],
+            metrics.local_samples_processed,
             local,
             "samples_processed",
             as_u64
         );
         set_counter!(
-            LOCAL_SAMPLES_DROPPED,
-            &[&self.frequency],
+            metrics.local_samples_dropped,
             local,
             "samples_dropped",
             as_u64
         );
-        set_counter!(LOCAL_MODEAC, &[&self.frequency], local, "modeac", as_u64);
-        set_counter!(LOCAL_MODES, &[&self.frequency], local, "modes", as_u64);
-        set_counter!(LOCAL_MODES_BAD, &[&self.frequency], local, "bad", as_u64);
-        set_counter!(
-            LOCAL_UNKNOWN_ICAO,
-            &[&self.frequency],
-            local,
-            "unknown_icao",
-            as_u64
-        );
+        set_counter!(metrics.local_modeac, local, "modeac", as_u64);
+        set_counter!(metrics.local_modes, local, "modes", as_u64);
+        set_counter!(metrics.local_modes_bad, local, "bad", as_u64);
+        set_counter!(metrics.local_unknown_icao, local, "unknown_icao", as_u64);
 
         if let Some(accepted) = local.get("accepted") {
             if let Some(accepted) = accepted.as_array() {
@@ -618,8 +654,9 @@ impl StatsJson {
                     .enumerate()
                     .for_each(|(corrections, count)| {
                         update_counter!(
-                            LOCAL_ACCEPTED,
-                            &[&self.frequency, &corrections.to_string()],
+                            metrics
+                                .local_accepted
+                                .with_label_values(&[&corrections.to_string()]),
                             count,
                             as_u64
                         );
@@ -632,16 +669,10 @@ impl StatsJson {
             .get("remote")
             .context("Missing remote data in \"total\" object")?;
 
-        set_counter!(REMOTE_MODEAC, &[&self.frequency], remote, "modeac", as_u64);
-        set_counter!(REMOTE_MODES, &[&self.frequency], remote, "modes", as_u64);
-        set_counter!(REMOTE_MODES_BAD, &[&self.frequency], remote, "bad", as_u64);
-        set_counter!(
-            REMOTE_UNKNOWN_ICAO,
-            &[&self.frequency],
-            remote,
-            "unknown_icao",
-            as_u64
-        );
+        set_counter!(metrics.remote_modeac, remote, "modeac", as_u64);
+        set_counter!(metrics.remote_modes, remote, "modes", as_u64);
+        set_counter!(metrics.remote_modes_bad, remote, "bad", as_u64);
+        set_counter!(metrics.remote_unknown_icao, remote, "unknown_icao", as_u64);
 
         if let Some(accepted) = remote.get("accepted") {
             if let Some(accepted) = accepted.as_array() {
@@ -650,8 +681,9 @@ impl StatsJson {
                     .enumerate()
                     .for_each(|(corrections, count)| {
                         update_counter!(
-                            REMOTE_ACCEPTED,
-                            &[&self.frequency, &corrections.to_string()],
+                            metrics
+                                .remote_accepted
+                                .with_label_values(&[&corrections.to_string()]),
                             count,
                             as_u64
                         );
@@ -664,21 +696,9 @@ impl StatsJson {
             .get("tracks")
             .context("Missing tracks data in \"total\" object")?;
 
-        set_counter!(TRACKS_ALL, &[&self.frequency], tracks, "all", as_u64);
-        set_counter!(
-            TRACKS_SINGLE,
-            &[&self.frequency],
-            tracks,
-            "single_message",
-            as_u64
-        );
-        set_counter!(
-            TRACKS_UNRELIABLE,
-            &[&self.frequency],
-            tracks,
-            "unreliable",
-            as_u64
-        );
+        set_counter!(metrics.tracks_all, tracks, "all", as_u64);
+        set_counter!(metrics.tracks_single, tracks, "single_message", as_u64);
+        set_counter!(metrics.tracks_unreliable, tracks, "unreliable", as_u64);
 
         //
         let last1min = data.get("last1min").context("missing last1min data")?;
@@ -687,15 +707,9 @@ impl StatsJson {
             .get("local")
             .context("Missing local data in \"last1min\" object")?;
 
-        set_gauge!(LOCAL_SIGNAL, &[&self.frequency], local, "signal", as_f64);
-        set_gauge!(
-            LOCAL_SIGNAL_PEAK,
-            &[&self.frequency],
-            local,
-            "peak_signal",
-            as_f64
-        );
-        set_gauge!(LOCAL_NOISE, &[&self.frequency], local, "noise", as_f64);
+        set_gauge!(metrics.local_signal, local, "signal", as_f64);
+        set_gauge!(metrics.local_signal_peak, local, "peak_signal", as_f64);
+        set_gauge!(metrics.local_noise, local, "noise", as_f64);
 
         Ok(())
     }
@@ -0,0 +1,189 @@
+use crate::adsb_exporter::ErrorSender;
+
+use anyhow::anyhow;
+use anyhow::Result;
+
+use log::error;
+use log::info;
+
+use prometheus::IntCounterVec;
+use prometheus::IntGaugeVec;
+use prometheus::Opts;
+use prometheus::Registry;
+
+use std::future::Future;
+use std::time::Duration;
+
+use tokio::task::JoinError;
+use tokio::task::JoinHandle;
+use tokio::time::sleep;
+use tokio::time::Instant;
+
+/// Delay before the first restart of a failed task, doubled for each following failure
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+
+const MAXIMUM_BACKOFF: Duration = Duration::from_secs(60);
+
+/// A task that runs this long before failing starts over with the initial backoff
+const STABLE_RUNTIME: Duration = Duration::from_secs(300);
+
+/// Consecutive failures after which a task is abandoned
+const MAXIMUM_FAILURES: u32 = 10;
+
+/// Up and restart metrics of supervised tasks
+#[derive(Clone)]
+pub(crate) struct TaskMetrics {
+    up: IntGaugeVec,
+    restarts: IntCounterVec,
+}
+
+impl TaskMetrics {
+    /// Metrics registered in `registry`
+    pub(crate) fn new(registry: &Registry) -> Result<Self> {
+        let up = IntGaugeVec::new(
+            Opts::new("adsb_task_up", "Whether a supervised task is running"),
+            &["task"],
+        )?;
+        let restarts = IntCounterVec::new(
+            Opts::new(
+                "adsb_task_restarts_total",
+                "Number of times a supervised task was restarted after failing",
+            ),
+            &["task"],
+        )?;
+
+        registry.register(Box::new(up.clone()))?;
+        registry.register(Box::new(restarts.clone()))?;
+
+        Ok(TaskMetrics { up, restarts })
+    }
+}
+
+/// A task restarted by its supervisor when it panics or stops
+pub(crate) struct Supervised {
+    name: String,
+    metrics: TaskMetrics,
+    supervisor: JoinHandle<()>,
+}
+
+impl Supervised {
+    /// Stop the task and remove its metrics
+    pub(crate) fn stop(self) {
+        self.supervisor.abort();
+
+        let _ = self.metrics.up.remove_label_values(&[&self.name]);
+        let _ = self.metrics.restarts.remove_label_values(&[&self.name]);
+    }
+}
+
+/// Aborts a task when dropped so aborting the supervisor stops the task too
+struct AbortOnDrop(JoinHandle<()>);
+
+impl Drop for AbortOnDrop {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+/// Run the future made by `task` as `name`, restarting it with backoff when it panics or stops
+///
+/// After MAXIMUM_FAILURES failures in a row the task is abandoned, it stays down in adsb_task_up
+/// and the last failure is sent to `error_tx` as a fatal error.
+pub(crate) fn supervise<T, F>(
+    name: String,
+    metrics: &TaskMetrics,
+    error_tx: ErrorSender,
+    task: T,
+) -> Supervised
+where
+    T: Fn() -> F + Send + 'static,
+    F: Future<Output = ()> + Send + 'static,
+{
+    let task_name = name.clone();
+    let task_metrics = metrics.clone();
+
+    let supervisor = crate::spawn_named(
+        async move {
+            run(task_name, task_metrics, error_tx, task).await;
+        },
+        &format!("supervisor::{}", name),
+    );
+
+    Supervised {
+        name,
+        metrics: metrics.clone(),
+        supervisor,
+    }
+}
+
+async fn run<T, F>(name: String, metrics: TaskMetrics, error_tx: ErrorSender, task: T)
+where
+    T: Fn() -> F,
+    F: Future<Output = ()> + Send + 'static,
+{
+    let up = metrics.up.with_label_values(&[&name]);
+    let restarts = metrics.restarts.with_label_values(&[&name]);
+
+    let mut backoff = INITIAL_BACKOFF;
+    let mut failures = 0;
+
+    loop {
+        let started = Instant::now();
+        up.set(1);
+
+        let mut child = AbortOnDrop(crate::spawn_named(task(), &name));
+        let result = (&mut child.0).await;
+
+        up.set(0);
+
+        let failure = match result {
+            Ok(()) => anyhow!("{} stopped unexpectedly", name),
+            Err(e) => anyhow!("{} panicked: {}", name, panic_message(e)),
+        };
+
+        if started.elapsed() >= STABLE_RUNTIME {
+            backoff = INITIAL_BACKOFF;
+            failures = 0;
+        }
+
+        failures += 1;
+
+        if failures >= MAXIMUM_FAILURES {
+            let error = failure.context(format!(
+                "giving up on {} after {} failures in a row",
+                name, failures
+            ));
+
+            // The exporter may already be shutting down
+            let _ = error_tx.send(error).await;
+
+            return;
+        }
+
+        error!("{:#}, restarting in {:?}", failure, backoff);
+
+        sleep(backoff).await;
+
+        info!("Restarting {}", name);
+        restarts.inc();
+
+        backoff = (backoff * 2).min(MAXIMUM_BACKOFF);
+    }
+}
+
+/// The message a task panicked with
+pub(crate) fn panic_message(error: JoinError) -> String {
+    if !error.is_panic() {
+        return error.to_string();
+    }
+
+    let panic = error.into_panic();
+
+    if let Some(message) = panic.downcast_ref::<String>() {
+        message.clone()
+    } else if let Some(message) = panic.downcast_ref::<&str>() {
+        message.to_string()
+    } else {
+        "unknown panic".to_string()
+    }
+}
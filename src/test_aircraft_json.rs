@@ -0,0 +1,143 @@
+use crate::aircraft_json::*;
+use crate::range::RangeBuckets;
+use crate::test_helpers::fetcher;
+use crate::test_helpers::label_values;
+
+use geo::Coordinate;
+
+use prometheus::core::Collector;
+
+use serde_json::json;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::sync::RwLock;
+
+fn aircraft_json() -> AircraftJson {
+    aircraft_json_recent(Duration::from_secs(60))
+}
+
+fn aircraft_json_recent(recent: Duration) -> AircraftJson {
+    let position = Coordinate { x: -122.3, y: 47.6 };
+
+    AircraftJson::new(
+        fetcher("aircraft.json"),
+        1090,
+        Duration::from_secs(30),
+        Arc::new(RwLock::new(Some(position))),
+        recent,
+        RangeBuckets::default(),
+    )
+}
+
+#[tokio::test]
+async fn test_update_aircraft_removes_vanished_buckets() {
+    let aircraft_json = aircraft_json();
+    let metrics = AircraftMetrics::new("roof", "1090").unwrap();
+
+    let north =
+        json!({ "hex": "a1b2c3", "seen": 1.0, "seen_pos": 1.0, "lat": 47.9, "lon": -122.3 });
+    let east = json!({ "hex": "a1b2c4", "seen": 1.0, "seen_pos": 1.0, "lat": 47.6, "lon": -121.0 });
+
+    aircraft_json
+        .update_aircraft(&metrics, json!({ "aircraft": [north, east] }))
+        .await
+        .unwrap();
+
+    assert_eq!(
+        vec!["0", "90"],
+        label_values(&metrics.collect(), "adsb_aircraft_ranges_recent", "bearing")
+    );
+    assert_eq!(
+        vec!["0", "90"],
+        label_values(
+            &metrics.collect(),
+            "adsb_aircraft_observations_recent",
+            "bearing"
+        )
+    );
+
+    aircraft_json
+        .update_aircraft(&metrics, json!({ "aircraft": [north] }))
+        .await
+        .unwrap();
+
+    assert_eq!(
+        vec!["0"],
+        label_values(&metrics.collect(), "adsb_aircraft_ranges_recent", "bearing")
+    );
+    assert_eq!(
+        vec!["0"],
+        label_values(
+            &metrics.collect(),
+            "adsb_aircraft_observations_recent",
+            "bearing"
+        )
+    );
+
+    aircraft_json
+        .update_aircraft(&metrics, json!({ "aircraft": [] }))
+        .await
+        .unwrap();
+
+    assert!(label_values(&metrics.collect(), "adsb_aircraft_ranges_recent", "bearing").is_empty());
+}
+
+#[tokio::test]
+async fn test_update_aircraft_fixture() {
+    let aircraft_json = aircraft_json();
+    let metrics = AircraftMetrics::new("roof", "1090").unwrap();
+
+    let data = serde_json::from_str(include_str!("fixtures/dump1090/aircraft.json")).unwrap();
+
+    aircraft_json.update_aircraft(&metrics, data).await.unwrap();
+
+    let value = |name: &str| {
+        metrics
+            .collect()
+            .iter()
+            .find(|f| f.get_name() == name)
+            .and_then(|f| f.get_metric().first().map(|m| m.get_gauge().get_value()))
+    };
+
+    // The aircraft last seen 95 seconds ago is not recent
+    assert_eq!(Some(3.0), value("adsb_aircraft_observed_recent"));
+    assert_eq!(Some(2.0), value("adsb_aircraft_with_position_recent"));
+    assert_eq!(Some(1.0), value("adsb_aircraft_mlat_recent"));
+
+    assert_eq!(
+        vec!["0", "90"],
+        label_values(&metrics.collect(), "adsb_aircraft_ranges_recent", "bearing")
+    );
+    assert_eq!(
+        vec!["roof", "roof"],
+        label_values(
+            &metrics.collect(),
+            "adsb_aircraft_ranges_recent",
+            "receiver"
+        )
+    );
+}
+
+#[tokio::test]
+async fn test_update_aircraft_recent_window() {
+    let aircraft_json = aircraft_json_recent(Duration::from_secs(150));
+    let metrics = AircraftMetrics::new("roof", "1090").unwrap();
+
+    let data = serde_json::from_str(include_str!("fixtures/dump1090/aircraft.json")).unwrap();
+
+    aircraft_json.update_aircraft(&metrics, data).await.unwrap();
+
+    let value = |name: &str| {
+        metrics
+            .collect()
+            .iter()
+            .find(|f| f.get_name() == name)
+            .and_then(|f| f.get_metric().first().map(|m| m.get_gauge().get_value()))
+    };
+
+    // The aircraft last seen 95 seconds ago at its position 120 seconds ago is now recent
+    assert_eq!(Some(4.0), value("adsb_aircraft_observed_recent"));
+    assert_eq!(Some(3.0), value("adsb_aircraft_with_position_recent"));
+}
@@ -0,0 +1,46 @@
+use crate::beast_watcher::BeastWatcher;
+use crate::configuration::Configuration;
+use crate::health::Health;
+use crate::sbs_watcher::SbsWatcher;
+
+use clap::IntoApp;
+
+use prometheus::Registry;
+
+use std::time::Duration;
+
+fn configuration() -> Configuration {
+    let matches = Configuration::into_app()
+        .try_get_matches_from([
+            "adsb_exporter",
+            "--beast-address",
+            "127.0.0.1:30005",
+            "--sbs-address",
+            "127.0.0.1:30003",
+        ])
+        .unwrap();
+
+    Configuration::from_matches(&matches).unwrap()
+}
+
+#[test]
+fn test_new_registers_metrics() {
+    let configuration = configuration();
+    let registry = Registry::new();
+    let health = Health::new(Duration::from_secs(60));
+
+    BeastWatcher::new(&configuration, registry.clone(), health.clone()).unwrap();
+    SbsWatcher::new(&configuration, registry.clone(), health.clone()).unwrap();
+
+    // Metrics are only registered in the given registry
+    assert!(prometheus::default_registry().gather().is_empty());
+
+    // A second watcher can't share the registry
+    let error = BeastWatcher::new(&configuration, registry.clone(), health.clone())
+        .err()
+        .unwrap();
+
+    assert!(error.to_string().contains("Duplicate metrics collector"));
+
+    assert!(SbsWatcher::new(&configuration, registry, health).is_err());
+}
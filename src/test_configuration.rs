@@ -0,0 +1,279 @@
+use crate::configuration::*;
+use crate::range::DistanceUnit;
+use crate::range::RangeBuckets;
+
+use anyhow::Result;
+
+use clap::FromArgMatches;
+use clap::IntoApp;
+
+use std::time::Duration;
+
+fn load(args: &[&str], file: &str) -> Result<Configuration> {
+    let matches = Configuration::into_app()
+        .try_get_matches_from(std::iter::once("adsb_exporter").chain(args.iter().copied()))?;
+
+    Configuration::from_arg_matches(&matches)?.merge(file.parse()?, &matches)
+}
+
+fn error(args: &[&str], file: &str) -> String {
+    format!("{:#}", load(args, file).err().unwrap())
+}
+
+#[test]
+fn test_file() {
+    let configuration = load(
+        &[],
+        r#"
+        bind_address = "127.0.0.1:9999"
+        aircraft_refresh_interval = 15
+        refresh_timeout = 500
+        recent_window = 90
+        latitude = 47.0
+        longitude = -122.0
+        beast_output_address = ["0.0.0.0:30015?df=17"]
+
+        [range]
+        distance = 10000
+
+        [[receivers]]
+        name = "roof"
+        url = "http://roof.example:8080"
+        stats_refresh_interval = 10
+        labels = { site = "home" }
+
+        [[receivers]]
+        name = "uat"
+        url = "http://uat.example"
+        frequency = 978
+        recent_window = 120
+        range = { distance = 50, maximum_distance = 300, unit = "nautical_miles", bearing = 10.0 }
+        "#,
+    )
+    .unwrap();
+
+    assert_eq!("127.0.0.1:9999", configuration.bind_address.to_string());
+    assert_eq!(Some(47.0), configuration.latitude);
+    assert_eq!(1, configuration.beast_output_address.len());
+    assert_eq!(10_000, configuration.range.distance);
+    assert_eq!(2, configuration.receivers.len());
+
+    let roof = &configuration.receivers[0];
+
+    assert_eq!("roof", roof.name);
+    assert_eq!(1090, roof.frequency);
+    assert_eq!(Duration::from_secs(15), roof.aircraft_refresh_interval);
+    assert_eq!(Duration::from_secs(300), roof.receiver_refresh_interval);
+    assert_eq!(Duration::from_secs(10), roof.stats_refresh_interval);
+    assert_eq!(Duration::from_secs(90), roof.recent_window);
+    assert_eq!(Duration::from_millis(500), roof.refresh_timeout);
+    assert_eq!(Some(&"home".to_string()), roof.labels.get("site"));
+    assert_eq!(configuration.range, roof.range);
+
+    let uat = &configuration.receivers[1];
+
+    assert_eq!(978, uat.frequency);
+    assert_eq!(Duration::from_secs(120), uat.recent_window);
+    assert_eq!(
+        RangeBuckets {
+            distance: 50,
+            maximum_distance: 300,
+            unit: DistanceUnit::NauticalMiles,
+            bearing: 10.0,
+        },
+        uat.range
+    );
+}
+
+#[test]
+fn test_command_line_overrides_file() {
+    let configuration = load(
+        &[
+            "--aircraft-refresh-interval",
+            "5",
+            "--dump1090-url",
+            "http://cli.example",
+            "--sbs-address",
+            "cli.example:30003",
+        ],
+        r#"
+        aircraft_refresh_interval = 15
+        receiver_refresh_interval = 600
+        dump1090_url = "http://file.example"
+        sbs_address = ["file.example:30003"]
+
+        [[receivers]]
+        name = "roof"
+        url = "http://roof.example"
+        aircraft_refresh_interval = 20
+        receiver_refresh_interval = 900
+        "#,
+    )
+    .unwrap();
+
+    assert_eq!(vec!["cli.example:30003"], configuration.sbs_address);
+
+    let dump1090 = &configuration.receivers[0];
+
+    assert_eq!("dump1090", dump1090.name);
+    assert_eq!("http://cli.example", dump1090.url);
+    assert_eq!(Duration::from_secs(5), dump1090.aircraft_refresh_interval);
+    assert_eq!(Duration::from_secs(600), dump1090.receiver_refresh_interval);
+
+    let roof = &configuration.receivers[1];
+
+    assert_eq!(Duration::from_secs(5), roof.aircraft_refresh_interval);
+    assert_eq!(Duration::from_secs(900), roof.receiver_refresh_interval);
+}
+
+#[test]
+fn test_defaults() {
+    let configuration = load(&[], "").unwrap();
+
+    assert!(configuration.receivers.is_empty());
+    assert_eq!(RangeBuckets::default(), configuration.range);
+    assert_eq!(
+        Duration::from_secs(30),
+        configuration.aircraft_refresh_interval
+    );
+    assert_eq!(Duration::from_secs(60), configuration.recent_window);
+}
+
+#[test]
+fn test_restart_required() {
+    let file = r#"
+        [[receivers]]
+        name = "roof"
+        url = "http://roof.example:8080"
+        "#;
+
+    let configuration = load(&["--sbs-address", "localhost:30003"], file).unwrap();
+
+    let reloaded = load(
+        &["--sbs-address", "localhost:30003"],
+        r#"
+        readiness_window = 300
+
+        [[receivers]]
+        name = "roof"
+        url = "http://moved.example:8080"
+        "#,
+    )
+    .unwrap();
+
+    // Receivers are reloaded
+    assert_eq!(
+        vec!["readiness_window"],
+        configuration.restart_required(&reloaded)
+    );
+
+    let reloaded = load(&["--sbs-address", "localhost:30004"], file).unwrap();
+
+    assert_eq!(
+        vec!["sbs_address"],
+        configuration.restart_required(&reloaded)
+    );
+}
+
+#[test]
+fn test_errors() {
+    assert!(error(&[], "bogus = 1").contains("unknown field `bogus`"));
+
+    assert_eq!(
+        "beast_output_address[1]: invalid listen address nowhere",
+        error(
+            &[],
+            r#"beast_output_address = ["0.0.0.0:30005", "nowhere"]"#
+        )
+    );
+
+    assert_eq!(
+        "latitude and longitude must be set together",
+        error(&[], "latitude = 47.0")
+    );
+
+    assert_eq!(
+        "aircraft_refresh_interval must be greater than 0",
+        error(&[], "aircraft_refresh_interval = 0")
+    );
+
+    assert_eq!(
+        "range: maximum_distance 1000 must be at least distance 80000",
+        error(&[], "[range]\nmaximum_distance = 1000")
+    );
+
+    assert!(error(&[], "[range]\nunit = \"furlongs\"").contains("unknown variant `furlongs`"));
+
+    assert_eq!(
+        "recent_window must be greater than 0",
+        error(&[], "recent_window = 0")
+    );
+
+    assert_eq!(
+        "readiness_window must be greater than 0",
+        error(&[], "readiness_window = 0")
+    );
+
+    assert_eq!(
+        "readiness_window 60s must be at least the stats_refresh_interval 90s of receiver \"dump1090\"",
+        error(
+            &["--dump1090-url", "http://dump1090.example"],
+            "readiness_window = 60\nstats_refresh_interval = 90"
+        )
+    );
+
+    let receivers = r#"
+        [[receivers]]
+        name = "roof"
+        url = "http://roof.example"
+
+        [[receivers]]
+        name = "garage"
+    "#;
+
+    assert_eq!(
+        "receivers[1]: \"garage\": frequency 1091 must be 1090 or 978",
+        error(
+            &[],
+            &format!(
+                "{}url = \"http://garage.example\"\nfrequency = 1091",
+                receivers
+            )
+        )
+    );
+
+    assert_eq!(
+        "receivers[1]: name \"roof\" is already used by another receiver",
+        error(
+            &[],
+            &receivers.replace("garage", "roof\"\nurl = \"http://x")
+        )
+    );
+
+    assert_eq!(
+        "receivers[1]: \"garage\": URL ftp://garage.example must be http or https, not ftp",
+        error(&[], &format!("{}url = \"ftp://garage.example\"", receivers))
+    );
+
+    assert_eq!(
+        "receivers[1]: \"garage\": labels: \"frequency\" is already used by exported metrics",
+        error(
+            &[],
+            &format!(
+                "{}url = \"http://garage.example\"\nlabels = {{ frequency = \"x\" }}",
+                receivers
+            )
+        )
+    );
+
+    assert_eq!(
+        "receivers[1]: \"garage\": labels: \"1st\" is not a valid label name",
+        error(
+            &[],
+            &format!(
+                "{}url = \"http://garage.example\"\nlabels = {{ 1st = \"x\" }}",
+                receivers
+            )
+        )
+    );
+}
@@ -0,0 +1,369 @@
+use crate::adsb_exporter::ErrorSender;
+use crate::configuration::Receiver;
+use crate::dump_watcher::*;
+use crate::fetch::FetchMetrics;
+use crate::health::Health;
+use crate::range::RangeBuckets;
+use crate::scrape::Collection;
+
+use crate::test_helpers::label_values;
+use crate::test_helpers::labels;
+use crate::test_helpers::serve;
+use crate::test_helpers::value;
+
+use hyper::Body;
+use hyper::Response;
+
+use prometheus::core::Collector;
+use prometheus::proto::MetricFamily;
+use prometheus::Registry;
+
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use tokio::sync::mpsc;
+
+const HTTP_METRICS: &[&str] = &[
+    "adsb_http_requests_total",
+    "adsb_http_request_errors_total",
+    "adsb_http_request_duration_seconds",
+    "adsb_http_response_size_bytes",
+];
+
+fn receiver(name: &str, labels: &[(&str, &str)]) -> Receiver {
+    Receiver {
+        name: name.to_string(),
+        url: "http://127.0.0.1:9".to_string(),
+        frequency: 1090,
+        aircraft_refresh_interval: Duration::from_secs(30),
+        receiver_refresh_interval: Duration::from_secs(300),
+        stats_refresh_interval: Duration::from_secs(60),
+        recent_window: Duration::from_secs(60),
+        refresh_timeout: Duration::from_millis(150),
+        labels: labels
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect::<BTreeMap<_, _>>(),
+        range: RangeBuckets::default(),
+    }
+}
+
+/// A fatal error channel nothing receives from, tasks are not given up on during the tests
+fn errors() -> ErrorSender {
+    mpsc::channel(1).0
+}
+
+/// Serves JSON with a `now` timestamp, and invalid JSON for stats.json
+async fn serve_receiver() -> SocketAddr {
+    serve(|path| {
+        let body = if path.ends_with("stats.json") {
+            "not json"
+        } else {
+            r#"{"now":1234.5}"#
+        };
+
+        Response::new(Body::from(body))
+    })
+    .await
+}
+
+#[test]
+fn test_export_receiver_info() {
+    let info = ReceiverInfo::new().unwrap();
+
+    info.export(&[
+        receiver("roof", &[("site", "home")]),
+        receiver("garage", &[("antenna", "collinear")]),
+    ])
+    .unwrap();
+
+    let pairs = |pairs: &[(&str, &str)]| -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    };
+
+    assert_eq!(
+        vec![
+            pairs(&[
+                ("antenna", ""),
+                ("frequency", "1090"),
+                ("receiver", "roof"),
+                ("site", "home")
+            ]),
+            pairs(&[
+                ("antenna", "collinear"),
+                ("frequency", "1090"),
+                ("receiver", "garage"),
+                ("site", "")
+            ]),
+        ],
+        labels(&info.collect(), "adsb_receiver_info")
+    );
+
+    // Reloading with different label names replaces the metric
+    info.export(&[receiver("roof", &[])]).unwrap();
+
+    assert_eq!(
+        vec![pairs(&[("frequency", "1090"), ("receiver", "roof")])],
+        labels(&info.collect(), "adsb_receiver_info")
+    );
+}
+
+#[tokio::test]
+async fn test_dump_watchers_update() {
+    let registry = Registry::new();
+    let mut watchers = DumpWatchers::new(
+        registry.clone(),
+        Health::new(Duration::from_secs(120)),
+        Collection::Poll,
+        errors(),
+    )
+    .unwrap();
+
+    watchers
+        .update(&[receiver("roof", &[]), receiver("garage", &[])])
+        .await
+        .unwrap();
+
+    assert_eq!(vec!["garage", "roof"], watchers.names());
+    assert_eq!(
+        vec!["garage", "roof"],
+        label_values(
+            &registry.gather(),
+            "adsb_aircraft_observed_recent",
+            "receiver"
+        )
+    );
+
+    let mut roof = receiver("roof", &[]);
+    roof.aircraft_refresh_interval = Duration::from_secs(5);
+
+    watchers
+        .update(&[roof, receiver("shed", &[])])
+        .await
+        .unwrap();
+
+    assert_eq!(vec!["roof", "shed"], watchers.names());
+
+    // Metrics of the removed receiver are unregistered
+    assert_eq!(
+        vec!["roof", "shed"],
+        label_values(
+            &registry.gather(),
+            "adsb_aircraft_observed_recent",
+            "receiver"
+        )
+    );
+    assert_eq!(
+        vec!["roof", "shed"],
+        label_values(&registry.gather(), "adsb_receiver_info", "receiver")
+    );
+
+    watchers.stop();
+
+    assert!(label_values(
+        &registry.gather(),
+        "adsb_aircraft_observed_recent",
+        "receiver"
+    )
+    .is_empty());
+}
+
+#[tokio::test]
+async fn test_dump_watchers_remove_http_metrics() {
+    let address = serve_receiver().await;
+    let registry = Registry::new();
+    let mut watchers = DumpWatchers::new(
+        registry.clone(),
+        Health::new(Duration::from_secs(120)),
+        Collection::Poll,
+        errors(),
+    )
+    .unwrap();
+
+    let mut garage = receiver("garage", &[]);
+    garage.url = format!("http://{}", address);
+
+    watchers.update(&[garage]).await.unwrap();
+
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while HTTP_METRICS
+            .iter()
+            .any(|name| label_values(&registry.gather(), name, "receiver").is_empty())
+        {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("timed out waiting for fetches");
+
+    watchers.update(&[]).await.unwrap();
+
+    for name in HTTP_METRICS {
+        assert!(
+            label_values(&registry.gather(), name, "receiver").is_empty(),
+            "{} has series of a removed receiver",
+            name
+        );
+    }
+}
+
+/// Wait until `ready` returns true for the gathered metrics of `registry`
+async fn wait_for(registry: &Registry, ready: impl Fn(&[MetricFamily]) -> bool) {
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while !ready(&registry.gather()) {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("timed out waiting for metrics");
+}
+
+#[tokio::test]
+async fn test_dump_watchers_restart_keeps_metrics() {
+    let address = serve_receiver().await;
+    let registry = Registry::new();
+    let mut watchers = DumpWatchers::new(
+        registry.clone(),
+        Health::new(Duration::from_secs(120)),
+        Collection::Poll,
+        errors(),
+    )
+    .unwrap();
+
+    let url = format!("http://{}/data/aircraft.json", address);
+    let requests = |families: &[MetricFamily]| {
+        value(families, "adsb_http_requests_total", &[("uri", &url)]).unwrap_or(0.0)
+    };
+
+    let mut roof = receiver("roof", &[]);
+    roof.url = format!("http://{}", address);
+
+    watchers.update(&[roof.clone()]).await.unwrap();
+
+    wait_for(&registry, |families| requests(families) >= 1.0).await;
+
+    // The restarted watcher fetches again, counting on from the previous watcher's requests
+    roof.aircraft_refresh_interval = Duration::from_secs(5);
+
+    watchers.update(&[roof.clone()]).await.unwrap();
+
+    wait_for(&registry, |families| requests(families) >= 2.0).await;
+
+    // Static labels only change adsb_receiver_info
+    roof.labels.insert("site".to_string(), "home".to_string());
+
+    watchers.update(&[roof]).await.unwrap();
+
+    assert_eq!(
+        vec!["home"],
+        label_values(&registry.gather(), "adsb_receiver_info", "site")
+    );
+    assert_eq!(2.0, requests(&registry.gather()));
+
+    watchers.stop();
+}
+
+#[tokio::test]
+async fn test_dump_watchers_update_rolls_back() {
+    let address = serve_receiver().await;
+    let registry = Registry::new();
+    let health = Health::new(Duration::from_secs(120));
+    let mut watchers =
+        DumpWatchers::new(registry.clone(), health.clone(), Collection::Poll, errors()).unwrap();
+
+    let mut roof = receiver("roof", &[]);
+    roof.url = format!("http://{}", address);
+
+    watchers.update(&[roof.clone()]).await.unwrap();
+
+    // Metrics already registered for garage make registering the new garage watcher fail
+    registry
+        .register(Box::new(FetchMetrics::new("garage").unwrap()))
+        .unwrap();
+
+    let mut moved = roof.clone();
+    moved.url = "http://127.0.0.1:9".to_string();
+
+    assert!(watchers
+        .update(&[moved, receiver("garage", &[])])
+        .await
+        .is_err());
+
+    // roof was not restarted for its new URL and keeps fetching the old one
+    assert_eq!(vec!["roof"], watchers.names());
+
+    let url = format!("http://{}/data/aircraft.json", address);
+
+    wait_for(&registry, |families| {
+        value(families, "adsb_http_requests_total", &[("uri", &url)]).is_some()
+    })
+    .await;
+
+    assert_eq!(
+        vec!["roof"],
+        label_values(&registry.gather(), "adsb_receiver_info", "receiver")
+    );
+    assert_eq!(
+        vec![url.as_str()],
+        health
+            .readiness()
+            .sources
+            .iter()
+            .filter(|source| source.file == "aircraft.json")
+            .map(|source| source.url.as_str())
+            .collect::<Vec<_>>()
+    );
+
+    watchers.stop();
+}
+
+#[tokio::test]
+async fn test_dump_watchers_separate_registries() {
+    let health = Health::new(Duration::from_secs(120));
+
+    let first = Registry::new();
+    let second = Registry::new();
+
+    let mut watchers = vec![
+        DumpWatchers::new(first.clone(), health.clone(), Collection::Poll, errors()).unwrap(),
+        DumpWatchers::new(
+            second.clone(),
+            health,
+            Collection::Scrape {
+                cache_age: Duration::from_secs(1),
+            },
+            errors(),
+        )
+        .unwrap(),
+    ];
+
+    // The same receiver is watched by both without its metrics colliding
+    for watchers in &mut watchers {
+        watchers.update(&[receiver("roof", &[])]).await.unwrap();
+    }
+
+    assert_eq!(
+        vec!["roof"],
+        label_values(&first.gather(), "adsb_aircraft_observed_recent", "receiver")
+    );
+
+    // Metrics of a scraped receiver are only built by a scrape
+    assert!(label_values(
+        &second.gather(),
+        "adsb_aircraft_observed_recent",
+        "receiver"
+    )
+    .is_empty());
+    assert_eq!(
+        vec!["roof"],
+        label_values(&second.gather(), "adsb_receiver_info", "receiver")
+    );
+
+    for watchers in &mut watchers {
+        watchers.stop();
+    }
+}
@@ -0,0 +1,102 @@
+use crate::fetch::*;
+use crate::test_helpers::metric;
+use crate::test_helpers::serve;
+use crate::test_helpers::url_fetcher;
+use crate::test_helpers::value;
+
+use hyper::Body;
+use hyper::Response;
+use hyper::StatusCode;
+
+use prometheus::core::Collector;
+use prometheus::Registry;
+
+use std::net::SocketAddr;
+
+/// Serves aircraft.json and responds 404 for other files
+async fn serve_aircraft() -> SocketAddr {
+    serve(|path| match path {
+        "/data/aircraft.json" => Response::new(Body::from(r#"{"now":1234.5}"#)),
+        _ => Response::builder()
+            .status(StatusCode::NOT_FOUND)
+            .body(Body::from("not found"))
+            .unwrap(),
+    })
+    .await
+}
+
+fn errors(metrics: &FetchMetrics, error_type: &str) -> f64 {
+    value(
+        &metrics.collect(),
+        "adsb_http_request_errors_total",
+        &[("error_type", error_type)],
+    )
+    .unwrap_or(0.0)
+}
+
+#[tokio::test]
+async fn test_fetch() {
+    let address = serve_aircraft().await;
+    let metrics = FetchMetrics::new("roof").unwrap();
+    let fetcher = url_fetcher(format!("http://{}/data/aircraft.json", address), &metrics);
+
+    assert_eq!(Some(1234.5), fetcher.fetch().await.unwrap()["now"].as_f64());
+    assert_eq!(Some(1.0), value(&metrics.collect(), "adsb_http_up", &[]));
+    assert_eq!(
+        Some(1234.5),
+        value(
+            &metrics.collect(),
+            "adsb_http_last_data_timestamp_seconds",
+            &[]
+        )
+    );
+    assert!(
+        value(
+            &metrics.collect(),
+            "adsb_http_last_success_timestamp_seconds",
+            &[]
+        )
+        .unwrap()
+            > 0.0
+    );
+    assert_eq!(
+        1,
+        metric(&metrics.collect(), "adsb_http_response_size_bytes", &[])
+            .unwrap()
+            .get_histogram()
+            .get_sample_count()
+    );
+
+    let registry = Registry::new();
+    registry.register(Box::new(metrics.clone())).unwrap();
+
+    assert!(registry
+        .gather()
+        .iter()
+        .any(|f| f.get_name() == "adsb_http_up"));
+
+    registry.unregister(Box::new(metrics)).unwrap();
+
+    assert!(registry.gather().is_empty());
+}
+
+#[tokio::test]
+async fn test_fetch_errors() {
+    let address = serve_aircraft().await;
+
+    let metrics = FetchMetrics::new("roof").unwrap();
+    let missing = url_fetcher(format!("http://{}/data/stats.json", address), &metrics);
+
+    assert!(missing.fetch().await.is_none());
+    assert_eq!(Some(0.0), value(&metrics.collect(), "adsb_http_up", &[]));
+    assert_eq!(1.0, errors(&metrics, "status"));
+    assert_eq!(0.0, errors(&metrics, "json"));
+
+    // Port 1 is reserved, nothing listens there
+    let metrics = FetchMetrics::new("roof").unwrap();
+    let refused = url_fetcher("http://127.0.0.1:1/data/aircraft.json".into(), &metrics);
+
+    assert!(refused.fetch().await.is_none());
+    assert_eq!(Some(0.0), value(&metrics.collect(), "adsb_http_up", &[]));
+    assert_eq!(1.0, errors(&metrics, "connect"));
+}
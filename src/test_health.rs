@@ -0,0 +1,217 @@
+use crate::adsb_exporter::respond;
+use crate::beast_watcher::BeastWatcher;
+use crate::configuration::Configuration;
+use crate::health::*;
+use crate::scrape::ScrapeTargets;
+
+use clap::IntoApp;
+
+use hyper::Body;
+use hyper::Request;
+use hyper::StatusCode;
+
+use prometheus::Registry;
+
+use serde_json::json;
+
+use std::time::Duration;
+use std::time::Instant;
+
+use tokio::io::AsyncWriteExt;
+use tokio::net::TcpListener;
+use tokio::sync::mpsc;
+use tokio::time::sleep;
+use tokio::time::timeout;
+
+const WINDOW: Duration = Duration::from_secs(60);
+
+fn reasons(health: &Health, now: Instant) -> Vec<Option<String>> {
+    health
+        .readiness_at(now)
+        .sources
+        .into_iter()
+        .map(|s| s.reason)
+        .collect()
+}
+
+#[test]
+fn test_readiness() {
+    let health = Health::new(WINDOW);
+    let start = Instant::now();
+
+    assert!(health.readiness_at(start).ready);
+
+    let aircraft = health.freshness("roof", "aircraft.json", "http://roof/data/aircraft.json");
+    aircraft.track();
+    let stats = health.freshness("roof", "stats.json", "http://roof/data/stats.json");
+    stats.track();
+
+    let readiness = health.readiness_at(start);
+
+    assert!(!readiness.ready);
+    assert_eq!(60, readiness.window_seconds);
+    assert_eq!(
+        vec![
+            Some("not fetched yet".to_string()),
+            Some("not fetched yet".to_string())
+        ],
+        reasons(&health, start)
+    );
+
+    aircraft.fetched_at(Some(100.0), start);
+    stats.fetched_at(None, start);
+
+    let readiness = health.readiness_at(start + Duration::from_secs(30));
+
+    assert!(readiness.ready);
+    assert_eq!(Some(100.0), readiness.sources[0].data_timestamp);
+    assert_eq!(Some(30.0), readiness.sources[0].last_success_seconds_ago);
+
+    assert_eq!(
+        vec![
+            Some("last fetched 90 seconds ago".to_string()),
+            Some("last fetched 90 seconds ago".to_string())
+        ],
+        reasons(&health, start + Duration::from_secs(90))
+    );
+}
+
+#[test]
+fn test_readiness_now_not_advancing() {
+    let health = Health::new(WINDOW);
+    let start = Instant::now();
+
+    let aircraft = health.freshness("roof", "aircraft.json", "http://roof/data/aircraft.json");
+    aircraft.track();
+
+    aircraft.fetched_at(Some(100.0), start);
+    aircraft.fetched_at(Some(100.0), start + Duration::from_secs(50));
+    aircraft.fetched_at(Some(100.0), start + Duration::from_secs(90));
+
+    let now = start + Duration::from_secs(90);
+
+    assert!(!health.readiness_at(now).ready);
+    assert_eq!(
+        vec![Some("now has not advanced for 90 seconds".to_string())],
+        reasons(&health, now)
+    );
+
+    aircraft.fetched_at(Some(190.0), now);
+
+    assert!(health.readiness_at(now).ready);
+}
+
+#[test]
+fn test_freshness_tracked_again() {
+    let health = Health::new(WINDOW);
+    let start = Instant::now();
+
+    let aircraft = health.freshness("roof", "aircraft.json", "http://roof/data/aircraft.json");
+    aircraft.track();
+    aircraft.fetched_at(None, start);
+
+    health
+        .freshness("roof", "aircraft.json", "http://roof/data/aircraft.json")
+        .track();
+
+    assert!(health.readiness_at(start).ready);
+
+    health
+        .freshness("roof", "aircraft.json", "http://moved/data/aircraft.json")
+        .track();
+
+    assert_eq!(
+        vec![Some("not fetched yet".to_string())],
+        reasons(&health, start)
+    );
+
+    health.remove("roof");
+
+    assert!(health.readiness_at(start).sources.is_empty());
+}
+
+#[tokio::test]
+async fn test_respond() {
+    let health = Health::new(WINDOW);
+
+    let get = |path: &str| {
+        let request = Request::get(path).body(Body::empty()).unwrap();
+        let health = health.clone();
+
+        async move { respond(&request, &Registry::new(), &health, &ScrapeTargets::new()).await }
+    };
+
+    let body = |response: hyper::Response<Body>| async {
+        let bytes = hyper::body::to_bytes(response.into_body()).await.unwrap();
+
+        String::from_utf8(bytes.to_vec()).unwrap()
+    };
+
+    assert_eq!(StatusCode::OK, get("/metrics").await.status());
+    assert_eq!(StatusCode::NOT_FOUND, get("/").await.status());
+
+    let healthz = get("/healthz").await;
+    assert_eq!(StatusCode::OK, healthz.status());
+    assert_eq!(r#"{"status":"ok"}"#, body(healthz).await);
+
+    assert_eq!(StatusCode::OK, get("/readyz").await.status());
+
+    health
+        .freshness("roof", "aircraft.json", "http://roof/data/aircraft.json")
+        .track();
+
+    let readyz = get("/readyz").await;
+    assert_eq!(StatusCode::SERVICE_UNAVAILABLE, readyz.status());
+
+    let readiness: serde_json::Value = serde_json::from_str(&body(readyz).await).unwrap();
+
+    assert_eq!(json!(false), readiness["ready"]);
+    assert_eq!(json!("roof"), readiness["sources"][0]["receiver"]);
+    assert_eq!(json!("not fetched yet"), readiness["sources"][0]["reason"]);
+}
+
+#[tokio::test]
+async fn test_readiness_beast() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+
+    let matches = Configuration::into_app()
+        .try_get_matches_from(["adsb_exporter", "--beast-address", &address])
+        .unwrap();
+    let configuration = Configuration::from_matches(&matches).unwrap();
+
+    let health = Health::new(WINDOW);
+    let (error_tx, _error_rx) = mpsc::channel(1);
+
+    let stopper = BeastWatcher::new(&configuration, Registry::new(), health.clone())
+        .unwrap()
+        .start(error_tx)
+        .await;
+
+    // A BEAST-only exporter is not ready until its server sends a message
+    let readiness = health.readiness();
+
+    assert!(!readiness.ready);
+    assert_eq!(address, readiness.sources[0].receiver);
+    assert_eq!("beast", readiness.sources[0].file);
+    assert_eq!(format!("tcp://{}", address), readiness.sources[0].url);
+
+    let (mut server, _) = listener.accept().await.unwrap();
+
+    // A DF 11 all-call reply
+    let frame = [
+        0x1a, b'2', 0, 0, 0, 0, 0, 0, 0x80, 0x5d, 0xa6, 0xa6, 0xb7, 0xfd, 0xe8, 0xb1,
+    ];
+
+    server.write_all(&frame).await.unwrap();
+
+    timeout(Duration::from_secs(5), async {
+        while !health.readiness().ready {
+            sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("timed out waiting for readiness");
+
+    stopper.stop().await;
+}
@@ -0,0 +1,124 @@
+use crate::fetch::FetchMetrics;
+use crate::fetch::Fetcher;
+
+use hyper::service::make_service_fn;
+use hyper::service::service_fn;
+use hyper::Body;
+use hyper::Request;
+use hyper::Response;
+use hyper::Server;
+
+use prometheus::proto::Metric;
+use prometheus::proto::MetricFamily;
+
+use reqwest::Client;
+
+use std::convert::Infallible;
+use std::net::SocketAddr;
+
+/// A fetcher of `url` for the receiver "roof" recording its fetches in `metrics`
+pub(crate) fn url_fetcher(url: String, metrics: &FetchMetrics) -> Fetcher {
+    Fetcher::new(Client::new(), "roof".into(), url, metrics.clone(), None)
+}
+
+/// A fetcher of `file` from the receiver "roof", for tests that never fetch
+pub(crate) fn fetcher(file: &str) -> Fetcher {
+    url_fetcher(
+        format!("http://roof.example/data/{}", file),
+        &FetchMetrics::new("roof").unwrap(),
+    )
+}
+
+/// Serve the body `respond` returns for each request path on an unused port
+pub(crate) async fn serve<F>(respond: F) -> SocketAddr
+where
+    F: Fn(&str) -> Response<Body> + Clone + Send + Sync + 'static,
+{
+    let service = make_service_fn(move |_| {
+        let respond = respond.clone();
+
+        async move {
+            Ok::<_, Infallible>(service_fn(move |request: Request<Body>| {
+                let response = respond(request.uri().path());
+
+                async move { Ok::<_, Infallible>(response) }
+            }))
+        }
+    });
+
+    let server = Server::bind(&"127.0.0.1:0".parse().unwrap()).serve(service);
+    let address = server.local_addr();
+
+    tokio::spawn(server);
+
+    address
+}
+
+/// The first series of `name` with every label pair of `labels`
+pub(crate) fn metric(
+    families: &[MetricFamily],
+    name: &str,
+    labels: &[(&str, &str)],
+) -> Option<Metric> {
+    families
+        .iter()
+        .filter(|f| f.get_name() == name)
+        .flat_map(|f| f.get_metric())
+        .find(|m| {
+            labels.iter().all(|(label, value)| {
+                m.get_label()
+                    .iter()
+                    .any(|l| l.get_name() == *label && l.get_value() == *value)
+            })
+        })
+        .cloned()
+}
+
+/// Value of the first counter or gauge series of `name` with every label pair of `labels`
+pub(crate) fn value(families: &[MetricFamily], name: &str, labels: &[(&str, &str)]) -> Option<f64> {
+    metric(families, name, labels).map(|m| {
+        if m.has_counter() {
+            m.get_counter().get_value()
+        } else {
+            m.get_gauge().get_value()
+        }
+    })
+}
+
+/// Values of `label` of each series of `name`, sorted
+pub(crate) fn label_values(families: &[MetricFamily], name: &str, label: &str) -> Vec<String> {
+    let mut values: Vec<String> = families
+        .iter()
+        .filter(|f| f.get_name() == name)
+        .flat_map(|f| f.get_metric())
+        .filter_map(|m| {
+            m.get_label()
+                .iter()
+                .find(|l| l.get_name() == label)
+                .map(|l| l.get_value().to_string())
+        })
+        .collect();
+
+    values.sort();
+
+    values
+}
+
+/// Label pairs of each series of `name`, sorted
+pub(crate) fn labels(families: &[MetricFamily], name: &str) -> Vec<Vec<(String, String)>> {
+    let mut labels: Vec<Vec<(String, String)>> = families
+        .iter()
+        .filter(|f| f.get_name() == name)
+        .flat_map(|f| f.get_metric())
+        .map(|m| {
+            m.get_label()
+                .iter()
+                .map(|l| (l.get_name().to_string(), l.get_value().to_string()))
+                .collect()
+        })
+        .collect();
+
+    labels.sort();
+
+    labels
+}
@@ -0,0 +1,276 @@
+use crate::range::*;
+
+use geo::Point;
+
+use prometheus::core::Collector;
+use prometheus::Opts;
+
+#[test]
+fn test_observation() {
+    let receiver = Point::new(-122.0, 47.0);
+    let buckets = RangeBuckets::default();
+
+    let north = Observation::new(receiver, Point::new(-122.0, 47.5), &buckets);
+
+    assert!((north.distance - 55_597.0).abs() < 1.0);
+    assert_eq!("80000", north.distance_bucket);
+    assert_eq!("0", north.bearing_bucket);
+
+    let east = Observation::new(receiver, Point::new(-116.0, 47.0), &buckets);
+
+    assert_eq!("> 400000", east.distance_bucket);
+    assert_eq!("90", east.bearing_bucket);
+
+    let south_west = Observation::new(receiver, Point::new(-123.0, 46.5), &buckets);
+
+    assert_eq!("225", south_west.bearing_bucket);
+}
+
+#[test]
+fn test_ranges() {
+    let receiver = Point::new(-122.0, 47.0);
+    let buckets = RangeBuckets::default();
+
+    let mut ranges = Ranges::default();
+
+    ranges.add(&Observation::new(
+        receiver,
+        Point::new(-122.0, 47.5),
+        &buckets,
+    ));
+    ranges.add(&Observation::new(
+        receiver,
+        Point::new(-122.0, 47.6),
+        &buckets,
+    ));
+    ranges.add(&Observation::new(
+        receiver,
+        Point::new(-122.0, 48.0),
+        &buckets,
+    ));
+
+    assert_eq!(
+        Some(&2),
+        ranges
+            .observations
+            .get(&("80000".to_string(), "0".to_string()))
+    );
+
+    let maximum = ranges.ranges.get("0").unwrap();
+
+    assert!((maximum - 111_195.0).abs() < 1.0);
+}
+
+#[test]
+fn test_observation_buckets() {
+    let receiver = Point::new(-122.0, 47.0);
+    let buckets = RangeBuckets {
+        distance: 10_000,
+        maximum_distance: 50_000,
+        unit: DistanceUnit::Meters,
+        bearing: 45.0,
+    };
+
+    let north = Observation::new(receiver, Point::new(-122.0, 47.2), &buckets);
+
+    assert_eq!("30000", north.distance_bucket);
+    assert_eq!("0", north.bearing_bucket);
+
+    let south_west = Observation::new(receiver, Point::new(-123.0, 46.5), &buckets);
+
+    assert_eq!("> 50000", south_west.distance_bucket);
+    assert_eq!("225", south_west.bearing_bucket);
+}
+
+#[test]
+fn test_observation_units() {
+    let receiver = Point::new(-122.0, 47.0);
+    let north = Point::new(-122.0, 47.5);
+
+    let bucket = |unit| {
+        let buckets = RangeBuckets {
+            distance: 1,
+            maximum_distance: 1_000,
+            unit,
+            ..RangeBuckets::default()
+        };
+
+        Observation::new(receiver, north, &buckets)
+    };
+
+    assert_eq!("56", bucket(DistanceUnit::Kilometers).distance_bucket);
+    assert_eq!("31", bucket(DistanceUnit::NauticalMiles).distance_bucket);
+    assert_eq!("35", bucket(DistanceUnit::StatuteMiles).distance_bucket);
+
+    assert_eq!(
+        DistanceUnit::Kilometers,
+        bucket(DistanceUnit::Kilometers).unit
+    );
+
+    // The distance is always meters
+    assert!((bucket(DistanceUnit::StatuteMiles).distance - 55_597.0).abs() < 1.0);
+}
+
+#[test]
+fn test_observation_beyond_default_maximum() {
+    let receiver = Point::new(-122.0, 47.0);
+    let buckets = RangeBuckets {
+        distance: 50,
+        maximum_distance: 600,
+        unit: DistanceUnit::Kilometers,
+        ..RangeBuckets::default()
+    };
+
+    let far = Observation::new(receiver, Point::new(-122.0, 51.5), &buckets);
+
+    assert!((far.distance - 500_377.0).abs() < 1.0);
+    assert_eq!("550", far.distance_bucket);
+}
+
+#[test]
+fn test_observation_largest_buckets() {
+    let receiver = Point::new(-122.0, 47.0);
+    let antipode = Point::new(58.0, -47.0);
+
+    let buckets = RangeBuckets {
+        distance: u32::MAX,
+        maximum_distance: u32::MAX,
+        ..RangeBuckets::default()
+    };
+
+    let far = Observation::new(receiver, antipode, &buckets);
+
+    assert_eq!(u32::MAX.to_string(), far.distance_bucket);
+
+    let buckets = RangeBuckets {
+        distance: 1,
+        maximum_distance: u32::MAX,
+        unit: DistanceUnit::StatuteMiles,
+        bearing: 360.0,
+    };
+
+    let far = Observation::new(receiver, antipode, &buckets);
+
+    assert_eq!("12437", far.distance_bucket);
+}
+
+#[test]
+fn test_observation_fine_bearing() {
+    let receiver = Point::new(-122.0, 47.0);
+    let buckets = RangeBuckets {
+        bearing: 0.1,
+        ..RangeBuckets::default()
+    };
+
+    let east = Observation::new(receiver, Point::new(-116.0, 47.0), &buckets);
+
+    assert_eq!("87.8", east.bearing_bucket);
+
+    let south_west = Observation::new(receiver, Point::new(-123.0, 46.5), &buckets);
+
+    assert_eq!("234.2", south_west.bearing_bucket);
+
+    let buckets = RangeBuckets {
+        bearing: 1.0,
+        ..RangeBuckets::default()
+    };
+
+    let east = Observation::new(receiver, Point::new(-116.0, 47.0), &buckets);
+
+    assert_eq!("88", east.bearing_bucket);
+}
+
+#[test]
+fn test_range_metrics() {
+    let metrics = RangeMetrics::new(
+        Opts::new("observations", "observations").const_label("receiver", "roof"),
+        Opts::new("ranges", "ranges").const_label("receiver", "roof"),
+    )
+    .unwrap();
+
+    assert_eq!(2, metrics.desc().len());
+    assert!(metrics
+        .collect()
+        .iter()
+        .all(|family| family.get_metric().is_empty()));
+
+    let receiver = Point::new(-122.0, 47.0);
+    let buckets = RangeBuckets::default();
+
+    let mut ranges = Ranges::default();
+    ranges.add(&Observation::new(
+        receiver,
+        Point::new(-122.0, 47.5),
+        &buckets,
+    ));
+    ranges.add(&Observation::new(
+        receiver,
+        Point::new(-121.0, 47.0),
+        &buckets,
+    ));
+
+    metrics.update(&ranges).unwrap();
+
+    let families = metrics.collect();
+
+    assert_eq!(2, families[0].get_metric().len());
+    assert_eq!(2, families[1].get_metric().len());
+
+    let mut ranges = Ranges::default();
+    ranges.add(&Observation::new(
+        receiver,
+        Point::new(-122.0, 47.5),
+        &buckets,
+    ));
+
+    metrics.update(&ranges).unwrap();
+
+    // Buckets without observations in the latest update are gone
+    let families = metrics.collect();
+
+    assert_eq!(1, families[0].get_metric().len());
+    assert_eq!(1, families[1].get_metric().len());
+    assert_eq!("0", families[1].get_metric()[0].get_label()[0].get_value());
+    assert_eq!(
+        "roof",
+        families[1].get_metric()[0].get_label()[1].get_value()
+    );
+
+    let labels = families[0].get_metric()[0].get_label();
+
+    assert_eq!("unit", labels[3].get_name());
+    assert_eq!("meters", labels[3].get_value());
+}
+
+#[test]
+fn test_range_metrics_unit() {
+    let metrics = RangeMetrics::new(
+        Opts::new("observations", "observations"),
+        Opts::new("ranges", "ranges"),
+    )
+    .unwrap();
+
+    let buckets = RangeBuckets {
+        distance: 50,
+        maximum_distance: 600,
+        unit: DistanceUnit::NauticalMiles,
+        ..RangeBuckets::default()
+    };
+
+    let mut ranges = Ranges::default();
+    ranges.add(&Observation::new(
+        Point::new(-122.0, 47.0),
+        Point::new(-122.0, 47.5),
+        &buckets,
+    ));
+
+    metrics.update(&ranges).unwrap();
+
+    let families = metrics.collect();
+    let labels = families[0].get_metric()[0].get_label();
+
+    assert_eq!("distance", labels[1].get_name());
+    assert_eq!("50", labels[1].get_value());
+    assert_eq!("unit", labels[2].get_name());
+    assert_eq!("nautical_miles", labels[2].get_value());
+}
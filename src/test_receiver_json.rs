@@ -0,0 +1,91 @@
+use crate::receiver_json::*;
+use crate::test_helpers::fetcher;
+use crate::test_helpers::label_values;
+
+use prometheus::core::Collector;
+
+use serde_json::json;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::sync::RwLock;
+
+#[tokio::test]
+async fn test_update_receiver_removes_old_version_and_position() {
+    let position = Arc::new(RwLock::new(None));
+    let receiver_json = ReceiverJson::new(
+        fetcher("receiver.json"),
+        1090,
+        Duration::from_secs(300),
+        position.clone(),
+    );
+    let metrics = ReceiverMetrics::new("roof", "1090").unwrap();
+
+    receiver_json
+        .update_receiver(
+            &metrics,
+            json!({ "version": "7.1", "lat": 47.6, "lon": -122.3 }),
+        )
+        .await
+        .unwrap();
+
+    receiver_json
+        .update_receiver(
+            &metrics,
+            json!({ "version": "7.2", "lat": 47.7, "lon": -122.3 }),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(
+        vec!["7.2"],
+        label_values(&metrics.collect(), "adsb_receiver_version_info", "version")
+    );
+    assert_eq!(
+        vec!["47.7"],
+        label_values(
+            &metrics.collect(),
+            "adsb_receiver_position_info",
+            "latitude"
+        )
+    );
+    assert_eq!(Some(47.7), position.read().await.map(|p| p.y));
+}
+
+#[tokio::test]
+async fn test_update_receiver_fixture() {
+    let position = Arc::new(RwLock::new(None));
+    let receiver_json = ReceiverJson::new(
+        fetcher("receiver.json"),
+        1090,
+        Duration::from_secs(300),
+        position.clone(),
+    );
+    let metrics = ReceiverMetrics::new("roof", "1090").unwrap();
+
+    let data = serde_json::from_str(include_str!("fixtures/dump1090/receiver.json")).unwrap();
+
+    receiver_json.update_receiver(&metrics, data).await.unwrap();
+
+    assert_eq!(
+        vec!["7.2"],
+        label_values(&metrics.collect(), "adsb_receiver_version_info", "version")
+    );
+    assert_eq!(
+        vec!["roof"],
+        label_values(&metrics.collect(), "adsb_receiver_version_info", "receiver")
+    );
+    assert_eq!(
+        vec!["-122.3"],
+        label_values(
+            &metrics.collect(),
+            "adsb_receiver_position_info",
+            "longitude"
+        )
+    );
+    assert_eq!(
+        Some((-122.3, 47.6)),
+        position.read().await.map(|p| (p.x, p.y))
+    );
+}
@@ -0,0 +1,107 @@
+use crate::sbs::*;
+
+use chrono::NaiveDate;
+
+#[test]
+fn test_parse_position() {
+    let message: SbsMessage =
+        "MSG,3,1,1,40621D,1,2022/01/02,03:04:05.678,2022/01/02,03:04:06.789,,38000,,,52.25720,3.91937,,,0,,0,0"
+            .parse()
+            .unwrap();
+
+    let generated = NaiveDate::from_ymd_opt(2022, 1, 2)
+        .unwrap()
+        .and_hms_milli_opt(3, 4, 5, 678)
+        .unwrap();
+    let logged = NaiveDate::from_ymd_opt(2022, 1, 2)
+        .unwrap()
+        .and_hms_milli_opt(3, 4, 6, 789)
+        .unwrap();
+
+    let expected = SbsMessage {
+        source: Source::ModeS,
+        transmission_type: 3,
+        icao: "40621D".to_string(),
+        generated: Some(generated),
+        logged: Some(logged),
+        call_sign: None,
+        altitude: Some(38000),
+        ground_speed: None,
+        track: None,
+        latitude: Some(52.2572),
+        longitude: Some(3.91937),
+        vertical_rate: None,
+        squawk: None,
+        alert: Some(false),
+        emergency: None,
+        spi: Some(false),
+        on_ground: Some(false),
+    };
+
+    assert_eq!(expected, message);
+    assert_eq!(Some((52.2572, 3.91937)), message.position());
+    assert_eq!(generated, message.time().unwrap().naive_local());
+}
+
+#[test]
+fn test_parse_mlat() {
+    let message: SbsMessage =
+        "MLAT,3,1,1,a1b2c3,1,,,2022/01/02,03:04:06.789,,12000,250,90,37.1,-122.2,-64,,,,,"
+            .parse()
+            .unwrap();
+
+    assert_eq!(Source::Mlat, message.source);
+    assert_eq!("A1B2C3", message.icao);
+    assert_eq!(None, message.generated);
+    assert_eq!(Some(250.0), message.ground_speed);
+    assert_eq!(Some(-64), message.vertical_rate);
+    assert_eq!(
+        message.logged.unwrap(),
+        message.time().unwrap().naive_local()
+    );
+}
+
+#[test]
+fn test_parse_tisb() {
+    let message: SbsMessage =
+        "TISB,1,1,1,~A1B2C3,1,2022/01/02,03:04:05.678,2022/01/02,03:04:05.678,N12345"
+            .parse()
+            .unwrap();
+
+    assert_eq!(Source::TisB, message.source);
+    assert_eq!("A1B2C3", message.icao);
+    assert_eq!(Some("N12345".to_string()), message.call_sign);
+    assert_eq!(None, message.on_ground);
+}
+
+#[test]
+fn test_parse_error() {
+    assert!("SEL,,1,1,A1B2C3,1,,,,,".parse::<SbsMessage>().is_err());
+    assert!("MSG,9,1,1,A1B2C3,1,,,,,".parse::<SbsMessage>().is_err());
+    assert!("MSG,3,1,1,A1B2C3".parse::<SbsMessage>().is_err());
+    assert!("MSG,3,1,1,A1B2C3,1,,,,,,high"
+        .parse::<SbsMessage>()
+        .is_err());
+}
+
+#[test]
+fn test_parse_record() {
+    let record: SbsRecord =
+        "MSG,8,1,1,A1B2C3,1,2022/01/02,03:04:05.678,2022/01/02,03:04:05.678,,,,,,,,,,,,0"
+            .parse()
+            .unwrap();
+
+    assert!(matches!(record, SbsRecord::Message(_)));
+
+    for line in [
+        "SEL,,1,1,A1B2C3,1,2022/01/02,03:04:05.678,2022/01/02,03:04:05.678,BAW123",
+        "ID,,1,1,A1B2C3,1,2022/01/02,03:04:05.678,2022/01/02,03:04:05.678,BAW123",
+        "AIR,,1,1,A1B2C3,1,2022/01/02,03:04:05.678,2022/01/02,03:04:05.678",
+        "STA,,1,1,A1B2C3,1,2022/01/02,03:04:05.678,2022/01/02,03:04:05.678,RM",
+        "CLK,,,,,,2022/01/02,03:04:05.678,2022/01/02,03:04:05.678",
+    ] {
+        assert_eq!(SbsRecord::Other, line.parse().unwrap());
+    }
+
+    assert!("BOGUS,,1,1,A1B2C3,1,,,,,".parse::<SbsRecord>().is_err());
+}
@@ -0,0 +1,183 @@
+use crate::aircraft_json::AircraftJson;
+use crate::fetch::FetchMetrics;
+use crate::range::RangeBuckets;
+use crate::receiver_json::ReceiverJson;
+use crate::scrape::*;
+use crate::stats_json::StatsJson;
+use crate::test_helpers::serve;
+use crate::test_helpers::url_fetcher;
+use crate::test_helpers::value;
+
+use hyper::Body;
+use hyper::Response;
+
+use prometheus::core::Collector;
+use prometheus::Registry;
+
+use std::net::SocketAddr;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::sync::RwLock;
+
+const RECEIVER: &str = r#"{"version":"7.2","refresh":1000,"history":120,"lat":47.6,"lon":-122.3}"#;
+
+const AIRCRAFT: &str = r#"{
+    "now": 1640000000.0,
+    "messages": 100,
+    "aircraft": [
+        {"hex": "a1b2c3", "seen": 1.0, "seen_pos": 1.0, "lat": 47.9, "lon": -122.3, "mlat": []},
+        {"hex": "a1b2c4", "seen": 2.0, "seen_pos": 2.0, "lat": 47.6, "lon": -121.0, "mlat": ["lat", "lon"]},
+        {"hex": "a1b2c5", "seen": 5.0}
+    ]
+}"#;
+
+const STATS: &str = r#"{
+    "total": {
+        "messages": 1234,
+        "adaptive": {},
+        "cpr": {"airborne": 10},
+        "cpu": {"demod": 2000},
+        "local": {"modes": 500},
+        "remote": {},
+        "tracks": {"all": 3}
+    },
+    "last1min": {"local": {"signal": -20.5}}
+}"#;
+
+/// Serves receiver JSON and counts requests for each file
+async fn serve_counted(requests: Arc<AtomicUsize>) -> SocketAddr {
+    serve(move |path| {
+        requests.fetch_add(1, Ordering::SeqCst);
+
+        let body = match path {
+            "/data/receiver.json" => RECEIVER,
+            "/data/aircraft.json" => AIRCRAFT,
+            _ => STATS,
+        };
+
+        Response::new(Body::from(body))
+    })
+    .await
+}
+
+fn target(address: SocketAddr, cache_age: Duration) -> Target {
+    let metrics = FetchMetrics::new("roof").unwrap();
+    let fetcher = |file: &str| url_fetcher(format!("http://{}/data/{}", address, file), &metrics);
+
+    let position = Arc::new(RwLock::new(None));
+    let interval = Duration::from_secs(60);
+
+    Target::new(
+        ReceiverJson::new(fetcher("receiver.json"), 1090, interval, position.clone()),
+        AircraftJson::new(
+            fetcher("aircraft.json"),
+            1090,
+            interval,
+            position,
+            Duration::from_secs(60),
+            RangeBuckets::default(),
+        ),
+        Some(StatsJson::new(fetcher("stats.json"), 1090, interval)),
+        interval,
+        cache_age,
+    )
+}
+
+#[tokio::test]
+async fn test_collect() {
+    let requests = Arc::new(AtomicUsize::new(0));
+    let address = serve_counted(requests.clone()).await;
+    let target = target(address, Duration::from_secs(60));
+
+    assert!(target.collect().is_empty());
+
+    target.refresh().await;
+    let families = target.collect();
+
+    assert_eq!(3, requests.load(Ordering::SeqCst));
+
+    assert_eq!(
+        Some(3.0),
+        value(&families, "adsb_aircraft_observed_recent", &[])
+    );
+    assert_eq!(
+        Some(2.0),
+        value(&families, "adsb_aircraft_with_position_recent", &[])
+    );
+    assert_eq!(
+        Some(1.0),
+        value(&families, "adsb_aircraft_mlat_recent", &[])
+    );
+    assert!(value(&families, "adsb_aircraft_ranges_recent", &[]).is_some());
+    assert_eq!(
+        Some(1.0),
+        value(&families, "adsb_receiver_version_info", &[])
+    );
+    assert_eq!(
+        Some(1234.0),
+        value(&families, "adsb_stats_messages_total", &[])
+    );
+    assert_eq!(
+        Some(2.0),
+        value(&families, "adsb_stats_cpu_demodulation_seconds_total", &[])
+    );
+    assert_eq!(
+        Some(-20.5),
+        value(&families, "adsb_stats_local_signal_dbfs", &[])
+    );
+
+    // A new scrape builds fresh metrics from the cached JSON, counters are not added twice
+    target.refresh().await;
+    let families = target.collect();
+
+    assert_eq!(3, requests.load(Ordering::SeqCst));
+    assert_eq!(
+        Some(1234.0),
+        value(&families, "adsb_stats_messages_total", &[])
+    );
+}
+
+#[tokio::test]
+async fn test_collect_cache_expired() {
+    let requests = Arc::new(AtomicUsize::new(0));
+    let address = serve_counted(requests.clone()).await;
+    let target = target(address, Duration::from_secs(0));
+
+    target.refresh().await;
+    target.refresh().await;
+
+    // receiver.json is reused for the receiver refresh interval
+    assert_eq!(5, requests.load(Ordering::SeqCst));
+}
+
+#[tokio::test]
+async fn test_scrape_targets() {
+    let requests = Arc::new(AtomicUsize::new(0));
+    let address = serve_counted(requests.clone()).await;
+    let target = target(address, Duration::from_secs(60));
+
+    let registry = Registry::new();
+    registry.register(Box::new(target.clone())).unwrap();
+
+    let targets = ScrapeTargets::new();
+    targets.add("roof", target.clone());
+    targets.refresh().await;
+
+    assert_eq!(3, requests.load(Ordering::SeqCst));
+    assert_eq!(
+        Some(1234.0),
+        value(&registry.gather(), "adsb_stats_messages_total", &[])
+    );
+
+    targets.remove("roof");
+    targets.refresh().await;
+
+    assert_eq!(3, requests.load(Ordering::SeqCst));
+
+    registry.unregister(Box::new(target)).unwrap();
+
+    assert!(registry.gather().is_empty());
+}
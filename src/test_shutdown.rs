@@ -0,0 +1,41 @@
+use crate::shutdown::*;
+
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::time::timeout;
+
+#[tokio::test]
+async fn test_stop() {
+    let stopper = Stopper::new();
+    let mut shutdown = stopper.shutdown();
+    let finished = Arc::new(AtomicBool::new(false));
+
+    let task_finished = finished.clone();
+
+    tokio::spawn(async move {
+        shutdown.requested().await;
+
+        // Flushing takes a while, stop waits for it
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        task_finished.store(true, Ordering::SeqCst);
+    });
+
+    stopper.stop().await;
+
+    assert!(finished.load(Ordering::SeqCst));
+}
+
+#[tokio::test]
+async fn test_requested_after_stopper_dropped() {
+    let stopper = Stopper::new();
+    let mut shutdown = stopper.shutdown();
+
+    drop(stopper);
+
+    assert!(timeout(Duration::from_millis(50), shutdown.requested())
+        .await
+        .is_err());
+}
@@ -0,0 +1,105 @@
+use crate::stats_json::*;
+use crate::test_helpers::fetcher;
+use crate::test_helpers::value;
+
+use prometheus::core::Collector;
+
+use std::time::Duration;
+
+const STATS: &str = include_str!("fixtures/dump1090/stats.json");
+
+fn stats_json() -> StatsJson {
+    StatsJson::new(fetcher("stats.json"), 1090, Duration::from_secs(60))
+}
+
+#[test]
+fn test_update_stats_fixture() {
+    let stats_json = stats_json();
+    let metrics = StatsMetrics::new("roof", "1090").unwrap();
+
+    stats_json
+        .update_stats(&metrics, serde_json::from_str(STATS).unwrap())
+        .unwrap();
+
+    let families = metrics.collect();
+    let value = |name, labels: &[(&str, &str)]| value(&families, name, labels);
+
+    assert_eq!(Some(8650000.0), value("adsb_stats_messages_total", &[]));
+    assert_eq!(
+        Some(4140000.0),
+        value(
+            "adsb_stats_messages_by_df_total",
+            &[("downlink_format", "4")]
+        )
+    );
+    assert_eq!(
+        Some(62800.0),
+        value(
+            "adsb_stats_adaptive_gain_seconds_total",
+            &[("gain_dB", "42.1")]
+        )
+    );
+    assert_eq!(Some(42.1), value("adsb_stats_adaptive_gain_dB", &[]));
+    assert_eq!(
+        Some(1950000.0),
+        value("adsb_stats_cpr_global_ok_total", &[])
+    );
+    assert_eq!(
+        Some(1175.0),
+        value("adsb_stats_cpu_demodulation_seconds_total", &[])
+    );
+    assert_eq!(
+        Some(150000.0),
+        value(
+            "adsb_stats_local_modes_accepted_total",
+            &[("corrections", "1")]
+        )
+    );
+    assert_eq!(Some(8600.0), value("adsb_stats_tracks_total", &[]));
+
+    // Signal levels are from the last minute
+    assert_eq!(Some(-19.2), value("adsb_stats_local_signal_dbfs", &[]));
+    assert_eq!(Some(-33.4), value("adsb_stats_local_noise_dbfs", &[]));
+
+    assert_eq!(
+        Some("roof"),
+        families[0]
+            .get_metric()
+            .first()
+            .and_then(|m| m.get_label().iter().find(|l| l.get_name() == "receiver"))
+            .map(|l| l.get_value())
+    );
+}
+
+#[test]
+fn test_update_stats_fixture_repeated() {
+    let stats_json = stats_json();
+    let metrics = StatsMetrics::new("roof", "1090").unwrap();
+
+    // Counters follow the totals in stats.json rather than adding them again
+    for _ in 0..2 {
+        stats_json
+            .update_stats(&metrics, serde_json::from_str(STATS).unwrap())
+            .unwrap();
+    }
+
+    let families = metrics.collect();
+
+    assert_eq!(
+        Some(8650000.0),
+        value(&families, "adsb_stats_messages_total", &[])
+    );
+    assert_eq!(
+        Some(1175.0),
+        value(&families, "adsb_stats_cpu_demodulation_seconds_total", &[])
+    );
+}
+
+#[test]
+fn test_update_stats_missing_total() {
+    let metrics = StatsMetrics::new("roof", "1090").unwrap();
+
+    assert!(stats_json()
+        .update_stats(&metrics, serde_json::json!({ "last1min": {} }))
+        .is_err());
+}
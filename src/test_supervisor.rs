@@ -0,0 +1,137 @@
+use crate::supervisor::*;
+use crate::test_helpers::value;
+
+use prometheus::Registry;
+
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::sync::mpsc;
+use tokio::time::sleep;
+
+#[tokio::test]
+async fn test_supervise_restarts() {
+    tokio::time::pause();
+
+    let registry = Registry::new();
+    let metrics = TaskMetrics::new(&registry).unwrap();
+    let starts = Arc::new(AtomicUsize::new(0));
+    let counter = starts.clone();
+
+    let (error_tx, _error_rx) = mpsc::channel(1);
+
+    let task = supervise("test::restart".to_string(), &metrics, error_tx, move || {
+        let starts = counter.clone();
+
+        async move {
+            if starts.fetch_add(1, Ordering::SeqCst) == 0 {
+                panic!("first run fails");
+            }
+
+            std::future::pending::<()>().await
+        }
+    });
+
+    // The first restart waits for the initial backoff of one second
+    sleep(Duration::from_millis(1500)).await;
+
+    assert_eq!(2, starts.load(Ordering::SeqCst));
+    assert_eq!(
+        Some(1.0),
+        value(
+            &registry.gather(),
+            "adsb_task_up",
+            &[("task", "test::restart")]
+        )
+    );
+    assert_eq!(
+        Some(1.0),
+        value(
+            &registry.gather(),
+            "adsb_task_restarts_total",
+            &[("task", "test::restart")]
+        )
+    );
+
+    task.stop();
+
+    assert_eq!(
+        None,
+        value(
+            &registry.gather(),
+            "adsb_task_up",
+            &[("task", "test::restart")]
+        )
+    );
+}
+
+#[tokio::test]
+async fn test_supervise_abandons() {
+    tokio::time::pause();
+
+    let registry = Registry::new();
+    let metrics = TaskMetrics::new(&registry).unwrap();
+    let starts = Arc::new(AtomicUsize::new(0));
+    let counter = starts.clone();
+
+    let (error_tx, mut error_rx) = mpsc::channel(1);
+
+    let failing = supervise(
+        "test::failing".to_string(),
+        &metrics,
+        error_tx.clone(),
+        move || {
+            counter.fetch_add(1, Ordering::SeqCst);
+
+            async move { panic!("always fails") }
+        },
+    );
+
+    let running = supervise("test::running".to_string(), &metrics, error_tx, || async {
+        std::future::pending::<()>().await
+    });
+
+    // Backoff of 1, 2, 4, 8, 16, 32 then 60 seconds between the ten failures
+    sleep(Duration::from_secs(600)).await;
+
+    assert_eq!(10, starts.load(Ordering::SeqCst));
+    assert_eq!(
+        Some(0.0),
+        value(
+            &registry.gather(),
+            "adsb_task_up",
+            &[("task", "test::failing")]
+        )
+    );
+    assert_eq!(
+        Some(9.0),
+        value(
+            &registry.gather(),
+            "adsb_task_restarts_total",
+            &[("task", "test::failing")]
+        )
+    );
+
+    // The last failure is fatal
+    let error = error_rx.try_recv().unwrap();
+
+    assert_eq!(
+        "giving up on test::failing after 10 failures in a row: test::failing panicked: always fails",
+        format!("{:#}", error)
+    );
+
+    // Other tasks keep running until the exporter stops
+    assert_eq!(
+        Some(1.0),
+        value(
+            &registry.gather(),
+            "adsb_task_up",
+            &[("task", "test::running")]
+        )
+    );
+
+    failing.stop();
+    running.stop();
+}